@@ -0,0 +1,13 @@
+fn main() {
+    // grpc feature的消息类型和服务由proto/bilive_danmaku.proto生成
+    #[cfg(feature = "grpc")]
+    {
+        // 使用随crate分发的protoc，不要求系统安装
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("找不到内置的protoc");
+        std::env::set_var("PROTOC", protoc);
+        tonic_build::configure()
+            .build_client(false)
+            .compile_protos(&["proto/bilive_danmaku.proto"], &["proto"])
+            .expect("生成gRPC代码失败");
+    }
+}
@@ -28,10 +28,10 @@ async fn tokio_main() {
     while let Some(maybe_evt) = stream.next().await {
         match maybe_evt {
             Ok(evt) => {
-                log::info!("{:?}", evt);
+                tracing::info!("{:?}", evt);
             }
             Err(e) => {
-                log::warn!("{:?}", e);
+                tracing::warn!("{:?}", e);
             }
         }
     }
@@ -0,0 +1,81 @@
+use serde::Deserialize;
+
+use crate::{http::HttpClient, InitError};
+
+/// 主播信息
+///
+/// # 说明
+/// - `follower_num` 粉丝数
+/// - `room_id` 直播间号
+/// - `level` 主播等级
+/// - `medal_name` 粉丝牌名称，没有时为空字符串
+#[derive(Debug, Clone)]
+pub struct AnchorInfo {
+    pub uid: u64,
+    pub uname: String,
+    pub face: String,
+    pub follower_num: u64,
+    pub room_id: u64,
+    pub level: u64,
+    pub medal_name: String,
+}
+
+///
+/// api url:
+/// https://api.live.bilibili.com/live_user/v1/Master/info?uid=9617619
+#[derive(Debug, Deserialize)]
+struct MasterInfoData {
+    info: MasterInfoUser,
+    exp: MasterInfoExp,
+    follower_num: u64,
+    room_id: u64,
+    #[serde(default)]
+    medal_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MasterInfoUser {
+    uid: u64,
+    uname: String,
+    face: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MasterInfoExp {
+    master_level: MasterLevel,
+}
+
+#[derive(Debug, Deserialize)]
+struct MasterLevel {
+    level: u64,
+}
+
+impl From<MasterInfoData> for AnchorInfo {
+    fn from(data: MasterInfoData) -> Self {
+        AnchorInfo {
+            uid: data.info.uid,
+            uname: data.info.uname,
+            face: data.info.face,
+            follower_num: data.follower_num,
+            room_id: data.room_id,
+            level: data.exp.master_level.level,
+            medal_name: data.medal_name,
+        }
+    }
+}
+
+impl HttpClient {
+    pub async fn anchor_info(&self, uid: u64) -> Result<AnchorInfo, InitError> {
+        let url = format!(
+            "https://api.live.bilibili.com/live_user/v1/Master/info?uid={}",
+            uid
+        );
+        self.get_data::<MasterInfoData>(url).await.map(Into::into)
+    }
+
+    /// 先查询直播间的主播uid，再查询主播信息
+    pub async fn anchor_info_by_room(&self, roomid: u64) -> Result<AnchorInfo, InitError> {
+        let uid = self.room_info(roomid).await?.uid;
+        self.anchor_info(uid).await
+    }
+}
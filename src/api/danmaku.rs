@@ -0,0 +1,148 @@
+use serde::{de::IgnoredAny, Deserialize};
+use serde_json::Value;
+
+use crate::{
+    cmd::medal_from_array,
+    credential::Credential,
+    event::{now_millis, DanmakuEvent, Event},
+    http::HttpClient,
+    model::{DanmakuMessage, Emoticon, User},
+    InitError,
+};
+
+///
+/// api url:
+/// https://api.live.bilibili.com/xlive/web-room/v1/dM/gethistory?roomid=510&room_type=0
+#[derive(Debug, Deserialize)]
+pub(crate) struct HistoryData {
+    #[serde(default)]
+    room: Vec<HistoryDanmaku>,
+}
+
+impl HistoryData {
+    pub(crate) fn into_events(self) -> Vec<Event> {
+        self.room.into_iter().map(Event::from).collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryDanmaku {
+    text: String,
+    uid: u64,
+    nickname: String,
+    #[serde(default)]
+    medal: Value,
+    #[serde(default)]
+    user_level: Value,
+    #[serde(default)]
+    guard_level: u64,
+    #[serde(default)]
+    check_info: Option<HistoryCheckInfo>,
+    #[serde(default)]
+    emoticon: Option<HistoryEmoticon>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryCheckInfo {
+    ts: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryEmoticon {
+    #[serde(default)]
+    emoticon_unique: String,
+    #[serde(default)]
+    url: String,
+    #[serde(default)]
+    height: u64,
+    #[serde(default)]
+    width: u64,
+}
+
+impl From<HistoryDanmaku> for Event {
+    fn from(history: HistoryDanmaku) -> Self {
+        let message = match history.emoticon {
+            Some(emoticon) if !emoticon.emoticon_unique.is_empty() => DanmakuMessage::Emoticon {
+                emoticon: Emoticon {
+                    unique_id: emoticon.emoticon_unique,
+                    height: emoticon.height,
+                    width: emoticon.width,
+                    url: emoticon.url,
+                },
+                alt_message: history.text,
+            },
+            _ => DanmakuMessage::Plain {
+                message: history.text,
+            },
+        };
+        let danmaku = DanmakuEvent {
+            flag: 0,
+            message,
+            user: User {
+                uid: history.uid,
+                uname: history.nickname,
+                face: None,
+            },
+            fans_medal: medal_from_array(&history.medal),
+            guard_level: history.guard_level,
+            user_level: history.user_level[0].as_u64().unwrap_or_default(),
+            mode: 1,
+            font_size: 25,
+            color: 0xFFFFFF,
+        };
+        Event {
+            data: danmaku.into(),
+            timestamp: now_millis(),
+            server_timestamp: history.check_info.map(|info| info.ts * 1000),
+            raw: None,
+        }
+    }
+}
+
+impl HttpClient {
+    /// 直播间最近的弹幕，按发送时间从早到晚排列，通常为最近10条
+    ///
+    /// `server_timestamp`为弹幕的发送时间，精确到秒
+    pub async fn danmaku_history(&self, roomid: u64) -> Result<Vec<Event>, InitError> {
+        let url = format!(
+            "https://api.live.bilibili.com/xlive/web-room/v1/dM/gethistory?roomid={}&room_type=0",
+            roomid
+        );
+        let data: HistoryData = self.get_data(url).await?;
+        Ok(data.into_events())
+    }
+
+    /// 以`credential`的身份在直播间发送一条弹幕
+    ///
+    /// 长度和频率限制由调用者处理，见[`DanmakuSender`](crate::sender::DanmakuSender)
+    ///
+    /// api url:
+    /// https://api.live.bilibili.com/msg/send
+    pub async fn send_danmaku(
+        &self,
+        credential: &Credential,
+        roomid: u64,
+        message: &str,
+    ) -> Result<(), InitError> {
+        let rnd = (now_millis() / 1000).to_string();
+        let roomid = roomid.to_string();
+        let form = [
+            ("bubble", "0"),
+            ("msg", message),
+            ("color", "16777215"),
+            ("mode", "1"),
+            ("fontsize", "25"),
+            ("rnd", &rnd),
+            ("roomid", &roomid),
+            ("csrf", &credential.bili_jct),
+            ("csrf_token", &credential.bili_jct),
+        ];
+        let request = self
+            .client
+            .post("https://api.live.bilibili.com/msg/send")
+            .header("Cookie", credential.cookie())
+            .form(&form);
+        self.send_data::<IgnoredAny>(request).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,192 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use futures_util::lock::Mutex;
+use serde::Deserialize;
+
+use crate::{
+    event::{now_millis, Event, EventData},
+    http::HttpClient,
+    model::{CoinType, Gift},
+    InitError,
+};
+
+/// 礼物配置中的一种礼物
+///
+/// # 说明
+/// - `price` 单价，单位为金瓜子或银瓜子，1000金瓜子为1元
+/// - `icon` 礼物图标的地址
+#[derive(Debug, Clone)]
+pub struct GiftInfo {
+    pub id: u64,
+    pub name: String,
+    pub price: u64,
+    pub coin_type: CoinType,
+    pub icon: String,
+}
+
+///
+/// api url:
+/// https://api.live.bilibili.com/xlive/web-room/v1/giftPanel/giftConfig?platform=pc&room_id=510
+#[derive(Debug, Deserialize)]
+struct GiftConfigData {
+    #[serde(default)]
+    list: Vec<GiftConfigItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GiftConfigItem {
+    id: u64,
+    name: String,
+    price: u64,
+    coin_type: CoinType,
+    #[serde(default)]
+    img_basic: String,
+}
+
+impl HttpClient {
+    /// 礼物配置，`roomid`为0时只有全站通用的礼物
+    pub async fn gift_config(&self, roomid: u64) -> Result<Vec<GiftInfo>, InitError> {
+        let url = format!(
+            "https://api.live.bilibili.com/xlive/web-room/v1/giftPanel/giftConfig?platform=pc&room_id={}",
+            roomid
+        );
+        let data: GiftConfigData = self.get_data(url).await?;
+        Ok(data
+            .list
+            .into_iter()
+            .map(|item| GiftInfo {
+                id: item.id,
+                name: item.name,
+                price: item.price,
+                coin_type: item.coin_type,
+                icon: item.img_basic,
+            })
+            .collect())
+    }
+}
+
+/// 带有过期时间的礼物配置缓存，克隆后共享同一份缓存
+///
+/// # 说明
+/// - `roomid` 获取哪个直播间的礼物配置，0为全站通用的礼物
+/// - `ttl` 缓存的有效期，过期后下一次查询时重新获取，默认1小时
+///```no_run,ignore
+///let gifts = GiftConfigCache::new(roomid);
+///while let Some(Ok(mut event)) = stream.next().await {
+///    gifts.enrich(&mut event).await?;
+///}
+///```
+#[derive(Debug, Clone)]
+pub struct GiftConfigCache {
+    pub roomid: u64,
+    pub ttl: Duration,
+    client: HttpClient,
+    cache: Arc<Mutex<GiftCache>>,
+}
+
+/// 获取失败后，在这段时间内不再重新获取，继续使用旧的缓存
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Default)]
+struct GiftCache {
+    gifts: HashMap<u64, GiftInfo>,
+    /// 上一次获取的时间，还没有获取过时为`None`
+    fetched_at: Option<u64>,
+    /// 上一次获取失败的时间，获取成功后清除
+    failed_at: Option<u64>,
+}
+
+impl GiftConfigCache {
+    pub fn new(roomid: u64) -> Self {
+        Self {
+            roomid,
+            ttl: Duration::from_secs(3600),
+            client: HttpClient::shared().clone(),
+            cache: Arc::default(),
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn with_client(mut self, client: HttpClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// 查询礼物，缓存过期时先重新获取
+    ///
+    /// 获取失败时返回错误，之后的一分钟内不再重新获取，查询使用旧的缓存，还没有获取成功过时返回`None`
+    pub async fn get(&self, gift_id: u64) -> Result<Option<GiftInfo>, InitError> {
+        let mut cache = self.cache.lock().await;
+        let now = now_millis();
+        let expired = cache
+            .fetched_at
+            .is_none_or(|fetched_at| now.saturating_sub(fetched_at) > self.ttl.as_millis() as u64);
+        let backoff = cache.failed_at.is_some_and(|failed_at| {
+            now.saturating_sub(failed_at) < RETRY_INTERVAL.as_millis() as u64
+        });
+        if expired && !backoff {
+            self.fetch(&mut cache).await?;
+        }
+        Ok(cache.gifts.get(&gift_id).cloned())
+    }
+
+    /// 立即重新获取，不受获取失败后的等待时间限制
+    pub async fn refresh(&self) -> Result<(), InitError> {
+        let mut cache = self.cache.lock().await;
+        self.fetch(&mut cache).await
+    }
+
+    async fn fetch(&self, cache: &mut GiftCache) -> Result<(), InitError> {
+        let gifts = match self.client.gift_config(self.roomid).await {
+            Ok(gifts) => gifts,
+            Err(e) => {
+                cache.failed_at = Some(now_millis());
+                return Err(e);
+            }
+        };
+        tracing::debug!(roomid = self.roomid, count = gifts.len(), "礼物配置已更新");
+        cache.gifts = gifts.into_iter().map(|gift| (gift.id, gift)).collect();
+        cache.fetched_at = Some(now_millis());
+        cache.failed_at = None;
+        Ok(())
+    }
+
+    /// 补全礼物事件中缺少的礼物名称和单价，不是礼物事件或者找不到礼物时不修改
+    ///
+    /// 只有消息中没有礼物名称时才查询，有名称而单价为0的是免费礼物
+    pub async fn enrich(&self, event: &mut Event) -> Result<(), InitError> {
+        let (gift, blindbox) = match &mut event.data {
+            EventData::GiftEvent(e) => (&mut e.gift, e.blindbox.as_mut()),
+            EventData::BlindboxGiftEvent(e) => (&mut e.gift, Some(&mut e.blindbox_gift_type)),
+            _ => return Ok(()),
+        };
+        if gift.gift_name.is_empty() {
+            if let Some(info) = self.get(gift.gift_id).await? {
+                fill_gift(gift, &info);
+            }
+        }
+        if let Some(blindbox) = blindbox.filter(|blindbox| blindbox.gift_name.is_empty()) {
+            if let Some(info) = self.get(blindbox.gift_id).await? {
+                blindbox.gift_name = info.name;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn fill_gift(gift: &mut Gift, info: &GiftInfo) {
+    if gift.gift_name.is_empty() {
+        gift.gift_name = info.name.clone();
+    }
+    if gift.price == 0 {
+        gift.price = info.price;
+        gift.coin_type = info.coin_type;
+    }
+    if gift.coin_count == 0 {
+        gift.coin_count = gift.price * gift.num;
+    }
+}
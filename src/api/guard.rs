@@ -0,0 +1,157 @@
+use serde::Deserialize;
+
+use crate::{http::HttpClient, InitError};
+
+/// 大航海列表中的一个用户
+///
+/// # 说明
+/// - `rank` 从1开始的名次
+/// - `guard_level` 1，2，3分别为总督，提督，舰长
+/// - `accompany` 陪伴天数
+/// - `medal_name` 粉丝牌名称，没有佩戴时为空字符串
+#[derive(Debug, Clone)]
+pub struct GuardEntry {
+    pub uid: u64,
+    pub uname: String,
+    pub face: String,
+    pub rank: u64,
+    pub guard_level: u64,
+    pub accompany: u64,
+    pub medal_name: String,
+    pub medal_level: u64,
+}
+
+/// 大航海列表的一页
+///
+/// # 说明
+/// - `total` 大航海总人数
+/// - `page` 当前页码，从1开始，`page_count` 总页数
+/// - `entries` 第一页包含前三名
+#[derive(Debug, Clone)]
+pub struct GuardListPage {
+    pub total: u64,
+    pub page: u64,
+    pub page_count: u64,
+    pub entries: Vec<GuardEntry>,
+}
+
+///
+/// api url:
+/// https://api.live.bilibili.com/xlive/app-room/v2/guardTab/topListNew?roomid=510&ruid=9617619&page=1&page_size=20&typ=5
+#[derive(Debug, Deserialize)]
+pub(crate) struct GuardListData {
+    info: GuardListInfo,
+    #[serde(default)]
+    top3: Vec<GuardListItem>,
+    #[serde(default)]
+    list: Vec<GuardListItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GuardListInfo {
+    num: u64,
+    page: u64,
+    now: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct GuardListItem {
+    rank: u64,
+    #[serde(default)]
+    accompany: u64,
+    uinfo: GuardListUser,
+}
+
+#[derive(Debug, Deserialize)]
+struct GuardListUser {
+    uid: u64,
+    base: GuardListUserBase,
+    medal: Option<GuardListMedal>,
+    guard: Option<GuardListGuard>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GuardListUserBase {
+    name: String,
+    face: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GuardListMedal {
+    name: String,
+    level: u64,
+    #[serde(default)]
+    guard_level: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct GuardListGuard {
+    level: u64,
+}
+
+impl From<GuardListItem> for GuardEntry {
+    fn from(item: GuardListItem) -> Self {
+        let GuardListUser {
+            uid,
+            base,
+            medal,
+            guard,
+        } = item.uinfo;
+        let guard_level = guard
+            .map(|g| g.level)
+            .or_else(|| medal.as_ref().map(|m| m.guard_level))
+            .unwrap_or_default();
+        let (medal_name, medal_level) = medal.map(|m| (m.name, m.level)).unwrap_or_default();
+        GuardEntry {
+            uid,
+            uname: base.name,
+            face: base.face,
+            rank: item.rank,
+            guard_level,
+            accompany: item.accompany,
+            medal_name,
+            medal_level,
+        }
+    }
+}
+
+impl GuardListData {
+    /// 只有第一页包含前三名，有的接口在每一页都会返回`top3`
+    pub(crate) fn into_page(self, page: u64) -> GuardListPage {
+        let GuardListData { info, top3, list } = self;
+        let top3 = if page == 1 { top3 } else { vec![] };
+        GuardListPage {
+            total: info.num,
+            page: info.now,
+            page_count: info.page,
+            entries: top3.into_iter().chain(list).map(Into::into).collect(),
+        }
+    }
+}
+
+impl HttpClient {
+    /// 大航海列表的第`page`页，`ruid`为主播的uid，每页20人
+    pub async fn guard_list_page(
+        &self,
+        roomid: u64,
+        ruid: u64,
+        page: u64,
+    ) -> Result<GuardListPage, InitError> {
+        let url = format!(
+            "https://api.live.bilibili.com/xlive/app-room/v2/guardTab/topListNew?roomid={}&ruid={}&page={}&page_size=20&typ=5",
+            roomid, ruid, page
+        );
+        let data: GuardListData = self.get_data(url).await?;
+        Ok(data.into_page(page))
+    }
+
+    /// 依次请求所有页，返回完整的大航海列表
+    pub async fn guard_list(&self, roomid: u64, ruid: u64) -> Result<Vec<GuardEntry>, InitError> {
+        let mut page = self.guard_list_page(roomid, ruid, 1).await?;
+        let mut entries = std::mem::take(&mut page.entries);
+        for index in 2..=page.page_count {
+            entries.extend(self.guard_list_page(roomid, ruid, index).await?.entries);
+        }
+        Ok(entries)
+    }
+}
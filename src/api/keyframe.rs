@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use crate::{event::now_millis, http::HttpClient, model::LiveStatus, InitError};
+
+/// 直播画面的关键帧截图
+///
+/// # 说明
+/// - `url` 关键帧截图的地址，未开播时可能为空字符串或者上一场直播的截图
+/// - `cover` 直播间封面
+/// - `timestamp` 获取时的本地毫秒时间戳
+#[derive(Debug, Clone)]
+pub struct Keyframe {
+    pub roomid: u64,
+    pub url: String,
+    pub cover: String,
+    pub live_status: LiveStatus,
+    pub timestamp: u64,
+}
+
+impl HttpClient {
+    /// 当前的关键帧截图，来自[`room_info`](Self::room_info)
+    pub async fn keyframe(&self, roomid: u64) -> Result<Keyframe, InitError> {
+        let info = self.room_info(roomid).await?;
+        Ok(Keyframe {
+            roomid: info.room_id,
+            url: info.keyframe,
+            cover: info.user_cover,
+            live_status: info.live_status,
+            timestamp: now_millis(),
+        })
+    }
+
+    /// 每隔`interval`获取一次关键帧，只在截图地址变化时产生，出错时产生错误后继续
+    ///```no_run,ignore
+    ///let mut keyframes = std::pin::pin!(client.keyframes(roomid, Duration::from_secs(60)));
+    ///while let Some(keyframe) = keyframes.next().await {
+    ///    let keyframe = keyframe?;
+    ///    std::fs::write(format!("{}.jpg", keyframe.timestamp), client.download(&keyframe.url).await?)?;
+    ///}
+    ///```
+    pub fn keyframes(
+        &self,
+        roomid: u64,
+        interval: Duration,
+    ) -> impl futures_util::Stream<Item = Result<Keyframe, InitError>> {
+        let client = self.clone();
+        futures_util::stream::unfold(
+            (client, None::<String>, true),
+            move |(client, mut last_url, mut first)| async move {
+                loop {
+                    if !first {
+                        crate::connection::sleep(interval).await;
+                    }
+                    first = false;
+                    match client.keyframe(roomid).await {
+                        Ok(keyframe) if last_url.as_ref() == Some(&keyframe.url) => continue,
+                        Ok(keyframe) => {
+                            last_url = Some(keyframe.url.clone());
+                            return Some((Ok(keyframe), (client, last_url, first)));
+                        }
+                        Err(e) => return Some((Err(e), (client, last_url, first))),
+                    }
+                }
+            },
+        )
+    }
+
+    /// 下载截图或封面等文件
+    pub async fn download(&self, url: &str) -> Result<Vec<u8>, InitError> {
+        let resp = self.send(self.client.get(url)).await?;
+        Ok(resp.bytes().await?.to_vec())
+    }
+}
@@ -0,0 +1,74 @@
+use serde::Deserialize;
+
+use crate::{http::HttpClient, InitError};
+
+/// 粉丝团排行中的一个用户
+///
+/// # 说明
+/// - `rank` 从1开始的名次
+/// - `score` 亲密度
+/// - `guard_level` 1，2，3分别为总督，提督，舰长，0为无
+#[derive(Debug, Clone, Deserialize)]
+pub struct FansMedalRankEntry {
+    pub uid: u64,
+    #[serde(rename = "name")]
+    pub uname: String,
+    #[serde(default)]
+    pub face: String,
+    pub rank: u64,
+    #[serde(default)]
+    pub score: u64,
+    #[serde(default)]
+    pub medal_name: String,
+    #[serde(rename = "level", default)]
+    pub medal_level: u64,
+    #[serde(default)]
+    pub guard_level: u64,
+}
+
+/// 粉丝团排行的一页
+///
+/// # 说明
+/// - `total` 粉丝团总人数
+#[derive(Debug, Clone)]
+pub struct FansMedalRankPage {
+    pub total: u64,
+    pub page: u64,
+    pub entries: Vec<FansMedalRankEntry>,
+}
+
+///
+/// api url:
+/// https://api.live.bilibili.com/xlive/general-interface/v1/rank/getFansMembersRank?ruid=9617619&page=1&page_size=30
+#[derive(Debug, Deserialize)]
+pub(crate) struct FansMembersRankData {
+    num: u64,
+    #[serde(default)]
+    item: Vec<FansMedalRankEntry>,
+}
+
+impl FansMembersRankData {
+    pub(crate) fn into_page(self, page: u64) -> FansMedalRankPage {
+        FansMedalRankPage {
+            total: self.num,
+            page,
+            entries: self.item,
+        }
+    }
+}
+
+impl HttpClient {
+    /// 粉丝团亲密度排行的第`page`页，`ruid`为主播的uid，每页30人
+    pub async fn fans_medal_rank(
+        &self,
+        ruid: u64,
+        page: u64,
+    ) -> Result<FansMedalRankPage, InitError> {
+        let url = format!(
+            "https://api.live.bilibili.com/xlive/general-interface/v1/rank/getFansMembersRank?ruid={}&page={}&page_size=30",
+            ruid, page
+        );
+        let data: FansMembersRankData = self.get_data(url).await?;
+        Ok(data.into_page(page))
+    }
+}
@@ -0,0 +1,34 @@
+//! b站直播相关的http接口
+//!
+//! 接口都实现为[`HttpClient`](crate::http::HttpClient)的方法，和[`Connector`](crate::Connector)共享限流
+//!```no_run,ignore
+//!use bilive_danmaku::http::HttpClient;
+//!let info = HttpClient::shared().room_info(851181).await?;
+//!println!("{}: {}", info.title, info.live_status);
+//!```
+mod anchor;
+mod danmaku;
+mod gift;
+mod guard;
+mod keyframe;
+mod medal;
+mod play_url;
+mod room;
+mod super_chat;
+mod user;
+pub use anchor::*;
+#[cfg(test)]
+pub(crate) use danmaku::HistoryData;
+#[cfg(test)]
+pub(crate) use gift::fill_gift;
+pub use gift::*;
+#[cfg(test)]
+pub(crate) use guard::GuardListData;
+pub use guard::*;
+pub use keyframe::*;
+#[cfg(test)]
+pub(crate) use medal::FansMembersRankData;
+pub use medal::*;
+pub use play_url::*;
+pub use room::*;
+pub use user::*;
@@ -0,0 +1,127 @@
+use serde::Deserialize;
+
+use crate::{http::HttpClient, InitError};
+
+/// 直播流地址
+///
+/// # 说明
+/// - `protocol` `http_stream`或`http_hls`
+/// - `format` `flv`，`ts`或`fmp4`
+/// - `codec` `avc`或`hevc`
+/// - `qn` 当前画质，`accept_qn` 可选的画质
+/// - `urls` 完整的播放地址，来自不同的cdn
+#[derive(Debug, Clone)]
+pub struct PlayUrl {
+    pub protocol: String,
+    pub format: String,
+    pub codec: String,
+    pub qn: u32,
+    pub accept_qn: Vec<u32>,
+    pub urls: Vec<String>,
+}
+
+/// 画质，比如`10000`为原画，`400`为蓝光
+#[derive(Debug, Clone, Deserialize)]
+pub struct Quality {
+    pub qn: u32,
+    pub desc: String,
+}
+
+/// 未开播时，`qualities`和`streams`都为空
+#[derive(Debug, Clone, Default)]
+pub struct PlayInfo {
+    pub qualities: Vec<Quality>,
+    pub streams: Vec<PlayUrl>,
+}
+
+///
+/// api url:
+/// https://api.live.bilibili.com/xlive/web-room/v2/index/getRoomPlayInfo?room_id=510&protocol=0,1&format=0,1,2&codec=0,1&qn=10000&platform=web&ptype=8
+#[derive(Debug, Deserialize)]
+struct PlayInfoData {
+    playurl_info: Option<PlayUrlInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayUrlInfo {
+    playurl: PlayUrlData,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayUrlData {
+    #[serde(default)]
+    g_qn_desc: Vec<Quality>,
+    #[serde(default)]
+    stream: Vec<StreamData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamData {
+    protocol_name: String,
+    format: Vec<FormatData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FormatData {
+    format_name: String,
+    codec: Vec<CodecData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CodecData {
+    codec_name: String,
+    current_qn: u32,
+    accept_qn: Vec<u32>,
+    base_url: String,
+    url_info: Vec<UrlInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UrlInfo {
+    host: String,
+    extra: String,
+}
+
+impl From<PlayUrlData> for PlayInfo {
+    fn from(data: PlayUrlData) -> Self {
+        let mut streams = vec![];
+        for stream in data.stream {
+            for format in stream.format {
+                for codec in format.codec {
+                    let urls = codec
+                        .url_info
+                        .iter()
+                        .map(|info| format!("{}{}{}", info.host, codec.base_url, info.extra))
+                        .collect();
+                    streams.push(PlayUrl {
+                        protocol: stream.protocol_name.clone(),
+                        format: format.format_name.clone(),
+                        codec: codec.codec_name,
+                        qn: codec.current_qn,
+                        accept_qn: codec.accept_qn,
+                        urls,
+                    })
+                }
+            }
+        }
+        PlayInfo {
+            qualities: data.g_qn_desc,
+            streams,
+        }
+    }
+}
+
+impl HttpClient {
+    /// 获取直播流地址，`qn`为期望的画质，服务器不一定满足
+    pub async fn play_info(&self, roomid: u64, qn: u32) -> Result<PlayInfo, InitError> {
+        let url = format!(
+            "https://api.live.bilibili.com/xlive/web-room/v2/index/getRoomPlayInfo?room_id={}&protocol=0,1&format=0,1,2&codec=0,1&qn={}&platform=web&ptype=8",
+            roomid, qn
+        );
+        let data = self.get_data::<PlayInfoData>(url).await?;
+        Ok(data
+            .playurl_info
+            .map(|info| info.playurl.into())
+            .unwrap_or_default())
+    }
+}
@@ -0,0 +1,68 @@
+use serde::Deserialize;
+
+use crate::{http::HttpClient, model::LiveStatus, InitError};
+
+///
+/// api url:
+/// https://api.live.bilibili.com/room/v1/Room/get_info?room_id=510
+///
+/// # 说明
+/// - `room_id` 真实房间号，`short_id` 短号，没有短号时为0
+/// - `online` 人气值
+/// - `live_time` 本次开播时间，格式为`2022-05-01 20:00:00`，未开播时为`None`
+#[derive(Debug, Clone, Deserialize)]
+pub struct RoomInfo {
+    pub uid: u64,
+    pub room_id: u64,
+    pub short_id: u64,
+    pub title: String,
+    pub description: String,
+    pub live_status: LiveStatus,
+    pub online: u64,
+    pub attention: u64,
+    pub area_id: u64,
+    pub area_name: String,
+    pub parent_area_id: u64,
+    pub parent_area_name: String,
+    pub user_cover: String,
+    pub keyframe: String,
+    #[serde(deserialize_with = "deser_live_time")]
+    pub live_time: Option<String>,
+}
+
+fn deser_live_time<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?
+        .filter(|time| !time.is_empty() && !time.starts_with("0000")))
+}
+
+///
+/// api url:
+/// https://api.live.bilibili.com/room/v1/Room/getRoomInfoOld?mid=9617619
+#[derive(Debug, Deserialize)]
+struct RoomInfoOldData {
+    roomid: u64,
+}
+
+impl HttpClient {
+    pub async fn room_info(&self, roomid: u64) -> Result<RoomInfo, InitError> {
+        let url = format!(
+            "https://api.live.bilibili.com/room/v1/Room/get_info?room_id={}",
+            roomid
+        );
+        self.get_data(url).await
+    }
+
+    /// 主播uid对应的直播间号，没有开通直播间时返回[`InitError::NoLiveRoom`]
+    pub async fn room_id_by_uid(&self, uid: u64) -> Result<u64, InitError> {
+        let url = format!(
+            "https://api.live.bilibili.com/room/v1/Room/getRoomInfoOld?mid={}",
+            uid
+        );
+        match self.get_data::<RoomInfoOldData>(url).await?.roomid {
+            0 => Err(InitError::NoLiveRoom { uid }),
+            roomid => Ok(roomid),
+        }
+    }
+}
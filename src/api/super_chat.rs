@@ -0,0 +1,79 @@
+use serde::Deserialize;
+
+use crate::{
+    event::{now_millis, Event, SuperChatEvent},
+    http::HttpClient,
+    model::{FansMedal, SuperChatUser, User},
+    InitError,
+};
+
+///
+/// api url:
+/// https://api.live.bilibili.com/av/v1/SuperChat/getMessageList?room_id=510
+#[derive(Debug, Deserialize)]
+struct SuperChatListData {
+    #[serde(default)]
+    list: Option<Vec<SuperChatItem>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SuperChatItem {
+    id: u64,
+    uid: u64,
+    price: u64,
+    message: String,
+    #[serde(default)]
+    message_trans: String,
+    #[serde(default)]
+    medal_info: serde_json::Value,
+    user_info: SuperChatUser,
+    #[serde(default)]
+    start_time: u64,
+}
+
+impl From<SuperChatItem> for Event {
+    fn from(item: SuperChatItem) -> Self {
+        let super_chat = SuperChatEvent {
+            id: item.id,
+            user: User {
+                uid: item.uid,
+                uname: item.user_info.uname,
+                face: Some(item.user_info.face),
+            },
+            // 没有佩戴粉丝牌时可能是null、空对象或者名称为空
+            fans_medal: serde_json::from_value::<FansMedal>(item.medal_info)
+                .ok()
+                .filter(|medal| !medal.medal_name.is_empty()),
+            price: item.price,
+            message: item.message,
+            message_jpn: Some(item.message_trans).filter(|s| !s.is_empty()),
+        };
+        Event {
+            data: super_chat.into(),
+            timestamp: now_millis(),
+            server_timestamp: Some(item.start_time * 1000).filter(|ts| *ts > 0),
+            raw: None,
+        }
+    }
+}
+
+impl HttpClient {
+    /// 直播间中仍在展示的醒目留言，按发送时间从早到晚排列，与直播中收到的[`SuperChatEvent`]相同
+    ///
+    /// `server_timestamp`为醒目留言的开始时间，精确到秒
+    pub async fn super_chat_history(&self, roomid: u64) -> Result<Vec<Event>, InitError> {
+        let url = format!(
+            "https://api.live.bilibili.com/av/v1/SuperChat/getMessageList?room_id={}",
+            roomid
+        );
+        let data: SuperChatListData = self.get_data(url).await?;
+        let mut events: Vec<Event> = data
+            .list
+            .unwrap_or_default()
+            .into_iter()
+            .map(Event::from)
+            .collect();
+        events.sort_by_key(|event| event.server_timestamp);
+        Ok(events)
+    }
+}
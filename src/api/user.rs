@@ -0,0 +1,250 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use futures_util::lock::Mutex;
+use serde::Deserialize;
+
+use crate::{event::Event, http::HttpClient, InitError};
+
+/// 用户的基本资料
+///
+/// # 说明
+/// - `face` 头像地址
+/// - `level` 主站等级
+/// - `fans` 粉丝数
+#[derive(Debug, Clone)]
+pub struct UserProfile {
+    pub uid: u64,
+    pub uname: String,
+    pub face: String,
+    pub sign: String,
+    pub level: u64,
+    pub fans: u64,
+}
+
+///
+/// api url:
+/// https://api.bilibili.com/x/web-interface/card?mid=9617619
+#[derive(Debug, Deserialize)]
+struct CardData {
+    card: Card,
+}
+
+#[derive(Debug, Deserialize)]
+struct Card {
+    name: String,
+    face: String,
+    #[serde(default)]
+    sign: String,
+    #[serde(default)]
+    fans: u64,
+    #[serde(default)]
+    level_info: LevelInfo,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct LevelInfo {
+    #[serde(default)]
+    current_level: u64,
+}
+
+impl HttpClient {
+    pub async fn user_profile(&self, uid: u64) -> Result<UserProfile, InitError> {
+        let url = format!("https://api.bilibili.com/x/web-interface/card?mid={}", uid);
+        let CardData { card } = self.get_data(url).await?;
+        Ok(UserProfile {
+            uid,
+            uname: card.name,
+            face: card.face,
+            sign: card.sign,
+            level: card.level_info.current_level,
+            fans: card.fans,
+        })
+    }
+}
+
+/// 同一个用户的请求，等待的查询拿到锁后直接使用其中的结果
+type Request = Arc<Mutex<Option<Result<UserProfile, InitError>>>>;
+
+/// 用户资料的LRU缓存，克隆后共享同一份缓存
+///
+/// 同一个用户同时只会有一个请求，其他查询等待这个请求的结果，请求失败时得到同样的错误
+///
+/// # 说明
+/// - `capacity` 最多缓存的用户数，默认1024
+///```no_run,ignore
+///let users = UserCache::new();
+///while let Some(Ok(mut event)) = stream.next().await {
+///    // 弹幕消息中没有头像
+///    users.enrich(&mut event).await?;
+///}
+///```
+#[derive(Debug, Clone)]
+pub struct UserCache {
+    pub capacity: usize,
+    client: HttpClient,
+    state: Arc<std::sync::Mutex<UserCacheState>>,
+}
+
+#[derive(Debug, Default)]
+struct UserCacheState {
+    /// 用户资料和最近一次使用的序号
+    profiles: HashMap<u64, (UserProfile, u64)>,
+    /// 按最近一次使用的序号排列的用户，第一个是最久没有使用的
+    recent: BTreeMap<u64, u64>,
+    /// 正在请求的用户，请求结束或者取消后移除
+    in_flight: HashMap<u64, Request>,
+    tick: u64,
+}
+
+impl UserCacheState {
+    fn get(&mut self, uid: u64) -> Option<UserProfile> {
+        let (profile, used) = self.profiles.get_mut(&uid)?;
+        self.tick += 1;
+        self.recent.remove(used);
+        self.recent.insert(self.tick, uid);
+        *used = self.tick;
+        Some(profile.clone())
+    }
+
+    fn insert(&mut self, profile: UserProfile, capacity: usize) {
+        self.tick += 1;
+        let uid = profile.uid;
+        if let Some((_, used)) = self.profiles.insert(uid, (profile, self.tick)) {
+            self.recent.remove(&used);
+        }
+        self.recent.insert(self.tick, uid);
+        while self.profiles.len() > capacity {
+            let Some((_, oldest)) = self.recent.pop_first() else {
+                break;
+            };
+            self.profiles.remove(&oldest);
+        }
+    }
+}
+
+/// 正在进行的请求，结束或者被取消时从`in_flight`中移除，
+/// 被取消时等待的查询拿到锁后会自己重新请求
+struct InFlight<'a> {
+    cache: &'a UserCache,
+    uid: u64,
+    request: Request,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        let mut state = self.cache.lock();
+        if state
+            .in_flight
+            .get(&self.uid)
+            .is_some_and(|request| Arc::ptr_eq(request, &self.request))
+        {
+            state.in_flight.remove(&self.uid);
+        }
+    }
+}
+
+impl Default for UserCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UserCache {
+    pub fn new() -> Self {
+        Self {
+            capacity: 1024,
+            client: HttpClient::shared().clone(),
+            state: Arc::default(),
+        }
+    }
+
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    pub fn with_client(mut self, client: HttpClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// 已经缓存的用户数
+    pub fn len(&self) -> usize {
+        self.lock().profiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 查询用户资料，没有缓存时请求接口
+    pub async fn get(&self, uid: u64) -> Result<UserProfile, InitError> {
+        let request = {
+            let mut state = self.lock();
+            if let Some(profile) = state.get(uid) {
+                return Ok(profile);
+            }
+            state.in_flight.entry(uid).or_default().clone()
+        };
+        let mut result = request.lock().await;
+        match &*result {
+            Some(Ok(profile)) => return Ok(profile.clone()),
+            Some(Err(e)) => return Err(duplicate_error(e)),
+            None => {}
+        }
+        let _in_flight = InFlight {
+            cache: self,
+            uid,
+            request: request.clone(),
+        };
+        let profile = self.client.user_profile(uid).await;
+        if let Ok(profile) = &profile {
+            self.lock().insert(profile.clone(), self.capacity);
+        }
+        *result = Some(match &profile {
+            Ok(profile) => Ok(profile.clone()),
+            Err(e) => Err(duplicate_error(e)),
+        });
+        profile
+    }
+
+    /// 补全事件中用户的头像，没有用户或者已经有头像时不请求
+    pub async fn enrich(&self, event: &mut Event) -> Result<(), InitError> {
+        let Some(user) = event.data.user_mut() else {
+            return Ok(());
+        };
+        if user.face.as_ref().is_some_and(|face| !face.is_empty()) || user.uid == 0 {
+            return Ok(());
+        }
+        let profile = self.get(user.uid).await?;
+        user.face = Some(profile.face);
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, UserCacheState> {
+        self.state.lock().expect("user cache poisoned")
+    }
+}
+
+/// 等待同一个请求的查询得到同样的错误，`reqwest`和`serde_json`的错误不能复制，转换为文字
+fn duplicate_error(e: &InitError) -> InitError {
+    match e {
+        InitError::ParseError(message) => InitError::ParseError(message.clone()),
+        InitError::HttpStatus(status) => InitError::HttpStatus(*status),
+        InitError::ApiError { code, message } => InitError::ApiError {
+            code: *code,
+            message: message.clone(),
+        },
+        InitError::NoLiveRoom { uid } => InitError::NoLiveRoom { uid: *uid },
+        InitError::Timeout => InitError::Timeout,
+        InitError::MissingRefreshToken => InitError::MissingRefreshToken,
+        InitError::RefreshFailed { source, expired } => InitError::RefreshFailed {
+            source: Box::new(duplicate_error(source)),
+            expired: Box::new(duplicate_error(expired)),
+        },
+        InitError::HttpError(_) | InitError::DeserError(_) => InitError::ParseError(e.to_string()),
+    }
+}
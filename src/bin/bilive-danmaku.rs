@@ -0,0 +1,74 @@
+//! 在终端里查看直播间的弹幕
+//!
+//!```sh
+//!cargo run --features cli -- 510
+//!cargo run --features cli -- 510 --json
+//!```
+use bilive_danmaku::{event::EventData, Connector};
+use futures_util::StreamExt;
+
+const USAGE: &str = "用法: bilive-danmaku <房间号> [--json]";
+
+struct Args {
+    roomid: u64,
+    json: bool,
+}
+
+fn parse_args() -> Option<Args> {
+    let mut roomid = None;
+    let mut json = false;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ => roomid = Some(arg.parse().ok()?),
+        }
+    }
+    Some(Args {
+        roomid: roomid?,
+        json,
+    })
+}
+
+/// 只输出弹幕、礼物、醒目留言和大航海
+fn is_shown(data: &EventData) -> bool {
+    matches!(
+        data,
+        EventData::DanmakuEvent(_)
+            | EventData::GiftEvent(_)
+            | EventData::SuperChatEvent(_)
+            | EventData::GuardBuyEvent(_)
+    )
+}
+
+async fn tail(args: Args) -> Result<(), bilive_danmaku::Error> {
+    let connector = Connector::init(args.roomid).await?;
+    let mut stream = connector.connect().await?;
+    while let Some(event) = stream.next().await {
+        let event = event?;
+        if args.json {
+            match event.to_json() {
+                Ok(json) => println!("{json}"),
+                Err(e) => eprintln!("序列化失败: {e}"),
+            }
+        } else if is_shown(&event.data) {
+            println!("{}", event.format_colored());
+        }
+    }
+    stream.abort();
+    Ok(())
+}
+
+fn main() {
+    let Some(args) = parse_args() else {
+        eprintln!("{USAGE}");
+        std::process::exit(2);
+    };
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build tokio runtime");
+    if let Err(e) = rt.block_on(tail(args)) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
@@ -1,31 +1,189 @@
 #![allow(unused_variables)]
 #![allow(dead_code)]
 
+/// `ONLINE_RANK_TOP3`中的一条提示
 #[derive(Debug, serde::Deserialize)]
+#[non_exhaustive]
 pub struct OnlineRankTop3ListItem {
-    msg: String,
-    rank: u64,
+    pub msg: String,
+    pub rank: u64,
 }
+
+/// 红包中的一种礼物，`award_price`为单价，单位为金瓜子
+#[derive(Debug, serde::Deserialize)]
+#[non_exhaustive]
+pub struct RedPocketAwardInfo {
+    pub award_name: String,
+    #[serde(default)]
+    pub award_price: u64,
+}
+
+/// 天选时刻的中奖用户
+#[derive(Debug, serde::Deserialize)]
+#[non_exhaustive]
+pub struct AnchorLotAwardUser {
+    pub uid: u64,
+    pub uname: String,
+    #[serde(default)]
+    pub face: Option<String>,
+}
+
+/// 高能榜中的一个用户
+#[derive(Debug, serde::Deserialize)]
+#[non_exhaustive]
+pub struct OnlineRankV2ListItem {
+    pub uid: u64,
+    pub uname: String,
+    #[serde(default)]
+    pub face: Option<String>,
+    pub rank: u64,
+    /// 贡献值是字符串
+    #[serde(deserialize_with = "deser_u64_or_str")]
+    pub score: u64,
+    #[serde(default)]
+    pub guard_level: u64,
+}
+
+/// PK消息中一方的直播间，`winner_type`为2时获胜，只有`PK_BATTLE_END`中有
+#[derive(Debug, serde::Deserialize)]
+#[non_exhaustive]
+pub struct PkRoomInfo {
+    pub room_id: u64,
+    #[serde(default)]
+    pub votes: u64,
+    #[serde(default)]
+    pub best_uname: String,
+    #[serde(default)]
+    pub winner_type: i64,
+}
+
+impl From<PkRoomInfo> for PkRoom {
+    fn from(info: PkRoomInfo) -> Self {
+        PkRoom {
+            roomid: info.room_id,
+            votes: info.votes,
+            best_uname: info.best_uname,
+        }
+    }
+}
+
+/// 盲盒礼物中原来的盲盒
 #[derive(Debug, serde::Deserialize)]
+#[non_exhaustive]
 pub struct BlindGiftInfo {
-    gift_action: String,
-    original_gift_id: u64,
-    original_gift_name: String,
+    pub gift_action: String,
+    pub original_gift_id: u64,
+    pub original_gift_name: String,
+}
+
+/// 开放平台消息中的用户，`uid`逐渐被`open_id`取代，可能为0
+#[derive(Debug, serde::Deserialize)]
+#[non_exhaustive]
+pub struct OpenPlatformUser {
+    #[serde(default)]
+    pub uid: u64,
+    pub uname: String,
+    pub uface: String,
+}
+
+impl From<OpenPlatformUser> for User {
+    fn from(user: OpenPlatformUser) -> Self {
+        User {
+            uid: user.uid,
+            uname: user.uname,
+            face: Some(user.uface),
+        }
+    }
+}
+
+/// 开放平台消息中的粉丝牌，只会是本直播间的粉丝牌
+#[derive(Debug, serde::Deserialize)]
+#[non_exhaustive]
+pub struct OpenPlatformMedal {
+    #[serde(default)]
+    pub fans_medal_level: u64,
+    #[serde(default)]
+    pub fans_medal_name: String,
+    #[serde(default)]
+    pub fans_medal_wearing_status: bool,
+}
+
+impl OpenPlatformMedal {
+    fn into_medal(self, room_id: u64, guard_level: u64) -> Option<FansMedal> {
+        medal_filter(Some(FansMedal {
+            anchor_roomid: room_id,
+            guard_level,
+            medal_level: self.fans_medal_level,
+            medal_name: self.fans_medal_name,
+            anchor_uname: None,
+            target_id: 0,
+            is_lighted: self.fans_medal_wearing_status,
+        }))
+    }
 }
 
+/// b站原始消息按`cmd`解析的结果，字段与b站的json基本一致，是转换为[`Event`](crate::event::Event)之前的中间形式
+///
+/// 需要更精简的事件类型时，为自己的类型实现`TryFrom<Cmd>`，配合[`Decoder::decode_as`](crate::decoder::Decoder::decode_as)使用，
+/// 不需要的消息在转换时直接丢弃，不会构造[`Event`](crate::event::Event)。消息格式随b站变化，新增的变体和字段不视为破坏性更新，
+/// 匹配带字段的变体时需要加上`..`
 #[derive(Debug, serde::Deserialize)]
 #[serde(tag = "cmd", content = "data", rename_all = "SCREAMING_SNAKE_CASE")]
-pub(crate) enum Cmd {
+#[non_exhaustive]
+pub enum Cmd {
+    #[non_exhaustive]
     ComboSend {
         action: String,
         batch_combo_num: u64,
         combo_total_coin: u64,
         gift_name: String,
         gift_id: u64,
+        #[serde(flatten)]
         user: User,
     },
+    #[non_exhaustive]
+    AnchorLotStart {
+        id: u64,
+        award_name: String,
+        award_num: u64,
+        #[serde(default)]
+        require_text: String,
+        #[serde(default)]
+        danmu: String,
+        #[serde(default)]
+        gift_id: u64,
+        #[serde(default)]
+        gift_name: String,
+        max_time: u64,
+    },
+    #[non_exhaustive]
+    AnchorLotEnd { id: u64 },
+    #[non_exhaustive]
+    AnchorLotAward {
+        id: u64,
+        award_name: String,
+        award_num: u64,
+        award_users: Vec<AnchorLotAwardUser>,
+    },
+    #[non_exhaustive]
     CommonNoticeDanmaku {},
-    EntryEffect {},
+    #[non_exhaustive]
+    DanmuAggregation {
+        msg: String,
+        aggregation_num: u64,
+        #[serde(default)]
+        activity_identity: String,
+    },
+    #[non_exhaustive]
+    EntryEffect {
+        uid: u64,
+        #[serde(default)]
+        face: Option<String>,
+        #[serde(default)]
+        privilege_type: u64,
+        copy_writing: String,
+    },
+    #[non_exhaustive]
     GuardBuy {
         gift_id: u64,
         gift_name: String,
@@ -35,53 +193,155 @@ pub(crate) enum Cmd {
         uid: u64,
         username: String,
     },
+    #[non_exhaustive]
     HotBuyNum {},
+    #[non_exhaustive]
     HotRankChangedV2 {
         area_name: String,
         rank: u64,
         rank_desc: String,
     },
+    #[non_exhaustive]
     HotRankSettlementV2 {
         area_name: String,
         rank: u64,
         uname: String,
         face: String,
     },
+    #[non_exhaustive]
     LiveInteractiveGame {},
-    OnlineRankV2 {},
+    #[serde(rename = "LIKE_INFO_V3_CLICK")]
+    #[non_exhaustive]
+    LikeInfoV3Click {
+        #[serde(flatten)]
+        user: User,
+        fans_medal: Option<FansMedal>,
+        like_text: String,
+    },
+    #[serde(rename = "LIKE_INFO_V3_UPDATE")]
+    #[non_exhaustive]
+    LikeInfoV3Update { click_count: u64 },
+    #[non_exhaustive]
+    OnlineRankV2 {
+        list: Vec<OnlineRankV2ListItem>,
+        #[serde(default)]
+        rank_type: String,
+    },
+    #[non_exhaustive]
     OnlineRankTop3 {
         dmscore: u64,
         list: Vec<OnlineRankTop3ListItem>,
     },
-    PopularityRedPocketStart {},
+    #[non_exhaustive]
+    PopularityRedPocketStart {
+        lot_id: u64,
+        sender_uid: u64,
+        sender_name: String,
+        sender_face: String,
+        total_price: u64,
+        last_time: u64,
+        danmu: String,
+        awards: Vec<RedPocketAward>,
+    },
+    #[non_exhaustive]
+    PopularityRedPocketWinnerList {
+        lot_id: u64,
+        total_num: u64,
+        /// 每一项为`[uid, 用户名, 中奖记录id, 礼物id, ..]`
+        winner_info: Vec<Vec<Value>>,
+        awards: std::collections::HashMap<String, RedPocketAwardInfo>,
+    },
+    #[non_exhaustive]
     RoomRealTimeMessageUpdate {
         fans: u64,
         fans_club: u64,
         red_notice: i64,
         roomid: u64,
     },
+    #[non_exhaustive]
+    RoomChange {
+        title: String,
+        area_id: u64,
+        area_name: String,
+        parent_area_id: u64,
+        parent_area_name: String,
+    },
+    #[non_exhaustive]
     UserToastMsg {},
-    StopLiveRoomList {
-        room_id_list: Vec<u64>,
+    /// 用户被禁言，`operator`为1时是房管操作，2时是主播操作
+    #[non_exhaustive]
+    RoomBlockMsg {
+        uid: u64,
+        uname: String,
+        #[serde(default)]
+        operator: u64,
+    },
+    /// 匹配到对手，`data`中是对手的信息
+    #[serde(alias = "PK_BATTLE_PRE_NEW")]
+    #[non_exhaustive]
+    PkBattlePre {
+        uid: u64,
+        uname: String,
+        #[serde(default)]
+        face: Option<String>,
+        room_id: u64,
+        #[serde(default)]
+        pk_votes_name: String,
     },
+    #[serde(alias = "PK_BATTLE_START_NEW")]
+    #[non_exhaustive]
+    PkBattleStart {
+        pk_start_time: u64,
+        pk_end_time: u64,
+        #[serde(default)]
+        pk_votes_name: String,
+        init_info: PkRoomInfo,
+        match_info: PkRoomInfo,
+    },
+    #[serde(alias = "PK_BATTLE_PROCESS_NEW")]
+    #[non_exhaustive]
+    PkBattleProcess {
+        init_info: PkRoomInfo,
+        match_info: PkRoomInfo,
+    },
+    #[non_exhaustive]
+    PkBattleEnd {
+        init_info: PkRoomInfo,
+        match_info: PkRoomInfo,
+    },
+    #[non_exhaustive]
+    StopLiveRoomList { room_id_list: Vec<u64> },
+    #[non_exhaustive]
     InteractWord {
         fans_medal: Option<FansMedal>,
         #[serde(flatten)]
         user: User,
+        #[serde(default)]
+        roomid: u64,
+        #[serde(default)]
+        privilege_type: u64,
+        #[serde(default)]
+        user_level: u64,
     },
-    WatchedChange {
-        num: u64,
-    },
-    OnlineRankCount {
-        count: u64,
-    },
+    #[non_exhaustive]
+    WatchedChange { num: u64 },
+    #[non_exhaustive]
+    OnlineRankCount { count: u64 },
+    #[non_exhaustive]
     DanmuMsg {
         danmaku_type: u64,
+        guard_level: u64,
+        user_level: u64,
+        mode: u64,
+        font_size: u64,
+        color: u32,
         fans_medal: Option<FansMedal>,
         user: User,
         message: String,
         emoticon: Option<Emoticon>,
+        voice: Option<Voice>,
     },
+    #[non_exhaustive]
     SendGift {
         action: String,
         #[serde(flatten)]
@@ -97,21 +357,106 @@ pub(crate) enum Cmd {
         total_coin: u64,
         blind_gift: Option<BlindGiftInfo>,
     },
+    #[non_exhaustive]
     SuperChatMessage {
+        #[serde(deserialize_with = "deser_u64_or_str")]
+        id: u64,
         medal_info: Option<FansMedal>,
         message: String,
+        #[serde(default)]
+        message_trans: String,
         price: u64,
         uid: u64,
         user_info: SuperChatUser,
     },
+    /// 日文版本的id和uid是字符串
+    #[non_exhaustive]
     SuperChatMessageJpn {
+        #[serde(deserialize_with = "deser_u64_or_str")]
+        id: u64,
         medal_info: Option<FansMedal>,
         message: String,
         message_jpn: String,
         price: u64,
+        #[serde(deserialize_with = "deser_u64_or_str")]
         uid: u64,
         user_info: SuperChatUser,
     },
+    #[non_exhaustive]
+    LiveOpenPlatformDm {
+        room_id: u64,
+        #[serde(flatten)]
+        user: OpenPlatformUser,
+        #[serde(flatten)]
+        medal: OpenPlatformMedal,
+        #[serde(default)]
+        guard_level: u64,
+        msg: String,
+        /// 0为普通弹幕，1为表情
+        #[serde(default)]
+        dm_type: u64,
+        #[serde(default)]
+        emoji_img_url: String,
+    },
+    #[non_exhaustive]
+    LiveOpenPlatformSendGift {
+        room_id: u64,
+        #[serde(flatten)]
+        user: OpenPlatformUser,
+        #[serde(flatten)]
+        medal: OpenPlatformMedal,
+        #[serde(default)]
+        guard_level: u64,
+        gift_id: u64,
+        gift_name: String,
+        gift_num: u64,
+        price: u64,
+        paid: bool,
+    },
+    #[non_exhaustive]
+    LiveOpenPlatformSuperChat {
+        room_id: u64,
+        #[serde(flatten)]
+        user: OpenPlatformUser,
+        #[serde(flatten)]
+        medal: OpenPlatformMedal,
+        #[serde(default)]
+        guard_level: u64,
+        message: String,
+        rmb: u64,
+    },
+    #[non_exhaustive]
+    LiveOpenPlatformGuard {
+        room_id: u64,
+        user_info: OpenPlatformUser,
+        #[serde(flatten)]
+        medal: OpenPlatformMedal,
+        guard_level: u64,
+        #[serde(default)]
+        price: u64,
+    },
+    /// `LIVE`和`PREPARING`，字段不在`data`中，手动解析
+    #[serde(skip)]
+    #[non_exhaustive]
+    LiveStatusChange {
+        status: LiveStatus,
+        live_time: Option<u64>,
+    },
+    /// 全站或分区的广播，字段不在`data`中，手动解析
+    #[serde(skip)]
+    #[non_exhaustive]
+    NoticeMsg {
+        msg_type: u64,
+        name: String,
+        msg_common: String,
+        msg_self: String,
+        real_roomid: u64,
+        link_url: String,
+    },
+    /// 还不认识的cmd，保留原始json
+    #[serde(skip)]
+    #[non_exhaustive]
+    Unknown { cmd: String, value: Value },
 }
 
 use std::fmt::Display;
@@ -120,6 +465,37 @@ use serde_json::Value;
 
 use crate::{event::EventData, model::*};
 
+/// 消息中携带的服务器时间，统一为毫秒
+///
+/// - `DANMU_MSG` 发送时间`info[0][4]`，单位为毫秒
+/// - 其他消息 `data.timestamp`或`data.start_time`，单位为秒
+pub(crate) fn server_timestamp(json: &Value) -> Option<u64> {
+    if json["cmd"].as_str() == Some("DANMU_MSG") {
+        return json["info"][0][4].as_u64().filter(|ts| *ts > 0);
+    }
+    let data = &json["data"];
+    let ts = data["timestamp"]
+        .as_u64()
+        .or_else(|| data["start_time"].as_u64())
+        .filter(|ts| *ts > 0)?;
+    // 有的消息已经是毫秒
+    Some(if ts < 10_000_000_000 { ts * 1000 } else { ts })
+}
+
+fn deser_u64_or_str<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    use serde::Deserialize;
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum U64OrStr {
+        U64(u64),
+        Str(String),
+    }
+    match U64OrStr::deserialize(deserializer)? {
+        U64OrStr::U64(n) => Ok(n),
+        U64OrStr::Str(s) => s.parse().map_err(serde::de::Error::custom),
+    }
+}
+
 fn medal_filter(fans_medal: Option<FansMedal>) -> Option<FansMedal> {
     match fans_medal {
         Some(FansMedal { medal_level: 0, .. }) | None => None,
@@ -127,12 +503,22 @@ fn medal_filter(fans_medal: Option<FansMedal>) -> Option<FansMedal> {
     }
 }
 
+/// # 说明
+/// - `CannotDeser` 已知的cmd反序列化失败，`path`为出错的字段路径，比如`data.num`
+/// - `Malformed` 手动解析的cmd（`DANMU_MSG`）缺少字段或类型错误，`path`为出错的字段路径，比如`info[2][0]`
 #[derive(Debug)]
 pub enum CmdDeserError {
     CannotDeser {
+        cmd: String,
+        path: String,
         json_error: serde_json::Error,
         text: String,
     },
+    Malformed {
+        cmd: String,
+        path: String,
+        text: String,
+    },
     Untagged {
         text: String,
     },
@@ -147,9 +533,18 @@ pub enum CmdDeserError {
 impl Display for CmdDeserError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            CmdDeserError::CannotDeser { json_error, text } => f.write_fmt(format_args!(
-                "无法反序列化\n json_error: \n{}, json文本: \n{}",
-                json_error, text
+            CmdDeserError::CannotDeser {
+                cmd,
+                path,
+                json_error,
+                text,
+            } => f.write_fmt(format_args!(
+                "无法反序列化{}，字段: {}\n json_error: \n{}, json文本: \n{}",
+                cmd, path, json_error, text
+            )),
+            CmdDeserError::Malformed { cmd, path, text } => f.write_fmt(format_args!(
+                "{}格式错误，字段: {}\n json文本: \n{}",
+                cmd, path, text
             )),
             CmdDeserError::Untagged { text } => {
                 f.write_fmt(format_args!("缺少 tag 的消息\n , json文本: \n{}", text))
@@ -160,92 +555,120 @@ impl Display for CmdDeserError {
     }
 }
 
-impl std::error::Error for CmdDeserError {}
+impl std::error::Error for CmdDeserError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CmdDeserError::CannotDeser { json_error, .. } => Some(json_error),
+            _ => None,
+        }
+    }
+}
+
+/// `DANMU_MSG`和弹幕历史中以数组表示的粉丝牌，没有佩戴时为`None`
+pub(crate) fn medal_from_array(fans_medal: &Value) -> Option<FansMedal> {
+    let medal_level = fans_medal[0].as_u64();
+    let medal_name = fans_medal[1].as_str();
+    let anchor_uname = fans_medal[2]
+        .as_str()
+        .filter(|s| !s.is_empty())
+        .map(str::to_owned);
+    let anchor_roomid = fans_medal[3].as_u64();
+    let guard_level = fans_medal[10].as_u64();
+    let is_lighted = fans_medal[11].as_u64().unwrap_or_default() != 0;
+    let target_id = fans_medal[12].as_u64().unwrap_or_default();
+    if let (Some(medal_level), Some(medal_name), Some(guard_level), Some(anchor_roomid)) =
+        (medal_level, medal_name, guard_level, anchor_roomid)
+    {
+        Some(FansMedal {
+            anchor_roomid,
+            guard_level,
+            medal_level,
+            medal_name: medal_name.to_owned(),
+            anchor_uname,
+            target_id,
+            is_lighted,
+        })
+    } else {
+        None
+    }
+}
+
+/// 消息没有对应的[`Event`](crate::event::Event)，例如[`Cmd::Unknown`]，`cmd`为原来的消息，可以继续自行处理
+#[derive(Debug)]
+pub struct NoEventError {
+    pub cmd: Box<Cmd>,
+}
+
+impl Display for NoEventError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.cmd.as_ref() {
+            Cmd::Unknown { cmd, .. } => write!(f, "未知的cmd: {cmd}"),
+            _ => f.write_str("消息没有对应的事件"),
+        }
+    }
+}
+
+impl std::error::Error for NoEventError {}
+
+/// 同[`Cmd::into_event`]，没有对应事件的消息转换失败，错误中带有原来的消息
+impl TryFrom<Cmd> for crate::event::Event {
+    type Error = NoEventError;
+
+    fn try_from(cmd: Cmd) -> Result<Self, Self::Error> {
+        cmd.try_into_event()
+            .map(Self::from)
+            .map_err(|cmd| NoEventError { cmd })
+    }
+}
 
 impl Cmd {
+    /// 未知的cmd得到[`Cmd::Unknown`]，只有已知的cmd格式错误时才会失败
     pub fn deser(val: Value) -> Result<Self, CmdDeserError> {
-        log::trace!("deserialize json value: {}", val.to_string());
+        tracing::trace!(json = %val, "deserialize json value");
         match &val["cmd"] {
             Value::String(cmd) => {
-                const PROTOCOL_ERROR: &str = "danmu_msg事件协议错误";
                 match cmd.as_str() {
-                    "NOTICE_MSG" | "WIDGET_BANNER" | "HOT_RANK_CHANGED" | "HOT_RANK_SETTLEMENT" => {
+                    "WIDGET_BANNER" | "HOT_RANK_CHANGED" | "HOT_RANK_SETTLEMENT" => {
                         Err(CmdDeserError::Ignored { tag: cmd.clone() })
                     }
-                    "DANMU_MSG" => {
-                        // 如果这里出问题，可能是b站协议发生变更了，所以panic一下无可厚非吧
-                        let info = val["info"].as_array().expect(PROTOCOL_ERROR);
-                        let message = info[1].as_str().expect(PROTOCOL_ERROR).clone();
-                        let user = info[2].as_array().expect(PROTOCOL_ERROR);
-                        let uid = user[0].as_u64().expect(PROTOCOL_ERROR);
-                        let name = user[1].as_str().expect(PROTOCOL_ERROR);
-                        let danmaku_type = info[0].as_array().expect(PROTOCOL_ERROR)[10]
+                    "DANMU_MSG" => Self::deser_danmu_msg(&val),
+                    "NOTICE_MSG" => Ok(Cmd::NoticeMsg {
+                        msg_type: val["msg_type"].as_u64().unwrap_or_default(),
+                        name: val["name"].as_str().unwrap_or_default().to_owned(),
+                        msg_common: val["msg_common"].as_str().unwrap_or_default().to_owned(),
+                        msg_self: val["msg_self"].as_str().unwrap_or_default().to_owned(),
+                        real_roomid: val["real_roomid"]
                             .as_u64()
-                            .expect(PROTOCOL_ERROR);
-                        let fans_medal = &info[3];
-                        let fans_medal = {
-                            let medal_level = fans_medal[0].as_u64();
-                            let medal_name = fans_medal[1].as_str();
-                            let anchor_roomid = fans_medal[3].as_u64();
-                            let guard_level = fans_medal[10].as_u64();
-                            if let (
-                                Some(medal_level),
-                                Some(medal_name),
-                                Some(guard_level),
-                                Some(anchor_roomid),
-                            ) = (medal_level, medal_name, guard_level, anchor_roomid)
-                            {
-                                Some(FansMedal {
-                                    anchor_roomid,
-                                    guard_level,
-                                    medal_level,
-                                    medal_name: medal_name.to_owned(),
-                                })
-                            } else {
-                                None
-                            }
-                        };
-                        // 是否为表情？
-                        let emoticon = if let Some(emoticon) =
-                            info[0].as_array().expect(PROTOCOL_ERROR)[13].as_object()
-                        {
-                            let height = emoticon["height"].as_u64().unwrap_or_default();
-                            let width = emoticon["width"].as_u64().unwrap_or_default();
-                            let emoticon_unique = emoticon["emoticon_unique"]
-                                .as_str()
-                                .unwrap_or_default()
-                                .to_owned();
-                            let url = emoticon["url"].as_str().unwrap_or_default().to_owned();
-                            Some(Emoticon {
-                                height,
-                                width,
-                                url,
-                                unique_id: emoticon_unique,
-                            })
-                        } else {
-                            None
-                        };
-                        // 是否为抽奖弹幕？
-
-                        let res = Cmd::DanmuMsg {
-                            danmaku_type,
-                            fans_medal,
-                            user: User {
-                                uname: name.to_owned(),
-                                uid,
-                                face: None,
-                            },
-                            message: message.to_owned(),
-                            emoticon,
-                        };
-                        Ok(res)
-                    }
-                    _ => serde_json::from_value(val.clone()).map_err(|json_error| {
-                        CmdDeserError::CannotDeser {
-                            json_error,
-                            text: val.to_string(),
-                        }
+                            .or_else(|| val["roomid"].as_u64())
+                            .unwrap_or_default(),
+                        link_url: val["link_url"].as_str().unwrap_or_default().to_owned(),
                     }),
+                    "LIVE" => Ok(Cmd::LiveStatusChange {
+                        status: LiveStatus::Live,
+                        live_time: val["live_time"].as_u64().filter(|time| *time > 0),
+                    }),
+                    // 下播后开始轮播时`round`为1
+                    "PREPARING" => Ok(Cmd::LiveStatusChange {
+                        status: match val["round"].as_u64() {
+                            Some(1) => LiveStatus::Round,
+                            _ => LiveStatus::Preparing,
+                        },
+                        live_time: None,
+                    }),
+                    _ => match serde_path_to_error::deserialize(&val) {
+                        Ok(cmd) => Ok(cmd),
+                        // 只有tag出错，说明是不认识的cmd
+                        Err(e) if e.path().to_string() == "cmd" => Ok(Cmd::Unknown {
+                            cmd: cmd.clone(),
+                            value: val,
+                        }),
+                        Err(e) => Err(CmdDeserError::CannotDeser {
+                            cmd: cmd.clone(),
+                            path: e.path().to_string(),
+                            json_error: e.into_inner(),
+                            text: val.to_string(),
+                        }),
+                    },
                 }
             }
             _ => Err(CmdDeserError::Untagged {
@@ -254,45 +677,151 @@ impl Cmd {
         }
     }
 
+    fn deser_danmu_msg(val: &Value) -> Result<Self, CmdDeserError> {
+        // 如果这里出问题，可能是b站协议发生变更了
+        let malformed = |path: &str| CmdDeserError::Malformed {
+            cmd: "DANMU_MSG".to_owned(),
+            path: path.to_owned(),
+            text: val.to_string(),
+        };
+        let info = &val["info"];
+        let message = info[1].as_str().ok_or_else(|| malformed("info[1]"))?;
+        let uid = info[2][0].as_u64().ok_or_else(|| malformed("info[2][0]"))?;
+        let name = info[2][1].as_str().ok_or_else(|| malformed("info[2][1]"))?;
+        let danmaku_type = info[0][10]
+            .as_u64()
+            .ok_or_else(|| malformed("info[0][10]"))?;
+        let guard_level = info[7].as_u64().unwrap_or_default();
+        let mode = info[0][1].as_u64().unwrap_or(1);
+        let font_size = info[0][2].as_u64().unwrap_or(25);
+        let color = info[0][3].as_u64().unwrap_or(0xFFFFFF) as u32;
+        let user_level = info[4][0].as_u64().unwrap_or_default();
+        let fans_medal = medal_from_array(&info[3]);
+        // 是否为表情？
+        let emoticon = if let Some(emoticon) = info[0][13].as_object() {
+            let height = emoticon["height"].as_u64().unwrap_or_default();
+            let width = emoticon["width"].as_u64().unwrap_or_default();
+            let emoticon_unique = emoticon["emoticon_unique"]
+                .as_str()
+                .unwrap_or_default()
+                .to_owned();
+            let url = emoticon["url"].as_str().unwrap_or_default().to_owned();
+            Some(Emoticon {
+                height,
+                width,
+                url,
+                unique_id: emoticon_unique,
+            })
+        } else {
+            None
+        };
+        // 是否为语音？
+        let voice = info[0][14]
+            .as_object()
+            .filter(|voice| {
+                voice["voice_url"]
+                    .as_str()
+                    .is_some_and(|url| !url.is_empty())
+            })
+            .map(|voice| Voice {
+                url: voice["voice_url"].as_str().unwrap_or_default().to_owned(),
+                file_format: voice["file_format"].as_str().unwrap_or_default().to_owned(),
+                duration: voice["file_duration"].as_u64().unwrap_or_default(),
+                text: voice["text"].as_str().unwrap_or_default().to_owned(),
+            });
+        // 是否为抽奖弹幕？
+
+        let res = Cmd::DanmuMsg {
+            danmaku_type,
+            guard_level,
+            user_level,
+            mode,
+            font_size,
+            color,
+            fans_medal,
+            user: User {
+                uname: name.to_owned(),
+                uid,
+                face: None,
+            },
+            message: message.to_owned(),
+            emoticon,
+            voice,
+        };
+        Ok(res)
+    }
+
+    /// 转换为[`EventData`]，没有对应事件的消息返回`None`
     pub fn into_event(self) -> Option<EventData> {
+        self.try_into_event().ok()
+    }
+
+    /// 没有对应事件的消息原样返回
+    fn try_into_event(self) -> Result<EventData, Box<Self>> {
         use crate::event::*;
         match self {
-            Cmd::InteractWord { fans_medal, user } => {
-                Some(EventData::EnterRoomEvent(EnterRoomEvent {
+            Cmd::InteractWord {
+                fans_medal,
+                user,
+                roomid,
+                privilege_type,
+                user_level,
+            } => {
+                // 没有privilege_type时，从本直播间的粉丝牌中取大航海等级
+                let guard_level = match (privilege_type, &fans_medal) {
+                    (0, Some(medal)) if medal.anchor_roomid == roomid => medal.guard_level,
+                    (level, _) => level,
+                };
+                Ok(EventData::EnterRoomEvent(EnterRoomEvent {
                     user,
                     fans_medal: medal_filter(fans_medal),
+                    guard_level,
+                    user_level,
                 }))
             }
             Cmd::DanmuMsg {
                 danmaku_type,
+                guard_level,
+                user_level,
+                mode,
+                font_size,
+                color,
                 fans_medal,
                 user,
                 message,
                 emoticon,
-            } => match emoticon {
-                Some(emoticon) => Some(EventData::DanmakuEvent(DanmakuEvent {
-                    flag: danmaku_type,
-                    message: DanmakuMessage::Emoticon {
+                voice,
+            } => {
+                let message = match (emoticon, voice) {
+                    (Some(emoticon), _) => DanmakuMessage::Emoticon {
                         alt_message: message,
                         emoticon,
                     },
-                    user,
-                    fans_medal,
-                })),
-                None => Some(EventData::DanmakuEvent(DanmakuEvent {
+                    (None, Some(voice)) => DanmakuMessage::Voice { voice },
+                    (None, None) => DanmakuMessage::Plain { message },
+                };
+                Ok(EventData::DanmakuEvent(DanmakuEvent {
                     flag: danmaku_type,
-                    message: DanmakuMessage::Plain { message },
+                    message,
                     user,
                     fans_medal,
-                })),
-            },
+                    guard_level,
+                    user_level,
+                    mode,
+                    font_size,
+                    color,
+                }))
+            }
             Cmd::SuperChatMessage {
+                id,
                 uid,
                 medal_info,
                 message,
+                message_trans,
                 price,
                 user_info,
-            } => Some(EventData::SuperChatEvent(SuperChatEvent {
+            } => Ok(EventData::SuperChatEvent(SuperChatEvent {
+                id,
                 user: User {
                     uid,
                     uname: user_info.uname,
@@ -301,16 +830,18 @@ impl Cmd {
                 fans_medal: medal_info,
                 price,
                 message,
-                message_jpn: None,
+                message_jpn: Some(message_trans).filter(|s| !s.is_empty()),
             })),
             Cmd::SuperChatMessageJpn {
+                id,
                 uid,
                 medal_info,
                 message,
                 price,
                 user_info,
                 message_jpn,
-            } => Some(EventData::SuperChatEvent(SuperChatEvent {
+            } => Ok(EventData::SuperChatEvent(SuperChatEvent {
+                id,
                 user: User {
                     uid,
                     uname: user_info.uname,
@@ -322,7 +853,7 @@ impl Cmd {
                 message_jpn: Some(message_jpn),
             })),
             Cmd::WatchedChange { num } => {
-                Some(EventData::WatchedUpdateEvent(WatchedUpdateEvent { num }))
+                Ok(EventData::WatchedUpdateEvent(WatchedUpdateEvent { num }))
             }
             Cmd::SendGift {
                 action,
@@ -337,7 +868,7 @@ impl Cmd {
                 blind_gift,
             } => {
                 if let Some(blind_gift_info) = blind_gift {
-                    Some(EventData::GiftEvent(GiftEvent {
+                    Ok(EventData::GiftEvent(GiftEvent {
                         user,
                         fans_medal: medal_filter(medal_info),
                         blindbox: Some(GiftType {
@@ -356,7 +887,7 @@ impl Cmd {
                         },
                     }))
                 } else {
-                    Some(EventData::GiftEvent(GiftEvent {
+                    Ok(EventData::GiftEvent(GiftEvent {
                         user,
                         fans_medal: medal_filter(medal_info),
                         blindbox: None,
@@ -376,28 +907,24 @@ impl Cmd {
                 area_name,
                 rank,
                 rank_desc,
-            } => Some(
-                HotRankChangedEvent {
-                    area: area_name,
-                    rank,
-                    description: rank_desc,
-                }
-                .into(),
-            ),
+            } => Ok(HotRankChangedEvent {
+                area: area_name,
+                rank,
+                description: rank_desc,
+            }
+            .into()),
             Cmd::HotRankSettlementV2 {
                 area_name,
                 rank,
                 uname,
                 face,
-            } => Some(
-                HotRankSettlementEvent {
-                    uname,
-                    face,
-                    area: area_name,
-                    rank,
-                }
-                .into(),
-            ),
+            } => Ok(HotRankSettlementEvent {
+                uname,
+                face,
+                area: area_name,
+                rank,
+            }
+            .into()),
             Cmd::GuardBuy {
                 gift_id,
                 gift_name,
@@ -406,22 +933,380 @@ impl Cmd {
                 num,
                 uid,
                 username,
-            } => Some(
-                GuardBuyEvent {
-                    level: guard_level,
-                    price,
-                    user: User {
-                        uname: username,
-                        uid,
-                        face: None,
+            } => Ok(GuardBuyEvent {
+                level: guard_level,
+                price,
+                user: User {
+                    uname: username,
+                    uid,
+                    face: None,
+                },
+            }
+            .into()),
+            Cmd::StopLiveRoomList { room_id_list } => Ok(StopLiveEvent { room_id_list }.into()),
+            Cmd::AnchorLotStart {
+                id,
+                award_name,
+                award_num,
+                require_text,
+                danmu,
+                gift_id,
+                gift_name,
+                max_time,
+            } => Ok(AnchorLotStartEvent {
+                id,
+                award_name,
+                award_num,
+                require_text,
+                danmu,
+                gift: (gift_id != 0).then(|| GiftType {
+                    action: "投喂".to_owned(),
+                    gift_name,
+                    gift_id,
+                }),
+                duration: max_time,
+            }
+            .into()),
+            Cmd::AnchorLotEnd { id } => Ok(AnchorLotEndEvent { id }.into()),
+            Cmd::OnlineRankV2 { list, rank_type } => Ok(OnlineRankUpdateEvent {
+                rank_type,
+                list: list
+                    .into_iter()
+                    .map(|item| OnlineRankUser {
+                        user: User {
+                            uid: item.uid,
+                            uname: item.uname,
+                            face: item.face,
+                        },
+                        rank: item.rank,
+                        score: item.score,
+                        guard_level: item.guard_level,
+                    })
+                    .collect(),
+            }
+            .into()),
+            Cmd::OnlineRankTop3 { mut list, .. } => {
+                list.sort_by_key(|item| item.rank);
+                Ok(OnlineRankTop3Event {
+                    messages: list
+                        .into_iter()
+                        .map(|item| item.msg.replace("<%", "").replace("%>", ""))
+                        .collect(),
+                }
+                .into())
+            }
+            Cmd::EntryEffect {
+                uid,
+                face,
+                privilege_type,
+                copy_writing,
+            } => {
+                // 用户名被包在<%和%>之间
+                let uname = copy_writing
+                    .split_once("<%")
+                    .and_then(|(_, rest)| rest.split_once("%>"))
+                    .map(|(uname, _)| uname.to_owned())
+                    .unwrap_or_default();
+                Ok(GuardEnterRoomEvent {
+                    user: User { uid, uname, face },
+                    guard_level: privilege_type,
+                    copy_writing: copy_writing.replace("<%", "").replace("%>", ""),
+                }
+                .into())
+            }
+            Cmd::DanmuAggregation {
+                msg,
+                aggregation_num,
+                activity_identity,
+            } => Ok(DanmakuAggregationEvent {
+                message: msg,
+                count: aggregation_num,
+                activity_id: activity_identity,
+            }
+            .into()),
+            Cmd::LikeInfoV3Click {
+                user,
+                fans_medal,
+                like_text,
+            } => Ok(LikeClickEvent {
+                user,
+                fans_medal: medal_filter(fans_medal),
+                text: like_text,
+            }
+            .into()),
+            Cmd::LikeInfoV3Update { click_count } => {
+                Ok(LikeCountUpdateEvent { count: click_count }.into())
+            }
+            Cmd::RoomRealTimeMessageUpdate {
+                fans, fans_club, ..
+            } => Ok(FansUpdateEvent { fans, fans_club }.into()),
+            Cmd::RoomBlockMsg {
+                uid,
+                uname,
+                operator,
+            } => Ok(UserBlockedEvent {
+                user: User {
+                    uid,
+                    uname,
+                    face: None,
+                },
+                operator: match operator {
+                    1 => BlockOperator::Admin,
+                    2 => BlockOperator::Anchor,
+                    other => BlockOperator::Unknown(other),
+                },
+            }
+            .into()),
+            Cmd::PkBattlePre {
+                uid,
+                uname,
+                face,
+                room_id,
+                pk_votes_name,
+            } => Ok(PkBattlePreEvent {
+                opponent: User { uid, uname, face },
+                opponent_roomid: room_id,
+                votes_name: pk_votes_name,
+            }
+            .into()),
+            Cmd::PkBattleStart {
+                pk_start_time,
+                pk_end_time,
+                pk_votes_name,
+                init_info,
+                match_info,
+            } => Ok(PkBattleStartEvent {
+                init: init_info.into(),
+                matched: match_info.into(),
+                start_time: pk_start_time,
+                end_time: pk_end_time,
+                votes_name: pk_votes_name,
+            }
+            .into()),
+            Cmd::PkBattleProcess {
+                init_info,
+                match_info,
+            } => Ok(PkBattleProcessEvent {
+                init: init_info.into(),
+                matched: match_info.into(),
+            }
+            .into()),
+            Cmd::PkBattleEnd {
+                init_info,
+                match_info,
+            } => {
+                let winner = [&init_info, &match_info]
+                    .into_iter()
+                    .find(|info| info.winner_type == 2)
+                    .map(|info| info.room_id);
+                Ok(PkBattleEndEvent {
+                    init: init_info.into(),
+                    matched: match_info.into(),
+                    winner,
+                }
+                .into())
+            }
+            Cmd::RoomChange {
+                title,
+                area_id,
+                area_name,
+                parent_area_id,
+                parent_area_name,
+            } => Ok(RoomChangeEvent {
+                title,
+                area_id,
+                area_name,
+                parent_area_id,
+                parent_area_name,
+            }
+            .into()),
+            Cmd::AnchorLotAward {
+                id,
+                award_name,
+                award_num,
+                award_users,
+            } => Ok(AnchorLotAwardEvent {
+                id,
+                award_name,
+                award_num,
+                winners: award_users
+                    .into_iter()
+                    .map(|user| User {
+                        uid: user.uid,
+                        uname: user.uname,
+                        face: user.face,
+                    })
+                    .collect(),
+            }
+            .into()),
+            Cmd::PopularityRedPocketStart {
+                lot_id,
+                sender_uid,
+                sender_name,
+                sender_face,
+                total_price,
+                last_time,
+                danmu,
+                awards,
+            } => Ok(RedPocketStartEvent {
+                lot_id,
+                sender: User {
+                    uid: sender_uid,
+                    uname: sender_name,
+                    face: Some(sender_face),
+                },
+                total_price,
+                duration: last_time,
+                danmu,
+                awards,
+            }
+            .into()),
+            Cmd::PopularityRedPocketWinnerList {
+                lot_id,
+                total_num,
+                winner_info,
+                awards,
+            } => {
+                let winners = winner_info
+                    .into_iter()
+                    .filter_map(|info| {
+                        let gift_id = info.get(3)?.as_u64()?;
+                        let award = awards.get(&gift_id.to_string());
+                        Some(RedPocketWinner {
+                            user: User {
+                                uid: info.first()?.as_u64()?,
+                                uname: info.get(1)?.as_str()?.to_owned(),
+                                face: None,
+                            },
+                            gift_id,
+                            gift_name: award.map(|a| a.award_name.clone()).unwrap_or_default(),
+                            gift_price: award.map(|a| a.award_price).unwrap_or_default(),
+                        })
+                    })
+                    .collect();
+                Ok(RedPocketWinnerEvent {
+                    lot_id,
+                    total_num,
+                    winners,
+                }
+                .into())
+            }
+            Cmd::LiveOpenPlatformDm {
+                room_id,
+                user,
+                medal,
+                guard_level,
+                msg,
+                dm_type,
+                emoji_img_url,
+            } => {
+                let message = match dm_type {
+                    1 => DanmakuMessage::Emoticon {
+                        emoticon: Emoticon {
+                            unique_id: msg.clone(),
+                            height: 0,
+                            width: 0,
+                            url: emoji_img_url,
+                        },
+                        alt_message: msg,
                     },
+                    _ => DanmakuMessage::Plain { message: msg },
+                };
+                Ok(DanmakuEvent {
+                    flag: 0,
+                    message,
+                    user: user.into(),
+                    fans_medal: medal.into_medal(room_id, guard_level),
+                    guard_level,
+                    user_level: 0,
+                    mode: 1,
+                    font_size: 25,
+                    color: 0xFFFFFF,
                 }
-                .into(),
-            ),
-            Cmd::StopLiveRoomList { room_id_list } => Some(StopLiveEvent { room_id_list }.into()),
+                .into())
+            }
+            Cmd::LiveOpenPlatformSendGift {
+                room_id,
+                user,
+                medal,
+                guard_level,
+                gift_id,
+                gift_name,
+                gift_num,
+                price,
+                paid,
+            } => Ok(GiftEvent {
+                user: user.into(),
+                fans_medal: medal.into_medal(room_id, guard_level),
+                blindbox: None,
+                gift: Gift {
+                    action: "投喂".to_owned(),
+                    num: gift_num,
+                    gift_name,
+                    gift_id,
+                    price,
+                    coin_type: if paid {
+                        CoinType::Gold
+                    } else {
+                        CoinType::Silver
+                    },
+                    coin_count: price * gift_num,
+                },
+            }
+            .into()),
+            Cmd::LiveOpenPlatformSuperChat {
+                room_id,
+                user,
+                medal,
+                guard_level,
+                message,
+                rmb,
+            } => Ok(SuperChatEvent {
+                id: 0,
+                user: user.into(),
+                fans_medal: medal.into_medal(room_id, guard_level),
+                price: rmb,
+                message,
+                message_jpn: None,
+            }
+            .into()),
+            Cmd::LiveOpenPlatformGuard {
+                user_info,
+                guard_level,
+                price,
+                ..
+            } => Ok(GuardBuyEvent {
+                level: guard_level,
+                price,
+                user: user_info.into(),
+            }
+            .into()),
+            Cmd::LiveStatusChange { status, live_time } => {
+                Ok(LiveStatusEvent { status, live_time }.into())
+            }
+            Cmd::NoticeMsg {
+                msg_type,
+                name,
+                msg_common,
+                msg_self,
+                real_roomid,
+                link_url,
+            } => Ok(NoticeEvent {
+                msg_type,
+                name,
+                template: msg_common,
+                template_self: msg_self,
+                roomid: real_roomid,
+                link_url,
+            }
+            .into()),
+            Cmd::Unknown { cmd, value } => {
+                tracing::trace!(cmd, "unknown cmd");
+                Err(Box::new(Cmd::Unknown { cmd, value }))
+            }
             rest => {
-                log::debug!("unhandled cmd: {:?}", rest);
-                None
+                tracing::debug!(cmd = ?rest, "unhandled cmd");
+                Err(Box::new(rest))
             }
         }
     }
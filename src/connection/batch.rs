@@ -0,0 +1,104 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures_util::{FutureExt, Stream, StreamExt};
+
+use crate::event::Event;
+
+/// 把事件流合并为批次，见[`Connection::batched`](super::Connection::batched)
+///
+/// 攒够`max_events`个事件，或者批次中第一个事件到达后经过`max_delay`时产生一批，
+/// 出错时先产生已经攒下的事件，再产生错误，事件流结束时产生剩余的事件
+pub struct Batched<S, E> {
+    inner: S,
+    max_events: usize,
+    max_delay: Duration,
+    batch: Vec<Event>,
+    deadline: Option<Pin<Box<tokio::time::Sleep>>>,
+    /// 已经攒下事件时遇到的错误（`Some`）或事件流结束（`None`），下一次产生
+    pending_end: Option<Option<E>>,
+}
+
+impl<S, E> Batched<S, E>
+where
+    S: Stream<Item = Result<Event, E>> + Unpin,
+{
+    pub fn new(inner: S, max_events: usize, max_delay: Duration) -> Self {
+        let max_events = max_events.max(1);
+        Self {
+            inner,
+            max_events,
+            max_delay,
+            batch: Vec::with_capacity(max_events),
+            deadline: None,
+            pending_end: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn take_batch(&mut self) -> Vec<Event> {
+        self.deadline = None;
+        std::mem::replace(&mut self.batch, Vec::with_capacity(self.max_events))
+    }
+}
+
+// 错误只会被移动，不会被pin
+impl<S: Unpin, E> Unpin for Batched<S, E> {}
+
+impl<S, E> Stream for Batched<S, E>
+where
+    S: Stream<Item = Result<Event, E>> + Unpin,
+{
+    type Item = Result<Vec<Event>, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(end) = self.pending_end.take() {
+            return Poll::Ready(end.map(Err));
+        }
+        loop {
+            match self.inner.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(event))) => {
+                    if self.batch.is_empty() {
+                        let deadline = tokio::time::sleep(self.max_delay);
+                        self.deadline = Some(Box::pin(deadline));
+                    }
+                    self.batch.push(event);
+                    if self.batch.len() >= self.max_events {
+                        return Poll::Ready(Some(Ok(self.take_batch())));
+                    }
+                }
+                Poll::Ready(end) => {
+                    let end = end.map(|item| match item {
+                        Err(e) => e,
+                        Ok(_) => unreachable!("events are handled above"),
+                    });
+                    if self.batch.is_empty() {
+                        return Poll::Ready(end.map(Err));
+                    }
+                    self.pending_end = Some(end);
+                    return Poll::Ready(Some(Ok(self.take_batch())));
+                }
+                Poll::Pending => break,
+            }
+        }
+        let expired = self
+            .deadline
+            .as_mut()
+            .is_some_and(|deadline| deadline.poll_unpin(cx).is_ready());
+        if expired {
+            Poll::Ready(Some(Ok(self.take_batch())))
+        } else {
+            Poll::Pending
+        }
+    }
+}
@@ -1,3 +1,28 @@
+//! 与弹幕服务器的连接
+//!
+//! [`Connection`]是拉取式的事件流，没有内部的广播通道，消费者处理得慢时不会丢失事件：
+//! 只有上一批事件被取走后才会解析下一个websocket帧，tokio连接的后台任务最多预读`buffer_capacity`帧（用来及时测量心跳的往返时间），
+//! 之后停止读取，积压的数据留在TCP缓冲区中。
+//! 需要多个消费者时，由调用方自行分发，并决定跟不上时的处理方式
+//!
+//! 事件按收到数据包的顺序发出，不按包头的`sequence`重排，序号和跳号只记录在[`ConnectionStats`]中
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use crate::{
+    dedup::Deduplicator,
+    event::Event,
+    filter::{CmdFilter, DanmakuSampler, EventFilter},
+    middleware::Middlewares,
+    packet::{Data, Diagnostic, RawPacket},
+};
+
 #[derive(Debug)]
 pub enum WsConnectError {
     #[cfg(feature = "rt_tokio")]
@@ -8,6 +33,14 @@ pub enum WsConnectError {
     JsError(gloo_utils::errors::JsError),
     UnexpecedEnd,
     AuthFailed,
+    /// 超过`handshake_timeout`没有完成websocket握手
+    HandshakeTimeout,
+    /// 超过`auth_timeout`没有收到鉴权回复
+    AuthTimeout,
+    /// 鉴权回复的`code`不为0，通常是token已经失效
+    AuthRejected {
+        code: i64,
+    },
 }
 
 impl std::fmt::Display for WsConnectError {
@@ -22,16 +55,62 @@ impl std::fmt::Display for WsConnectError {
             WsError(e) => write!(f, "WebSocket错误：{}", e),
             UnexpecedEnd => write!(f, "连接意外关闭"),
             AuthFailed => write!(f, "鉴权失败"),
+            HandshakeTimeout => write!(f, "握手超时"),
+            AuthTimeout => write!(f, "等待鉴权回复超时"),
+            AuthRejected { code } => write!(f, "鉴权被拒绝：code {}", code),
+        }
+    }
+}
+
+impl std::error::Error for WsConnectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(any(feature = "rt_tokio", feature = "rt_wasm"))]
+            WsConnectError::WsError(e) => Some(e),
+            #[cfg(feature = "rt_wasm")]
+            WsConnectError::JsError(e) => Some(e),
+            _ => None,
         }
     }
 }
 
-impl std::error::Error for WsConnectError {}
+/// 检查鉴权回复，只有明确返回非0的`code`时才失败
+pub(crate) fn check_auth_reply(reply: &[u8]) -> Result<(), WsConnectError> {
+    let packet = RawPacket::from_buffer(reply);
+    tracing::debug!(reply = ?packet, "auth reply");
+    match packet.ok().and_then(|packet| packet.auth_reply_code()) {
+        Some(0) | None => Ok(()),
+        Some(code) => {
+            tracing::error!(code, "auth rejected");
+            Err(WsConnectError::AuthRejected { code })
+        }
+    }
+}
+
+/// `duration`为`None`时不限时，超时时返回`None`
+pub(crate) async fn timeout<F: std::future::Future>(
+    duration: Option<std::time::Duration>,
+    future: F,
+) -> Option<F::Output> {
+    use futures_util::future::{select, Either};
+    let Some(duration) = duration else {
+        return Some(future.await);
+    };
+    let future = std::pin::pin!(future);
+    let timer = std::pin::pin!(sleep(duration));
+    match select(future, timer).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum EventStreamError {
     ConnectionClosed,
-    WsError(String),
+    /// 保留底层的websocket错误，可以通过[`source`](std::error::Error::source)取得
+    WsError(Arc<dyn std::error::Error + Send + Sync>),
+    /// 超过`stall_timeout`没有收到任何数据
+    Stalled,
 }
 
 impl std::fmt::Display for EventStreamError {
@@ -40,11 +119,531 @@ impl std::fmt::Display for EventStreamError {
         match self {
             ConnectionClosed => write!(f, "连接已关闭"),
             WsError(e) => write!(f, "WebSocket错误：{}", e),
+            Stalled => write!(f, "长时间没有收到数据"),
+        }
+    }
+}
+
+impl std::error::Error for EventStreamError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EventStreamError::WsError(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// 连接结束的原因
+#[derive(Debug, Clone)]
+pub enum CloseReason {
+    /// 调用了`abort`或者取消了[`ConnectConfig::cancel`]
+    Cancelled,
+    /// 服务器关闭了连接
+    ServerClosed,
+    /// 读取数据时出错
+    Error(EventStreamError),
+    /// 发送心跳或数据包失败
+    SendFailed(String),
+    /// 后台任务panic
+    Panicked(String),
+}
+
+impl CloseReason {
+    /// 是否是主动取消或服务器正常关闭
+    pub fn is_clean(&self) -> bool {
+        matches!(self, CloseReason::Cancelled | CloseReason::ServerClosed)
+    }
+}
+
+impl std::fmt::Display for CloseReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CloseReason::Cancelled => write!(f, "已取消"),
+            CloseReason::ServerClosed => write!(f, "服务器关闭了连接"),
+            CloseReason::Error(e) => write!(f, "连接出错：{}", e),
+            CloseReason::SendFailed(e) => write!(f, "发送失败：{}", e),
+            CloseReason::Panicked(e) => write!(f, "后台任务panic：{}", e),
+        }
+    }
+}
+
+/// 建立连接时使用的配置
+///
+/// # 说明
+/// - `cancel` 取消时，心跳任务停止，事件流结束
+/// - `cmd_filter` 按b站原始的cmd丢弃消息，在解析json之前进行，比`filter`开销更小
+/// - `filter` 被过滤的事件不会出现在事件流中
+/// - `dedup` 丢弃窗口内重复的消息，多个连接使用同一个[`Deduplicator`]时共享窗口
+/// - `parsers` 在内置解析之前尝试的cmd解析器，可以支持库中还没有的cmd，见[`CmdParsers`](crate::parser::CmdParsers)
+/// - `middlewares` 依次处理通过过滤的事件，可以修改或丢弃事件
+/// - `sampler` 经过中间件之后对弹幕限流，其他事件不受影响，见[`DanmakuSampler`]，重连后重新计数
+/// - `raw_tap` 收到的每个原始数据包都会先发送一份到这里（包头中有服务器的`sequence`），再解析为事件，接收端不再读取时要及时丢弃，否则数据包会一直积压
+/// - `diagnostics` 格式错误的数据包、解析失败的消息和服务器的Close帧会附带原始数据发送到这里，见[`Diagnostic`]，接收端同样要及时读取或丢弃
+/// - `heartbeat` 心跳的间隔和内容
+/// - `handshake_timeout` 建立TCP连接、TLS和websocket握手的总时间，超时产生[`WsConnectError::HandshakeTimeout`]，默认为10秒，`None`时不限时
+/// - `auth_timeout` 发送鉴权包后等待回复的时间，超时产生[`WsConnectError::AuthTimeout`]，默认为10秒，`None`时不限时
+/// - `backfill_history` [`Connector`](crate::Connector)连接成功后，用`Connector::client`获取最近的弹幕历史并先产生（见[`HttpClient::danmaku_history`](crate::http::HttpClient::danmaku_history)），最多等待5秒，
+///   [`Connector::connect_switching`](crate::Connector::connect_switching)只在第一次连接时产生
+/// - `resolver` 自定义弹幕服务器的域名解析，见[`Resolver`]，默认使用系统解析
+/// - `tls_config` websocket连接使用的`rustls::ClientConfig`，可以添加自定义的根证书，需要`custom_tls`特性，默认使用webpki的根证书
+/// - `headers` websocket握手请求中额外的请求头，例如[`browser_headers`](crate::http::browser_headers)，浏览器中不允许设置，wasm会忽略
+/// - `stall_timeout` 超过这个时间没有收到任何数据时，事件流产生[`EventStreamError::Stalled`]，服务器每次心跳都会回复人气值，所以应当大于心跳间隔，默认为90秒，`None`时不检查
+/// - `max_rtt` 连续3次心跳延迟超过这个时间时，[`Connector::connect_switching`](crate::Connector::connect_switching)切换服务器，默认不检查
+/// - `keep_raw` 在事件的`raw`字段中保留原始json，会增加内存占用
+/// - `buffer_capacity` tokio连接的读取任务最多预读的websocket帧数，默认为32，满了之后暂停读取，由TCP的流量控制让服务器减慢发送，不会丢弃数据，wasm会忽略；
+///   [`Room`](crate::room::Room)分发事件时的积压上限是`Room::spawn`的`capacity`，跟不上时按[`LagPolicy`](crate::room::LagPolicy)处理
+/// - `retry` [`Connector::connect_retrying`](crate::Connector::connect_retrying)和[`Connector::connect_switching`](crate::Connector::connect_switching)使用的重试策略
+#[derive(Debug, Clone)]
+pub struct ConnectConfig {
+    #[cfg(feature = "rt_tokio")]
+    pub cancel: Option<tokio_util::sync::CancellationToken>,
+    pub cmd_filter: CmdFilter,
+    pub filter: Option<EventFilter>,
+    pub dedup: Option<Deduplicator>,
+    pub parsers: crate::parser::CmdParsers,
+    pub middlewares: Middlewares,
+    pub sampler: Option<DanmakuSampler>,
+    pub raw_tap: Option<RawTap>,
+    pub diagnostics: Option<DiagnosticTap>,
+    pub heartbeat: HeartbeatConfig,
+    pub handshake_timeout: Option<std::time::Duration>,
+    pub auth_timeout: Option<std::time::Duration>,
+    pub backfill_history: bool,
+    #[cfg(feature = "rt_tokio")]
+    pub resolver: Option<Resolver>,
+    #[cfg(feature = "custom_tls")]
+    pub tls_config: Option<Arc<rustls::ClientConfig>>,
+    pub headers: Vec<(String, String)>,
+    #[cfg(feature = "rt_tokio")]
+    pub stall_timeout: Option<std::time::Duration>,
+    pub max_rtt: Option<std::time::Duration>,
+    pub keep_raw: bool,
+    pub buffer_capacity: Option<usize>,
+    pub retry: crate::retry::RetryPolicy,
+}
+
+impl Default for ConnectConfig {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "rt_tokio")]
+            cancel: None,
+            cmd_filter: CmdFilter::default(),
+            filter: None,
+            dedup: None,
+            parsers: Default::default(),
+            middlewares: Middlewares::default(),
+            sampler: None,
+            raw_tap: None,
+            diagnostics: None,
+            heartbeat: HeartbeatConfig::default(),
+            handshake_timeout: Some(std::time::Duration::from_secs(10)),
+            auth_timeout: Some(std::time::Duration::from_secs(10)),
+            backfill_history: false,
+            #[cfg(feature = "rt_tokio")]
+            resolver: None,
+            #[cfg(feature = "custom_tls")]
+            tls_config: None,
+            headers: vec![],
+            #[cfg(feature = "rt_tokio")]
+            stall_timeout: Some(std::time::Duration::from_secs(90)),
+            max_rtt: None,
+            keep_raw: false,
+            buffer_capacity: None,
+            retry: Default::default(),
+        }
+    }
+}
+
+/// 心跳配置
+///
+/// # 说明
+/// - `interval` 发送间隔，默认为30秒，最小为1秒
+/// - `body` 心跳包的内容，默认为`[object Object]`
+#[derive(Debug, Clone)]
+pub struct HeartbeatConfig {
+    pub interval: std::time::Duration,
+    pub body: Vec<u8>,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            interval: std::time::Duration::from_secs(30),
+            body: b"[object Object]".to_vec(),
         }
     }
 }
 
-impl std::error::Error for EventStreamError {}
+impl HeartbeatConfig {
+    pub(crate) fn interval(&self) -> std::time::Duration {
+        self.interval.max(std::time::Duration::from_secs(1))
+    }
+
+    pub(crate) fn packet(&self) -> RawPacket {
+        RawPacket::build(crate::packet::Operation::Heartbeat, self.body.clone())
+    }
+}
+
+/// 原始数据包的发送端，配合[`futures::channel::mpsc::unbounded`]使用
+pub type RawTap = futures::channel::mpsc::UnboundedSender<RawPacket>;
+
+/// [`Diagnostic`]的发送端，配合[`futures::channel::mpsc::unbounded`]使用
+pub type DiagnosticTap = futures::channel::mpsc::UnboundedSender<Diagnostic>;
+
+/// 连接的收发统计
+///
+/// # 说明
+/// - `packets_in`，`bytes_in` 收到的websocket帧数和字节数
+/// - `packets_out`，`bytes_out` 发出的心跳包和用户数据包的数量和字节数
+/// - `decompressed_bytes` 压缩数据包解压后的字节数
+/// - `parse_errors` 按cmd统计的解析失败次数，没有cmd的消息记在空字符串下
+/// - `rtt` 最近一次心跳的往返时间，从发出心跳包到从socket读到人气值回复，使用单调时钟
+/// - `avg_rtt` 所有心跳往返时间的平均值，`rtt_samples`为样本数，`rtt_total`为所有样本的总和
+/// - `last_sequence_in` 最近收到的非0序号，服务器推送的消息序号通常为0，不计入
+/// - `sequence_gaps` 收到的非0序号向前跳过（中间有序号没有收到）的次数，`sequence_out`为最近发出的数据包的序号，鉴权包为1
+///
+/// 序号只用于观察，收到的数据包不会按序号重排或缓冲：同一个websocket连接内的数据包已经按TCP的顺序到达，
+/// 服务器推送的消息序号又通常为0，没有可以排序的依据，事件仍然按收到的顺序发出
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionStats {
+    pub packets_in: u64,
+    pub bytes_in: u64,
+    pub packets_out: u64,
+    pub bytes_out: u64,
+    pub decompressed_bytes: u64,
+    pub parse_errors: HashMap<String, u64>,
+    pub rtt: Option<Duration>,
+    pub avg_rtt: Option<Duration>,
+    pub rtt_samples: u64,
+    pub rtt_total: Duration,
+    pub last_sequence_in: Option<u32>,
+    pub sequence_gaps: u64,
+    pub sequence_out: u32,
+}
+
+impl ConnectionStats {
+    fn record_sequence_in(&mut self, sequence: u32) {
+        if sequence == 0 {
+            return;
+        }
+        if let Some(last) = self.last_sequence_in {
+            if sequence > last.saturating_add(1) {
+                tracing::debug!(last, sequence, "数据包序号不连续");
+                self.sequence_gaps += 1;
+            }
+        }
+        self.last_sequence_in = Some(sequence);
+    }
+}
+
+/// 连接与心跳任务共享的统计
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StatsHandle {
+    stats: Arc<Mutex<ConnectionStats>>,
+    /// 还没有收到回复的心跳的发送时间，[`monotonic_micros`]，0表示没有
+    heartbeat_sent_at: Arc<AtomicU64>,
+    /// 读取任务收到心跳回复时立即更新，不等事件被消费
+    #[cfg(feature = "rt_tokio")]
+    popularity: tokio::sync::watch::Sender<Option<u32>>,
+}
+
+/// 单调时钟的微秒数，只用来计算时间差，不会为0
+fn monotonic_micros() -> u64 {
+    #[cfg(all(feature = "rt_wasm", target_arch = "wasm32"))]
+    {
+        // wasm中没有`Instant`，使用高精度的`performance.now()`
+        let now = js_sys::Reflect::get(&js_sys::global(), &"performance".into())
+            .ok()
+            .and_then(|performance| {
+                let now = js_sys::Reflect::get(&performance, &"now".into()).ok()?;
+                js_sys::Function::from(now)
+                    .call0(&performance)
+                    .ok()?
+                    .as_f64()
+            })
+            .unwrap_or_else(js_sys::Date::now);
+        (now * 1000.0) as u64 + 1
+    }
+    #[cfg(not(all(feature = "rt_wasm", target_arch = "wasm32")))]
+    {
+        static START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+        START
+            .get_or_init(std::time::Instant::now)
+            .elapsed()
+            .as_micros() as u64
+            + 1
+    }
+}
+
+impl StatsHandle {
+    pub fn update(&self, f: impl FnOnce(&mut ConnectionStats)) {
+        f(&mut self.stats.lock().expect("stats poisoned"))
+    }
+
+    pub fn snapshot(&self) -> ConnectionStats {
+        self.stats.lock().expect("stats poisoned").clone()
+    }
+
+    /// 发出心跳包时记录时间，上一个心跳还没有回复时以新的为准
+    pub fn record_heartbeat(&self) {
+        self.heartbeat_sent_at
+            .store(monotonic_micros(), Ordering::Relaxed);
+    }
+
+    /// 收到心跳回复时计算往返时间，没有等待回复的心跳时忽略
+    pub fn record_heartbeat_reply(&self) -> Option<Duration> {
+        let sent_at = self.heartbeat_sent_at.swap(0, Ordering::Relaxed);
+        if sent_at == 0 {
+            return None;
+        }
+        let rtt = Duration::from_micros(monotonic_micros().saturating_sub(sent_at));
+        self.update(|stats| {
+            stats.rtt_samples += 1;
+            stats.rtt_total += rtt;
+            stats.rtt = Some(rtt);
+            stats.avg_rtt = Some(stats.rtt_total / stats.rtt_samples as u32);
+        });
+        Some(rtt)
+    }
+
+    /// 从socket读到一帧时调用，是心跳回复时立即计算往返时间、更新人气值，不等事件被消费
+    #[cfg(feature = "rt_tokio")]
+    pub fn observe_frame(&self, bin: &bytes::Bytes) -> Option<Duration> {
+        let packet = RawPacket::from_bytes(bin.clone()).ok()?;
+        if packet.head().opcode != crate::packet::Operation::HeartbeatReply as u32 {
+            return None;
+        }
+        if let Some(popularity) = packet.body().first_chunk::<4>() {
+            self.popularity
+                .send_replace(Some(u32::from_be_bytes(*popularity)));
+        }
+        self.record_heartbeat_reply()
+    }
+
+    pub fn record_out(&self, bytes: usize) {
+        self.update(|stats| {
+            stats.packets_out += 1;
+            stats.bytes_out += bytes as u64;
+        })
+    }
+
+    /// 下一个发出的数据包的序号，鉴权包为1，之后依次递增
+    pub fn next_sequence(&self) -> u32 {
+        let mut sequence = 0;
+        self.update(|stats| {
+            stats.sequence_out = stats.sequence_out.max(1).wrapping_add(1);
+            sequence = stats.sequence_out;
+        });
+        sequence
+    }
+}
+
+/// 把收到的二进制帧解析为事件，tokio与wasm连接共用
+pub(crate) struct Processor {
+    #[cfg_attr(not(any(feature = "metrics", feature = "rt_tokio")), allow(dead_code))]
+    roomid: u64,
+    buffer: VecDeque<Result<Event, EventStreamError>>,
+    cmd_filter: CmdFilter,
+    filter: Option<EventFilter>,
+    dedup: Option<Deduplicator>,
+    parsers: crate::parser::CmdParsers,
+    middlewares: Middlewares,
+    sampler: Option<DanmakuSampler>,
+    raw_tap: Option<RawTap>,
+    diagnostics: Option<DiagnosticTap>,
+    stats: StatsHandle,
+    keep_raw: bool,
+    #[cfg(not(feature = "rt_tokio"))]
+    popularity: Option<u32>,
+}
+
+impl Processor {
+    pub fn new(roomid: u64, config: &ConnectConfig) -> Self {
+        Self {
+            roomid,
+            buffer: VecDeque::with_capacity(256),
+            cmd_filter: config.cmd_filter.clone(),
+            filter: config.filter.clone(),
+            dedup: config.dedup.clone(),
+            parsers: config.parsers.clone(),
+            middlewares: config.middlewares.clone(),
+            sampler: config.sampler.clone(),
+            raw_tap: config.raw_tap.clone(),
+            diagnostics: config.diagnostics.clone(),
+            stats: StatsHandle::default(),
+            keep_raw: config.keep_raw,
+            #[cfg(not(feature = "rt_tokio"))]
+            popularity: None,
+        }
+    }
+
+    pub fn stats(&self) -> &StatsHandle {
+        &self.stats
+    }
+
+    /// 最近一次收到的人气值，tokio在读取时更新，wasm在事件被消费时更新
+    pub fn popularity(&self) -> Option<u32> {
+        #[cfg(feature = "rt_tokio")]
+        {
+            *self.stats.popularity.borrow()
+        }
+        #[cfg(not(feature = "rt_tokio"))]
+        {
+            self.popularity
+        }
+    }
+
+    #[cfg(feature = "rt_tokio")]
+    pub fn subscribe_popularity(&self) -> tokio::sync::watch::Receiver<Option<u32>> {
+        self.stats.popularity.subscribe()
+    }
+
+    /// 插入不是来自弹幕服务器的事件，同样经过`filter`和`middlewares`，不经过`dedup`
+    pub fn inject(&mut self, events: impl IntoIterator<Item = Event>) {
+        for event in events {
+            if self.filter.as_ref().is_some_and(|f| !f.accept(&event)) {
+                continue;
+            }
+            if let Some(event) = self.middlewares.apply(event) {
+                self.buffer.push_back(Ok(event));
+            }
+        }
+    }
+
+    pub fn diagnose(&mut self, diagnostic: Diagnostic) {
+        if let Some(tap) = &self.diagnostics {
+            if tap.unbounded_send(diagnostic).is_err() {
+                tracing::debug!("诊断信息的接收端已关闭");
+                self.diagnostics = None;
+            }
+        }
+    }
+
+    fn deliver(buffer: &mut VecDeque<Result<Event, EventStreamError>>, _roomid: u64, event: Event) {
+        #[cfg(feature = "metrics")]
+        metrics::counter!(
+            "bilive_danmaku_events_total",
+            "roomid" => _roomid.to_string(),
+            "cmd" => event.data.cmd()
+        )
+        .increment(1);
+        buffer.push_back(Ok(event))
+    }
+
+    pub fn pop(&mut self) -> Option<Result<Event, EventStreamError>> {
+        self.buffer.pop_front()
+    }
+
+    pub fn feed(&mut self, bin: bytes::Bytes) {
+        let bin_len = bin.len() as u64;
+        #[cfg(feature = "metrics")]
+        {
+            let roomid = self.roomid.to_string();
+            metrics::counter!("bilive_danmaku_packets_received_total", "roomid" => roomid.clone())
+                .increment(1);
+            metrics::counter!("bilive_danmaku_bytes_received_total", "roomid" => roomid)
+                .increment(bin_len);
+        }
+        let raw = bin.clone();
+        let packet = match RawPacket::from_bytes(bin) {
+            Ok(packet) => packet,
+            Err(e) => {
+                self.diagnose(Diagnostic::MalformedPacket {
+                    error: e.to_string(),
+                    raw,
+                });
+                #[cfg(feature = "metrics")]
+                metrics::counter!("bilive_danmaku_parse_errors_total", "roomid" => self.roomid.to_string())
+                    .increment(1);
+                tracing::warn!(error = %e, "丢弃格式错误的数据包");
+                self.stats.update(|stats| {
+                    stats.packets_in += 1;
+                    stats.bytes_in += bin_len;
+                    *stats.parse_errors.entry(String::new()).or_default() += 1;
+                });
+                return;
+            }
+        };
+        let mut decompressed = 0;
+        let sequence = packet.head().sequence;
+        if let Some(tap) = &self.raw_tap {
+            if tap.unbounded_send(packet.clone()).is_err() {
+                tracing::debug!("原始数据包的接收端已关闭");
+                self.raw_tap = None;
+            }
+        }
+        let datas = packet.get_datas(&mut decompressed, &self.cmd_filter);
+        let mut parse_errors = vec![];
+        for data in datas {
+            if let Data::Invalid(diagnostic) = data {
+                self.diagnose(diagnostic);
+                continue;
+            }
+            if let Data::Deflate(text) = &data {
+                self.diagnose(Diagnostic::Deflate { text: text.clone() });
+            }
+            if let Data::Popularity(_popularity) = data {
+                let _rtt = self.stats.record_heartbeat_reply();
+                #[cfg(feature = "metrics")]
+                if let Some(rtt) = _rtt {
+                    metrics::histogram!("bilive_danmaku_heartbeat_rtt_seconds", "roomid" => self.roomid.to_string())
+                        .record(rtt.as_secs_f64());
+                }
+                // tokio的读取任务已经更新过人气值
+                #[cfg(not(feature = "rt_tokio"))]
+                {
+                    self.popularity = Some(_popularity);
+                }
+            }
+            if let (Some(dedup), Data::Json(json)) = (&self.dedup, &data) {
+                if !dedup.check_json(json) {
+                    tracing::debug!(json = %json, "丢弃重复的消息");
+                    continue;
+                }
+            }
+            match data.into_event(self.keep_raw, &self.parsers) {
+                Ok(Some(event)) if self.filter.as_ref().is_some_and(|f| !f.accept(&event)) => {
+                    tracing::trace!(cmd = event.data.cmd(), "事件被过滤");
+                }
+                Ok(Some(event)) => {
+                    let Some(event) = self.middlewares.apply(event) else {
+                        tracing::trace!("事件被中间件丢弃");
+                        continue;
+                    };
+                    match &mut self.sampler {
+                        Some(sampler) => {
+                            for event in sampler.sample(event) {
+                                Self::deliver(&mut self.buffer, self.roomid, event);
+                            }
+                        }
+                        None => Self::deliver(&mut self.buffer, self.roomid, event),
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    #[cfg(feature = "metrics")]
+                    metrics::counter!("bilive_danmaku_parse_errors_total", "roomid" => self.roomid.to_string())
+                        .increment(1);
+                    tracing::warn!(error = %e, "解析数据包失败");
+                    parse_errors.push(e.cmd());
+                    if let Some(diagnostic) = e.diagnostic() {
+                        self.diagnose(diagnostic);
+                    }
+                }
+            }
+        }
+        self.stats.update(|stats| {
+            stats.packets_in += 1;
+            stats.bytes_in += bin_len;
+            stats.decompressed_bytes += decompressed;
+            stats.record_sequence_in(sequence);
+            for cmd in parse_errors {
+                *stats.parse_errors.entry(cmd).or_default() += 1;
+            }
+        });
+    }
+}
+
 // #[async_trait]
 // pub trait Connector: Stream<Item = Result<Event, EventStreamError>> + StreamExt
 // where
@@ -56,12 +655,36 @@ impl std::error::Error for EventStreamError {}
 //     fn abort(self);
 // }
 
+#[cfg(feature = "rt_tokio")]
+mod batch;
+#[cfg(feature = "rt_tokio")]
+pub use batch::Batched;
+#[cfg(feature = "rt_tokio")]
+mod resolver;
+#[cfg(feature = "rt_tokio")]
+pub use resolver::Resolver;
+/// 与websocket连接使用的版本相同的rustls，用来构造[`ConnectConfig::tls_config`]
+///```no_run,ignore
+///use bilive_danmaku::connection::rustls;
+///let mut roots = rustls::RootCertStore::empty();
+///roots.add(corporate_ca)?;
+///let tls = rustls::ClientConfig::builder()
+///    .with_root_certificates(roots)
+///    .with_no_client_auth();
+///connector.config.tls_config = Some(std::sync::Arc::new(tls));
+///```
+#[cfg(feature = "custom_tls")]
+pub use rustls;
 #[cfg(feature = "rt_tokio")]
 mod tokio_connection;
 #[cfg(feature = "rt_tokio")]
+pub(crate) use tokio_connection::sleep;
+#[cfg(feature = "rt_tokio")]
 pub use tokio_connection::TokioConnection as Connection;
 
 #[cfg(feature = "rt_wasm")]
 mod wasm_connection;
 #[cfg(feature = "rt_wasm")]
+pub(crate) use wasm_connection::sleep;
+#[cfg(feature = "rt_wasm")]
 pub use wasm_connection::WasmConnection as Connection;
@@ -0,0 +1,94 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    future::Future,
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use futures_util::future::BoxFuture;
+
+pub type ResolveFn =
+    Arc<dyn Fn(String, u16) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>> + Send + Sync>;
+
+/// 弹幕服务器的域名解析，克隆后共享同样的配置
+///
+/// 依次查找：手动指定的地址、自定义的解析函数、系统解析
+///```no_run,ignore
+///use bilive_danmaku::connection::Resolver;
+///connector.config.resolver = Some(
+///    Resolver::new()
+///        // 固定到某个CDN节点
+///        .with_override("broadcastlv.chat.bilibili.com", ["1.2.3.4".parse()?])
+///        .with_resolver(|host, port| async move { my_dns_lookup(&host, port).await }),
+///);
+///```
+#[derive(Clone, Default)]
+pub struct Resolver {
+    overrides: HashMap<String, Vec<IpAddr>>,
+    custom: Option<ResolveFn>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 把`host`解析为固定的地址，多个地址时按顺序尝试
+    pub fn with_override(
+        mut self,
+        host: impl Into<String>,
+        addrs: impl IntoIterator<Item = IpAddr>,
+    ) -> Self {
+        self.overrides
+            .insert(host.into(), addrs.into_iter().collect());
+        self
+    }
+
+    /// 没有手动指定地址的域名使用`resolver`解析
+    pub fn with_resolver<F, Fut>(mut self, resolver: F) -> Self
+    where
+        F: Fn(String, u16) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = io::Result<Vec<SocketAddr>>> + Send + 'static,
+    {
+        self.custom = Some(Arc::new(move |host, port| Box::pin(resolver(host, port))));
+        self
+    }
+
+    pub async fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        if let Some(addrs) = self.overrides.get(host) {
+            return Ok(addrs.iter().map(|ip| SocketAddr::new(*ip, port)).collect());
+        }
+        if let Some(custom) = &self.custom {
+            return custom(host.to_string(), port).await;
+        }
+        Ok(tokio::net::lookup_host((host, port)).await?.collect())
+    }
+
+    /// 解析后依次尝试每个地址，返回第一个成功的TCP连接
+    pub(crate) async fn connect(&self, host: &str, port: u16) -> io::Result<tokio::net::TcpStream> {
+        let mut last_error = None;
+        for addr in self.resolve(host, port).await? {
+            match tokio::net::TcpStream::connect(addr).await {
+                Ok(tcp) => return Ok(tcp),
+                Err(e) => {
+                    tracing::debug!(host, %addr, error = %e, "连接失败，尝试下一个地址");
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("{} 没有解析到地址", host))
+        }))
+    }
+}
+
+impl Debug for Resolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Resolver")
+            .field("overrides", &self.overrides)
+            .field("custom", &self.custom.is_some())
+            .finish()
+    }
+}
@@ -1,21 +1,45 @@
 use super::*;
-use futures_util::{stream::SplitStream, SinkExt, Stream, StreamExt};
-use std::collections::VecDeque;
+use futures_util::{stream::SplitStream, FutureExt, SinkExt, Stream, StreamExt};
 // use tungstenite;
 use crate::{
     connection::WsConnectError,
-    event::Event,
     packet::{Auth, Operation, RawPacket},
 };
 use tokio_tungstenite as tokio_ws2;
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
 use tokio_ws2::tungstenite as ws2;
+use tracing::Instrument;
 type WsStream = tokio_ws2::WebSocketStream<tokio_ws2::MaybeTlsStream<tokio::net::TcpStream>>;
 type WsRx = SplitStream<WsStream>;
+type Frame = Result<ws2::Message, ws2::Error>;
+
+/// 没有设置`buffer_capacity`时，读取任务最多预读的帧数，超过后停止读取，积压的数据留在TCP缓冲区中
+const READ_AHEAD: usize = 32;
 
 pub struct TokioConnection {
-    ws_rx: WsRx,
-    hb_handle: tokio::task::JoinHandle<()>,
-    buffer: VecDeque<Result<Event, EventStreamError>>, // rx_handle: tokio::task::JoinHandle<()>,
+    /// 由读取任务转发的websocket帧
+    frames: tokio::sync::mpsc::Receiver<Frame>,
+    /// 后台任务，drop时全部abort，不会在连接对象之后继续持有socket
+    tasks: tokio::task::JoinSet<()>,
+    outbound_tx: futures::channel::mpsc::UnboundedSender<RawPacket>,
+    processor: Processor,
+    span: tracing::Span,
+    cancel: CancellationToken,
+    cancelled: std::pin::Pin<Box<WaitForCancellationFutureOwned>>,
+    stall: Option<(std::time::Duration, std::pin::Pin<Box<tokio::time::Sleep>>)>,
+    close: tokio::sync::watch::Sender<Option<CloseReason>>,
+}
+
+/// 只记录第一个结束原因
+fn set_close_reason(close: &tokio::sync::watch::Sender<Option<CloseReason>>, reason: CloseReason) {
+    close.send_if_modified(|current| {
+        if current.is_some() {
+            return false;
+        }
+        tracing::debug!(reason = %reason, "connection closed");
+        *current = Some(reason);
+        true
+    });
 }
 
 impl Stream for TokioConnection {
@@ -28,31 +52,54 @@ impl Stream for TokioConnection {
         use std::task::Poll::*;
         use ws2::Message::*;
         use EventStreamError::*;
-        if let Some(event) = self.buffer.pop_front() {
+        let span = self.span.clone();
+        let _enter = span.enter();
+        if self.cancelled.poll_unpin(cx).is_ready() {
+            set_close_reason(&self.close, CloseReason::Cancelled);
+            return Ready(None);
+        }
+        if let Some(event) = self.processor.pop() {
             return Ready(Some(event));
         }
         // 读取新序列
-        match self.ws_rx.poll_next_unpin(cx) {
+        let polled = self.frames.poll_recv(cx);
+        if let Some((timeout, stall)) = &mut self.stall {
+            let deadline = tokio::time::Instant::now() + *timeout;
+            if polled.is_ready() {
+                stall.as_mut().reset(deadline);
+            } else if stall.poll_unpin(cx).is_ready() {
+                stall.as_mut().reset(deadline);
+                tracing::warn!("连接停滞");
+                return Ready(Some(Err(Stalled)));
+            }
+        }
+        match polled {
             Ready(Some(Ok(Binary(bin)))) => {
-                let packet = RawPacket::from_buffer(&bin);
-                for data in packet.get_datas() {
-                    match data.into_event() {
-                        Ok(Some(event)) => self.buffer.push_back(Ok(event)),
-                        Ok(None) => {}
-                        Err(e) => {
-                            log::warn!("解析数据包失败：{}", e);
-                        }
-                    }
-                }
+                self.processor.feed(bin);
                 self.poll_next(cx)
             }
-            Ready(Some(Ok(Close(_)))) => Ready(Some(Err(ConnectionClosed))),
+            Ready(Some(Ok(Close(frame)))) => {
+                let (code, reason) = frame
+                    .map(|frame| (frame.code.into(), frame.reason.to_string()))
+                    .unwrap_or_default();
+                tracing::info!(code, reason, "服务器关闭连接");
+                self.processor.diagnose(Diagnostic::Closed { code, reason });
+                set_close_reason(&self.close, CloseReason::ServerClosed);
+                Ready(Some(Err(ConnectionClosed)))
+            }
             // 这不太可能发生，可能要标记一下
             Ready(Some(Ok(_))) => self.poll_next(cx),
             // 错误
-            Ready(Some(Err(e))) => Ready(Some(Err(WsError(e.to_string())))),
+            Ready(Some(Err(e))) => {
+                let error = WsError(Arc::new(e));
+                set_close_reason(&self.close, CloseReason::Error(error.clone()));
+                Ready(Some(Err(error)))
+            }
             // 接受到None
-            Ready(None) => Ready(None),
+            Ready(None) => {
+                set_close_reason(&self.close, CloseReason::ServerClosed);
+                Ready(None)
+            }
             Pending => Pending,
         }
     }
@@ -65,46 +112,308 @@ impl From<ws2::Error> for WsConnectError {
 }
 
 impl TokioConnection {
-    pub async fn connect(url: String, auth: Auth) -> Result<Self, WsConnectError> {
-        use ws2::Message::*;
-        let (mut ws_stream, _resp) = tokio_ws2::connect_async(url).await?;
+    pub async fn connect(
+        url: String,
+        auth: Auth,
+        config: &ConnectConfig,
+    ) -> Result<Self, WsConnectError> {
+        let roomid = auth.roomid();
+        let span = tracing::info_span!("connection", roomid, url = %url);
+        let cancel = match &config.cancel {
+            Some(cancel) => cancel.child_token(),
+            None => CancellationToken::new(),
+        };
+        Self::connect_inner(url, auth, config)
+            .instrument(span.clone())
+            .await
+            .map(|(ws_stream, roomid)| {
+                let processor = Processor::new(roomid, config);
+                let read_ahead = config.buffer_capacity.unwrap_or(READ_AHEAD).max(1);
+                let mut connection = Self::start(
+                    ws_stream,
+                    processor,
+                    config.heartbeat.clone(),
+                    read_ahead,
+                    span,
+                    cancel,
+                );
+                connection.stall = config
+                    .stall_timeout
+                    .map(|timeout| (timeout, Box::pin(tokio::time::sleep(timeout))));
+                connection
+            })
+    }
+
+    async fn connect_inner(
+        url: String,
+        auth: Auth,
+        config: &ConnectConfig,
+    ) -> Result<(WsStream, u64), WsConnectError> {
+        use ws2::{client::IntoClientRequest, http, Message::*};
+        let mut request = url.into_client_request()?;
+        for (name, value) in &config.headers {
+            let name = http::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| ws2::Error::HttpFormat(e.into()))?;
+            let value =
+                http::HeaderValue::from_str(value).map_err(|e| ws2::Error::HttpFormat(e.into()))?;
+            request.headers_mut().insert(name, value);
+        }
+        #[cfg(feature = "custom_tls")]
+        let tls = config.tls_config.clone().map(tokio_ws2::Connector::Rustls);
+        #[cfg(not(feature = "custom_tls"))]
+        let tls: Option<tokio_ws2::Connector> = None;
+        let handshake = async {
+            let resolver = config.resolver.clone().unwrap_or_default();
+            let uri = request.uri();
+            let host = uri.host().unwrap_or_default().to_string();
+            let port = uri.port_u16().unwrap_or(match uri.scheme_str() {
+                Some("ws") => 80,
+                _ => 443,
+            });
+            let tcp = resolver.connect(&host, port).await?;
+            #[cfg(any(feature = "rustls", feature = "native-tls"))]
+            return tokio_ws2::client_async_tls_with_config(request, tcp, None, tls).await;
+            // 没有启用TLS时只能连接ws://
+            #[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+            {
+                let _ = tls;
+                let tcp = tokio_ws2::MaybeTlsStream::Plain(tcp);
+                tokio_ws2::client_async_with_config(request, tcp, None).await
+            }
+        };
+        let (mut ws_stream, _resp) = timeout(config.handshake_timeout, handshake)
+            .await
+            .ok_or(WsConnectError::HandshakeTimeout)??;
+        let roomid = auth.roomid();
         let authpack_bin = RawPacket::build(Operation::Auth, auth.ser()).ser();
-        ws_stream.send(Binary(authpack_bin)).await?;
-        let resp = ws_stream.next().await.ok_or_else(|| {
-            log::error!("ws stream encounter unexpected end");
-            WsConnectError::UnexpecedEnd
-        })??;
+        ws_stream.send(Binary(authpack_bin.into())).await?;
+        let resp = timeout(config.auth_timeout, ws_stream.next())
+            .await
+            .ok_or_else(|| {
+                tracing::error!("auth reply timeout");
+                WsConnectError::AuthTimeout
+            })?
+            .ok_or_else(|| {
+                tracing::error!("ws stream encounter unexpected end");
+                WsConnectError::UnexpecedEnd
+            })??;
         match resp {
-            Binary(auth_reply_bin) => {
-                log::debug!("auth reply: {:?}", RawPacket::from_buffer(&auth_reply_bin));
-            }
+            Binary(auth_reply_bin) => check_auth_reply(&auth_reply_bin)?,
             _other => {
-                log::error!("auth reply is not a binary: {:?}", _other);
+                tracing::error!(reply = ?_other, "auth reply is not a binary");
                 return Err(WsConnectError::AuthFailed);
             }
         }
+        tracing::debug!("connected");
+        Ok((ws_stream, roomid))
+    }
+
+    fn start(
+        ws_stream: WsStream,
+        processor: Processor,
+        heartbeat: HeartbeatConfig,
+        read_ahead: usize,
+        span: tracing::Span,
+        cancel: CancellationToken,
+    ) -> Self {
+        #[cfg(feature = "metrics")]
+        let roomid = processor.roomid;
+        let stats = processor.stats().clone();
+        let (close, _) = tokio::sync::watch::channel(None);
+        let hb_close = close.clone();
         let (mut tx, rx) = ws_stream.split();
-        // hb task
+        let (frames_tx, frames) = tokio::sync::mpsc::channel(read_ahead);
+        let reader = Self::reader(
+            rx,
+            frames_tx,
+            processor.stats().clone(),
+            processor.roomid,
+            cancel.clone(),
+        );
+        let (outbound_tx, outbound_rx) = futures::channel::mpsc::unbounded::<RawPacket>();
+        // hb task，同时发送用户的数据包
         let hb = async move {
-            use tokio::time::*;
-            // 30s 发送一次
-            let mut interval = interval(Duration::from_secs(30));
-            loop {
-                interval.tick().await;
-                tx.send(ws2::Message::Binary(RawPacket::heartbeat().ser()))
+            let heartbeats = futures_util::stream::unfold(
+                tokio::time::interval(heartbeat.interval()),
+                move |mut interval| {
+                    let packet = heartbeat.packet();
+                    async move {
+                        interval.tick().await;
+                        Some((packet, interval))
+                    }
+                },
+            );
+            let mut outbound = futures_util::stream::select(Box::pin(heartbeats), outbound_rx);
+            while let Some(packet) = outbound.next().await {
+                let is_heartbeat = packet.head().opcode == Operation::Heartbeat as u32;
+                let bin = packet.with_sequence(stats.next_sequence()).ser();
+                let len = bin.len();
+                tx.send(ws2::Message::Binary(bin.into()))
                     .await
-                    .expect("hb send error");
+                    .map_err(|e| CloseReason::SendFailed(e.to_string()))?;
+                stats.record_out(len);
+                if is_heartbeat {
+                    stats.record_heartbeat();
+                    #[cfg(feature = "metrics")]
+                    metrics::counter!("bilive_danmaku_heartbeats_sent_total", "roomid" => roomid.to_string())
+                        .increment(1);
+                }
+            }
+            Ok::<(), CloseReason>(())
+        };
+        let hb_cancel = cancel.clone();
+        let hb = async move {
+            let result = std::panic::AssertUnwindSafe(hb_cancel.run_until_cancelled(hb))
+                .catch_unwind()
+                .await;
+            let reason = match result {
+                Ok(None | Some(Ok(()))) => None,
+                Ok(Some(Err(reason))) => Some(reason),
+                Err(panic) => Some(CloseReason::Panicked(panic_message(panic))),
+            };
+            if let Some(reason) = reason {
+                tracing::error!(reason = %reason, "heartbeat task failed");
+                set_close_reason(&hb_close, reason);
+                // 结束事件流
+                hb_cancel.cancel();
+            }
+            tracing::debug!("heartbeat task stopped");
+        };
+        let mut tasks = tokio::task::JoinSet::new();
+        tasks.spawn(hb.instrument(span.clone()));
+        tasks.spawn(reader.instrument(span.clone()));
+        TokioConnection {
+            frames,
+            tasks,
+            outbound_tx,
+            processor,
+            span,
+            cancelled: Box::pin(cancel.clone().cancelled_owned()),
+            cancel,
+            stall: None,
+            close,
+        }
+    }
+
+    /// 读取websocket帧并转发给事件流，读到心跳回复时立即记录往返时间
+    ///
+    /// 事件流跟不上时，最多预读`read_ahead`帧后停止读取
+    async fn reader(
+        mut rx: WsRx,
+        frames_tx: tokio::sync::mpsc::Sender<Frame>,
+        stats: StatsHandle,
+        _roomid: u64,
+        cancel: CancellationToken,
+    ) {
+        let read = async move {
+            while let Some(frame) = rx.next().await {
+                if let Ok(ws2::Message::Binary(bin)) = &frame {
+                    let _rtt = stats.observe_frame(bin);
+                    #[cfg(feature = "metrics")]
+                    if let Some(rtt) = _rtt {
+                        metrics::histogram!("bilive_danmaku_heartbeat_rtt_seconds", "roomid" => _roomid.to_string())
+                            .record(rtt.as_secs_f64());
+                    }
+                }
+                if frames_tx.send(frame).await.is_err() {
+                    break;
+                }
             }
         };
-        Ok(TokioConnection {
-            ws_rx: rx,
-            hb_handle: tokio::spawn(hb),
-            buffer: VecDeque::with_capacity(256),
-        })
+        cancel.run_until_cancelled(read).await;
+        tracing::debug!("reader task stopped");
+    }
+
+    /// 连接已经结束时的原因
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        self.close.borrow().clone()
+    }
+
+    /// 等待连接结束，返回结束的原因
+    ///
+    /// 读取失败和服务器关闭在消费事件流时才能发现，发送失败和后台任务panic会结束事件流
+    pub fn closed(&self) -> impl std::future::Future<Output = CloseReason> + Send + 'static {
+        let mut close = self.close.subscribe();
+        async move {
+            match close.wait_for(Option::is_some).await {
+                Ok(reason) => reason.clone().unwrap_or(CloseReason::Cancelled),
+                Err(_) => CloseReason::Cancelled,
+            }
+        }
+    }
+
+    /// 这个连接的取消令牌，取消后心跳任务停止，事件流结束
+    pub fn cancel_token(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    /// 发送一个数据包，与心跳包在同一个任务中依次发出
+    pub fn send_packet(&self, packet: RawPacket) -> Result<(), EventStreamError> {
+        self.outbound_tx
+            .unbounded_send(packet)
+            .map_err(|_| EventStreamError::ConnectionClosed)
+    }
+
+    /// 在还没有取走的事件之后插入事件，比如弹幕历史
+    pub fn inject(&mut self, events: impl IntoIterator<Item = Event>) {
+        self.processor.inject(events);
     }
 
-    pub fn abort(self) {
-        self.hb_handle.abort();
+    /// 最近一次收到的人气值，还没有收到时为`None`，读取任务收到心跳回复时就会更新，不需要消费事件流
+    pub fn get_popularity(&self) -> Option<u32> {
+        self.processor.popularity()
     }
+
+    /// 订阅人气值的变化，可以在消费事件流之外的任务中获取最新的人气值，事件流没有被消费时同样会更新
+    pub fn subscribe_popularity(&self) -> tokio::sync::watch::Receiver<Option<u32>> {
+        self.processor.subscribe_popularity()
+    }
+
+    /// 最近一次心跳的往返时间，还没有收到心跳回复时为`None`，平均值见[`stats`](Self::stats)
+    pub fn rtt(&self) -> Option<std::time::Duration> {
+        self.processor.stats().snapshot().rtt
+    }
+
+    /// 到目前为止的收发统计
+    pub fn stats(&self) -> ConnectionStats {
+        self.processor.stats().snapshot()
+    }
+
+    /// 按批次产生事件，攒够`max_events`个事件或者距离批次中第一个事件超过`max_delay`时产生一批
+    ///
+    /// 适合批量写入数据库或者批量推送，事件稀疏时不会产生空的批次
+    ///```no_run,ignore
+    ///let mut batches = connection.batched(100, Duration::from_millis(500));
+    ///while let Some(Ok(events)) = batches.next().await {
+    ///    db.insert_many(&events).await?;
+    ///}
+    ///```
+    pub fn batched(
+        self,
+        max_events: usize,
+        max_delay: std::time::Duration,
+    ) -> super::Batched<Self, EventStreamError> {
+        super::Batched::new(self, max_events, max_delay)
+    }
+
+    /// 取消连接并立即abort后台任务，直接drop时同样会abort后台任务
+    pub fn abort(mut self) {
+        self.cancel.cancel();
+        self.tasks.abort_all();
+    }
+}
+
+fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => panic
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .unwrap_or_else(|| "unknown panic".to_owned()),
+    }
+}
+
+pub(crate) async fn sleep(duration: std::time::Duration) {
+    tokio::time::sleep(duration).await
 }
 // 动物化的后现代
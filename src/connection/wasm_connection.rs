@@ -4,14 +4,13 @@ use futures::{stream::SplitStream, SinkExt, Stream, StreamExt};
 use gloo_net::{self, websocket::futures::WebSocket};
 use gloo_timers::future::IntervalStream;
 use js_sys::Promise;
-use std::collections::VecDeque;
 
 // use tungstenite;
 use crate::{
     connection::WsConnectError,
-    event::Event,
     packet::{Auth, Operation, RawPacket},
 };
+use tracing::Instrument;
 use wasm_bindgen_futures::future_to_promise;
 // type WsStream = tokio_ws2::WebSocketStream<tokio_ws2::MaybeTlsStream<tokio::net::TcpStream>>;
 type WsRx = SplitStream<WebSocket>;
@@ -19,7 +18,9 @@ type WsRx = SplitStream<WebSocket>;
 pub struct WasmConnection {
     ws_rx: WsRx,
     pub hb_handle: Promise,
-    buffer: VecDeque<Result<Event, EventStreamError>>, // rx_handle: tokio::task::JoinHandle<()>,
+    outbound_tx: futures::channel::mpsc::UnboundedSender<RawPacket>,
+    processor: Processor,
+    span: tracing::Span,
 }
 
 impl Stream for WasmConnection {
@@ -32,26 +33,22 @@ impl Stream for WasmConnection {
         use gloo_net::websocket::Message::*;
         use std::task::Poll::*;
         use EventStreamError::*;
-
-        if let Some(event) = self.buffer.pop_front() {
+        let span = self.span.clone();
+        let _enter = span.enter();
+        if let Some(event) = self.processor.pop() {
             return Ready(Some(event));
         }
         // 读取新序列
         match self.ws_rx.poll_next_unpin(cx) {
             Ready(Some(Ok(Bytes(bin)))) => {
-                let packet = RawPacket::from_buffer(&bin);
-                for data in packet.get_datas() {
-                    if let Ok(Some(event)) = data.into_event() {
-                        self.buffer.push_back(Ok(event))
-                    }
-                }
+                self.processor.feed(bin.into());
                 self.poll_next(cx)
             }
             // Ready(Some(Ok(Close(_)))) => return Ready(Some(Err(ConnectionClosed))),
             // 这不太可能发生，可能要标记一下
             Ready(Some(Ok(_))) => self.poll_next(cx),
             // 错误
-            Ready(Some(Err(e))) => Ready(Some(Err(WsError(e.to_string())))),
+            Ready(Some(Err(e))) => Ready(Some(Err(WsError(std::sync::Arc::new(e))))),
             // 接受到None
             Ready(None) => Ready(None),
             Pending => Pending,
@@ -71,41 +68,101 @@ impl From<gloo_net::websocket::WebSocketError> for WsConnectError {
     }
 }
 impl WasmConnection {
-    pub async fn connect(url: String, auth: Auth) -> Result<Self, WsConnectError> {
+    pub async fn connect(
+        url: String,
+        auth: Auth,
+        config: &ConnectConfig,
+    ) -> Result<Self, WsConnectError> {
         use gloo_net::websocket::Message::*;
+        let roomid = auth.roomid();
+        let span = tracing::info_span!("connection", roomid, url = %url);
         let ws_stream = WebSocket::open(url.as_str())?;
 
         let (mut tx, mut rx) = ws_stream.split();
         let authpack_bin = RawPacket::build(Operation::Auth, auth.ser()).ser();
-        tx.send(Bytes(authpack_bin)).await?;
-        let _auth_reply = match rx.next().await {
-            Some(Ok(Bytes(auth_reply_bin))) => RawPacket::from_buffer(&auth_reply_bin),
+        // 连接打开后才能发出数据，所以握手的时间算在第一次发送中
+        timeout(config.handshake_timeout, tx.send(Bytes(authpack_bin)))
+            .await
+            .ok_or(WsConnectError::HandshakeTimeout)??;
+        match timeout(config.auth_timeout, rx.next())
+            .await
+            .ok_or(WsConnectError::AuthTimeout)?
+        {
+            Some(Ok(Bytes(auth_reply_bin))) => check_auth_reply(&auth_reply_bin)?,
             _other => {
                 return Err(WsConnectError::UnexpecedEnd);
             }
         };
-        // hb task
+        let processor = Processor::new(roomid, config);
+        let stats = processor.stats().clone();
+        let heartbeat = config.heartbeat.clone();
+        let (outbound_tx, outbound_rx) = futures::channel::mpsc::unbounded::<RawPacket>();
+        // hb task，同时发送用户的数据包
         let hb = async move {
-            // use tokio::time::*;
-            // 30s 发送一次
-            let mut interval = IntervalStream::new(30000);
-            loop {
-                interval.next().await;
-                tx.send(Bytes(RawPacket::heartbeat().ser()))
-                    .await
-                    .expect("fail to send heart beat ");
+            let heartbeats = IntervalStream::new(heartbeat.interval().as_millis() as u32)
+                .map(move |_| heartbeat.packet());
+            let mut outbound = futures::stream::select(heartbeats, outbound_rx);
+            while let Some(packet) = outbound.next().await {
+                let is_heartbeat = packet.head().opcode == Operation::Heartbeat as u32;
+                let bin = packet.with_sequence(stats.next_sequence()).ser();
+                let len = bin.len();
+                if let Err(e) = tx.send(Bytes(bin)).await {
+                    tracing::error!(error = %e, "heartbeat task failed");
+                    return Err(wasm_bindgen::JsValue::from_str(&e.to_string()));
+                }
+                stats.record_out(len);
+                if is_heartbeat {
+                    stats.record_heartbeat();
+                    #[cfg(feature = "metrics")]
+                    metrics::counter!("bilive_danmaku_heartbeats_sent_total", "roomid" => roomid.to_string())
+                        .increment(1);
+                }
             }
+            Ok(wasm_bindgen::JsValue::UNDEFINED)
         };
         // let hb = spawn_local();
         Ok(WasmConnection {
             ws_rx: rx,
-            hb_handle: future_to_promise(hb),
-            buffer: VecDeque::with_capacity(256),
+            hb_handle: future_to_promise(hb.instrument(span.clone())),
+            outbound_tx,
+            processor,
+            span,
         })
     }
 
+    /// 发送一个数据包，与心跳包在同一个任务中依次发出
+    pub fn send_packet(&self, packet: RawPacket) -> Result<(), EventStreamError> {
+        self.outbound_tx
+            .unbounded_send(packet)
+            .map_err(|_| EventStreamError::ConnectionClosed)
+    }
+
+    /// 在还没有取走的事件之后插入事件，比如弹幕历史
+    pub fn inject(&mut self, events: impl IntoIterator<Item = Event>) {
+        self.processor.inject(events);
+    }
+
+    /// 最近一次收到的人气值，还没有收到时为`None`，只在消费事件流、处理到心跳回复时更新
+    pub fn get_popularity(&self) -> Option<u32> {
+        self.processor.popularity()
+    }
+
+    /// 最近一次心跳的往返时间，还没有收到心跳回复时为`None`，平均值见[`stats`](Self::stats)
+    pub fn rtt(&self) -> Option<std::time::Duration> {
+        self.processor.stats().snapshot().rtt
+    }
+
+    /// 到目前为止的收发统计
+    pub fn stats(&self) -> ConnectionStats {
+        self.processor.stats().snapshot()
+    }
+
     pub fn abort(self) {
         // literally do nothing
     }
 }
+
+pub(crate) async fn sleep(duration: std::time::Duration) {
+    gloo_timers::future::sleep(duration).await
+}
 // 动物化的后现代
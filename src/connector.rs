@@ -1,6 +1,6 @@
 use serde::Deserialize;
 
-use crate::{connection::*, packet::*};
+use crate::{connection::*, http::HttpClient, packet::*};
 
 #[derive(Debug, Clone)]
 pub struct Connector {
@@ -9,13 +9,43 @@ pub struct Connector {
     pub token: String,
     pub host_index: usize,
     pub host_list: Vec<Host>,
+    pub config: ConnectConfig,
+    /// 使用手动指定的服务器，重新获取token时不会覆盖`host_list`
+    pub pinned_hosts: bool,
+    /// 初始化时的直播状态，不是通过接口初始化时为`None`
+    pub live_status: Option<crate::model::LiveStatus>,
+    /// 获取弹幕历史等接口使用的客户端，通过接口初始化时为初始化使用的客户端，否则为[`HttpClient::shared`]
+    pub client: HttpClient,
 }
 
+/// 连接后获取弹幕历史的超时时间，超时后只记录日志，不影响连接
+const BACKFILL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
 #[derive(Debug)]
 pub enum InitError {
     ParseError(String),
     HttpError(reqwest::Error),
+    /// http状态码不是2xx
+    HttpStatus(reqwest::StatusCode),
+    /// 接口返回的`code`不为0
+    ApiError {
+        code: i64,
+        message: String,
+    },
     DeserError(serde_json::Error),
+    /// 用户没有开通直播间
+    NoLiveRoom {
+        uid: u64,
+    },
+    /// 超过[`HttpClient::with_timeout`]设置的时间没有收到响应
+    Timeout,
+    /// 凭据中没有刷新cookie需要的`refresh_token`，只能重新登录
+    MissingRefreshToken,
+    /// 凭据失效后刷新失败，`source`为刷新时的错误，`expired`为凭据失效时原来的错误
+    RefreshFailed {
+        source: Box<InitError>,
+        expired: Box<InitError>,
+    },
 }
 
 impl From<serde_json::Error> for InitError {
@@ -35,14 +65,40 @@ impl std::fmt::Display for InitError {
         match self {
             InitError::ParseError(msg) => write!(f, "ParseError: {}", msg),
             InitError::HttpError(err) => write!(f, "HttpError: {}", err),
+            InitError::HttpStatus(status) => write!(f, "HttpStatus: {}", status),
+            InitError::ApiError { code, message } => {
+                write!(f, "ApiError: code {}, message {}", code, message)
+            }
             InitError::DeserError(err) => write!(f, "DeserError: {}", err),
+            InitError::NoLiveRoom { uid } => write!(f, "NoLiveRoom: uid {}", uid),
+            InitError::Timeout => write!(f, "Timeout"),
+            InitError::MissingRefreshToken => write!(f, "MissingRefreshToken"),
+            InitError::RefreshFailed { source, expired } => {
+                write!(f, "RefreshFailed: {} (expired: {})", source, expired)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InitError::HttpError(err) => Some(err),
+            InitError::DeserError(err) => Some(err),
+            InitError::RefreshFailed { source, .. } => Some(source.as_ref()),
+            _ => None,
         }
     }
 }
 
 impl Connector {
-    pub async fn init(mut roomid: u64) -> Result<Self, InitError> {
-        let client = reqwest::Client::new();
+    /// 使用[`HttpClient::shared`]初始化
+    pub async fn init(roomid: u64) -> Result<Self, InitError> {
+        Self::init_with(roomid, HttpClient::shared()).await
+    }
+
+    #[tracing::instrument(level = "debug", skip(client))]
+    pub async fn init_with(mut roomid: u64, client: &HttpClient) -> Result<Self, InitError> {
         let room_info_url = format!(
             "https://api.live.bilibili.com/xlive/web-room/v2/index/getRoomPlayInfo?room_id={}",
             roomid
@@ -50,37 +106,107 @@ impl Connector {
         let RoomPlayInfoData {
             room_id: real_room_id,
             uid,
-        } = client
-            .get(room_info_url)
-            .send()
-            .await?
-            .json::<RoomPlayInfo>()
-            .await?
-            .data
-            .ok_or(InitError::ParseError(format!("Fail to get room info")))?;
+            live_status,
+        } = client.get_data(room_info_url).await?;
         roomid = real_room_id;
-        let url = format!(
-            "https://api.live.bilibili.com/xlive/web-room/v1/index/getDanmuInfo?id={}&type=0",
-            roomid
-        );
-        let DanmuInfoData { token, host_list } = client
-            .get(url)
-            .send()
-            .await?
-            .json::<DanmuInfo>()
-            .await?
-            .data
-            .ok_or(InitError::ParseError(format!("Fail to get danmu info")))?;
+        let DanmuInfoData { token, host_list } = danmu_info(roomid, client).await?;
         let connector = Connector {
             uid,
             host_index: 0,
             roomid,
             token,
             host_list,
+            config: ConnectConfig::default(),
+            pinned_hosts: false,
+            live_status: Some(live_status),
+            client: client.clone(),
         };
         Ok(connector)
     }
 
+    /// 不请求接口，直接使用已有的token，需要再通过[`with_hosts`](Self::with_hosts)或[`with_url`](Self::with_url)指定服务器
+    pub fn new(roomid: u64, uid: u64, token: impl Into<String>) -> Self {
+        Connector {
+            roomid,
+            uid,
+            token: token.into(),
+            host_index: 0,
+            host_list: Vec::new(),
+            config: ConnectConfig::default(),
+            pinned_hosts: false,
+            live_status: None,
+            client: HttpClient::shared().clone(),
+        }
+    }
+
+    pub fn with_client(mut self, client: HttpClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// 使用指定的服务器代替`getDanmuInfo`返回的服务器列表，用于自建的中转、固定服务器或者调试
+    pub fn with_hosts(mut self, hosts: Vec<Host>) -> Self {
+        self.host_list = hosts;
+        self.host_index = 0;
+        self.pinned_hosts = true;
+        self
+    }
+
+    /// 使用完整的websocket地址，见[`Host::from_url`]
+    pub fn with_url(self, url: impl Into<String>) -> Self {
+        self.with_hosts(vec![Host::from_url(url)])
+    }
+
+    /// 重新获取token和服务器列表，使用`client`
+    pub async fn refresh_token(&mut self) -> Result<(), InitError> {
+        let client = self.client.clone();
+        self.refresh_token_with(&client).await
+    }
+
+    /// 重新获取token和服务器列表，从第一个服务器开始连接，`pinned_hosts`时只更新token
+    #[tracing::instrument(level = "debug", skip(self, client), fields(roomid = self.roomid))]
+    pub async fn refresh_token_with(&mut self, client: &HttpClient) -> Result<(), InitError> {
+        let DanmuInfoData { token, host_list } = danmu_info(self.roomid, client).await?;
+        self.token = token;
+        if !self.pinned_hosts {
+            self.host_list = host_list;
+        }
+        self.host_index = 0;
+        Ok(())
+    }
+
+    /// 通过主播的uid初始化，使用[`HttpClient::shared`]
+    pub async fn init_by_uid(uid: u64) -> Result<Self, InitError> {
+        Self::init_by_uid_with(uid, HttpClient::shared()).await
+    }
+
+    pub async fn init_by_uid_with(uid: u64, client: &HttpClient) -> Result<Self, InitError> {
+        let roomid = client.room_id_by_uid(uid).await?;
+        Self::init_with(roomid, client).await
+    }
+
+    /// 并发初始化多个直播间，使用[`HttpClient::shared`]，最多同时初始化8个
+    pub async fn init_many(roomids: &[u64]) -> Vec<(u64, Result<Self, InitError>)> {
+        Self::init_many_with(roomids, HttpClient::shared(), 8).await
+    }
+
+    /// 并发初始化多个直播间，结果的顺序与`roomids`相同
+    ///
+    /// # 说明
+    /// - `concurrency` 同时进行的初始化数量，最小为1，请求频率仍受`client`的限流器限制
+    pub async fn init_many_with(
+        roomids: &[u64],
+        client: &HttpClient,
+        concurrency: usize,
+    ) -> Vec<(u64, Result<Self, InitError>)> {
+        use futures_util::StreamExt;
+        futures_util::stream::iter(roomids.iter().copied())
+            .map(|roomid| async move { (roomid, Self::init_with(roomid, client).await) })
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
     pub fn use_host(&mut self, index: usize) -> Result<&'_ str, usize> {
         if self.host_list.len() > index {
             self.host_index = index;
@@ -90,59 +216,348 @@ impl Connector {
         }
     }
 
+    /// 并发测量每个服务器的TCP握手耗时，按耗时从小到大排序`host_list`，并使用最快的服务器
+    ///
+    /// 超时或连接失败的服务器排在最后，耗时为`None`，设置了`config.resolver`时使用它解析域名
+    #[cfg(feature = "rt_tokio")]
+    pub async fn rank_hosts(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> Vec<(Host, Option<std::time::Duration>)> {
+        let resolver = self.config.resolver.clone().unwrap_or_default();
+        let resolver = &resolver;
+        let probes = self.host_list.iter().map(|host| async move {
+            let start = std::time::Instant::now();
+            let tcp = resolver.connect(&host.host, host.wss_port);
+            let latency = match tokio::time::timeout(timeout, tcp).await {
+                Ok(Ok(_)) => Some(start.elapsed()),
+                Ok(Err(e)) => {
+                    tracing::debug!(host = host.host, error = %e, "probe failed");
+                    None
+                }
+                Err(_) => None,
+            };
+            (host.clone(), latency)
+        });
+        let mut ranked = futures_util::future::join_all(probes).await;
+        ranked.sort_by_key(|(_, latency)| latency.unwrap_or(std::time::Duration::MAX));
+        self.host_list = ranked.iter().map(|(host, _)| host.clone()).collect();
+        self.host_index = 0;
+        ranked
+    }
+
+    /// 自动切换服务器的事件流
+    ///
+    /// 连接出错、关闭、停滞（见[`ConnectConfig::stall_timeout`](crate::connection::ConnectConfig)）
+    /// 或心跳延迟持续过高（见[`ConnectConfig::max_rtt`](crate::connection::ConnectConfig)）时，
+    /// 切换到`host_list`中的下一个服务器重新连接，并产生一个[`HostSwitchEvent`](crate::event::HostSwitchEvent)。
+    /// 连接失败时产生错误，按[`ConnectConfig::retry`](crate::connection::ConnectConfig)等待后尝试下一个服务器，
+    /// 遇到不可重试的错误或用完尝试次数时事件流结束，否则只会在取消时结束。
+    /// 鉴权被拒绝，或者所有服务器都连接失败时，用`client`重新获取token和服务器列表，`pinned_hosts`时不重新获取
+    pub fn connect_switching(
+        self,
+    ) -> impl futures_util::Stream<Item = Result<crate::event::Event, ConnectError>> {
+        let init = (self, None::<Connection>, 0u32, RttWatch::default(), false);
+        futures_util::stream::unfold(Some(init), |state| async move {
+            use futures_util::StreamExt;
+            let (mut connector, mut connection, mut failures, mut rtt, mut connected) = state?;
+            loop {
+                let Some(stream) = connection.as_mut() else {
+                    match connector.connect().await {
+                        Ok(stream) => {
+                            #[cfg(feature = "metrics")]
+                            if connected {
+                                metrics::counter!("bilive_danmaku_reconnects_total", "roomid" => connector.roomid.to_string())
+                                    .increment(1);
+                            }
+                            connection = Some(stream);
+                            connected = true;
+                            failures = 0;
+                            rtt = RttWatch::default();
+                            // 重连时不再重复产生弹幕历史
+                            connector.config.backfill_history = false;
+                            continue;
+                        }
+                        Err(e) => {
+                            failures += 1;
+                            if !connector.config.retry.should_retry(&e, failures) {
+                                return Some((Err(e), None));
+                            }
+                            connector.prepare_retry(&e, failures).await;
+                            return Some((
+                                Err(e),
+                                Some((connector, None, failures, rtt, connected)),
+                            ));
+                        }
+                    }
+                };
+                let max_rtt = connector.config.max_rtt;
+                let reason = if max_rtt.is_some_and(|max| rtt.degraded(&stream.stats(), max)) {
+                    "心跳延迟过高".to_owned()
+                } else {
+                    match stream.next().await {
+                        Some(Ok(event)) => {
+                            return Some((
+                                Ok(event),
+                                Some((connector, connection, failures, rtt, connected)),
+                            ))
+                        }
+                        Some(Err(e)) => e.to_string(),
+                        None if connector.is_cancelled() => return None,
+                        // 没有Close帧的断开、心跳任务失败等，同样切换服务器
+                        None => close_reason(stream),
+                    }
+                };
+                if let Some(stream) = connection.take() {
+                    stream.abort();
+                }
+                let event = connector.switch_host(reason);
+                return Some((Ok(event), Some((connector, None, failures, rtt, connected))));
+            }
+        })
+    }
+
+    /// 第`attempt`次连接失败后，切换服务器或重新获取token，并按重试策略等待
+    ///
+    /// `pinned_hosts`时服务器和token都是手动指定的，只切换服务器
+    async fn prepare_retry(&mut self, error: &ConnectError, attempt: u32) {
+        let hosts = self.host_list.len().max(1) as u32;
+        if self.pinned_hosts {
+            self.next_host();
+        } else if error.is_auth_rejected() || attempt.is_multiple_of(hosts) {
+            if let Err(e) = self.refresh_token().await {
+                tracing::warn!(error = %e, "重新获取token失败");
+                self.next_host();
+            }
+        } else {
+            self.next_host();
+        }
+        crate::connection::sleep(self.config.retry.delay_for(attempt)).await;
+    }
+
+    /// 切换到下一个服务器，返回对应的[`HostSwitchEvent`](crate::event::HostSwitchEvent)
+    fn switch_host(&mut self, reason: String) -> crate::event::Event {
+        let from = self.current_host();
+        self.next_host();
+        let to = self.current_host();
+        tracing::warn!(reason, from, to, "切换服务器");
+        #[cfg(feature = "metrics")]
+        metrics::counter!("bilive_danmaku_host_switches_total", "roomid" => self.roomid.to_string())
+            .increment(1);
+        let event = crate::event::HostSwitchEvent { from, to, reason };
+        crate::event::EventData::from(event).into()
+    }
+
+    /// 是否已经通过`config.cancel`取消
+    fn is_cancelled(&self) -> bool {
+        #[cfg(feature = "rt_tokio")]
+        {
+            self.config
+                .cancel
+                .as_ref()
+                .is_some_and(|cancel| cancel.is_cancelled())
+        }
+        #[cfg(not(feature = "rt_tokio"))]
+        {
+            false
+        }
+    }
+
+    fn current_host(&self) -> String {
+        self.host_list
+            .get(self.host_index)
+            .map(|host| host.host.clone())
+            .unwrap_or_default()
+    }
+
+    fn next_host(&mut self) {
+        if !self.host_list.is_empty() {
+            self.host_index = (self.host_index + 1) % self.host_list.len();
+        }
+    }
+
+    /// 默认的鉴权包，可以修改后交给[`connect_with_auth`](Self::connect_with_auth)
+    ///```no_run,ignore
+    ///// 强制使用不压缩的json，便于调试
+    ///let stream = connector.connect_with_auth(connector.auth().with_protover(1)).await?;
+    ///```
+    pub fn auth(&self) -> Auth {
+        Auth::new(self.uid, self.roomid, Some(self.token.clone()))
+    }
+
     pub async fn connect(&self) -> Result<Connection, ConnectError> {
+        self.connect_with_auth(self.auth()).await
+    }
+
+    /// 连接，鉴权被拒绝时重新获取token和服务器列表后再试一次
+    pub async fn connect_refreshing(&mut self) -> Result<Connection, ConnectError> {
+        match self.connect().await {
+            Err(e) if e.is_auth_rejected() => {
+                tracing::info!(roomid = self.roomid, "token失效，重新获取");
+                self.refresh_token()
+                    .await
+                    .map_err(ConnectError::TokenRefresh)?;
+                self.connect().await
+            }
+            result => result,
+        }
+    }
+
+    /// 按[`ConnectConfig::retry`](crate::connection::ConnectConfig)重试连接，失败时切换服务器，
+    /// 鉴权被拒绝或所有服务器都失败时重新获取token和服务器列表，`pinned_hosts`时只切换服务器
+    pub async fn connect_retrying(&mut self) -> Result<Connection, ConnectError> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match self.connect().await {
+                Err(e) if self.config.retry.should_retry(&e, attempt) => {
+                    tracing::warn!(roomid = self.roomid, error = %e, attempt, "连接失败，稍后重试");
+                    self.prepare_retry(&e, attempt).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    #[tracing::instrument(name = "room", skip(self, auth), fields(roomid = self.roomid))]
+    pub async fn connect_with_auth(&self, auth: Auth) -> Result<Connection, ConnectError> {
         if self.host_list.is_empty() {
             return Err(ConnectError::HostListIsEmpty);
         }
         let url = self.host_list[self.host_index].wss();
-        let roomid = self.roomid;
-        let backup = self.clone();
-        let auth = Auth::new(self.uid, roomid, Some(backup.token.clone()));
-        let stream = Connection::connect(url, auth).await.map_err(|e| {
-            log::error!("handshake error: {:?}", e);
-            ConnectError::HandshakeError
-        })?;
+        let mut stream = Connection::connect(url, auth, &self.config)
+            .await
+            .map_err(|e| {
+                tracing::error!(error = ?e, "handshake error");
+                ConnectError::HandshakeError(e)
+            })?;
+        if self.config.backfill_history {
+            let history = self.client.danmaku_history(self.roomid);
+            match crate::connection::timeout(Some(BACKFILL_TIMEOUT), history).await {
+                Some(Ok(history)) => stream.inject(history),
+                Some(Err(e)) => tracing::warn!(error = %e, "获取弹幕历史失败"),
+                None => tracing::warn!("获取弹幕历史超时"),
+            }
+        }
         Ok(stream)
     }
 }
 
-#[derive(Debug, Deserialize)]
-struct RoomPlayInfoData {
-    room_id: u64,
-    uid: u64,
+/// 连续多少次心跳延迟超过`max_rtt`后切换服务器
+const SLOW_RTT_LIMIT: u32 = 3;
+
+/// 记录连续过慢的心跳次数
+#[derive(Debug, Default)]
+struct RttWatch {
+    samples: u64,
+    slow: u32,
+}
+
+impl RttWatch {
+    /// 有新的心跳延迟时更新计数，连续`SLOW_RTT_LIMIT`次超过`max_rtt`时返回true
+    fn degraded(
+        &mut self,
+        stats: &crate::connection::ConnectionStats,
+        max_rtt: std::time::Duration,
+    ) -> bool {
+        let Some(rtt) = stats.rtt else {
+            return false;
+        };
+        if stats.rtt_samples == self.samples {
+            return false;
+        }
+        self.samples = stats.rtt_samples;
+        if rtt > max_rtt {
+            self.slow += 1;
+        } else {
+            self.slow = 0;
+        }
+        self.slow >= SLOW_RTT_LIMIT
+    }
+}
+
+/// 事件流结束时的断开原因
+fn close_reason(_connection: &Connection) -> String {
+    #[cfg(feature = "rt_tokio")]
+    if let Some(reason) = _connection.close_reason() {
+        return reason.to_string();
+    }
+    "连接关闭".to_owned()
 }
 
 ///
 /// api url:
 /// https://api.live.bilibili.com/xlive/web-room/v2/index/getRoomPlayInfo?room_id=510
 #[derive(Debug, Deserialize)]
-struct RoomPlayInfo {
-    data: Option<RoomPlayInfoData>,
+struct RoomPlayInfoData {
+    room_id: u64,
+    uid: u64,
+    #[serde(default)]
+    live_status: crate::model::LiveStatus,
 }
 
+///
+/// api url:
+/// https://api.live.bilibili.com/xlive/web-room/v1/index/getDanmuInfo?id=510&type=0
 #[derive(Debug, Deserialize)]
-struct DanmuInfo {
-    // code: i32,
-    // message: String,
-    // ttl: i32,
-    data: Option<DanmuInfoData>,
-}
-#[derive(Debug, Deserialize)]
-
 struct DanmuInfoData {
     // max_delay: i32,
     token: String,
     host_list: Vec<Host>,
 }
 
+async fn danmu_info(roomid: u64, client: &HttpClient) -> Result<DanmuInfoData, InitError> {
+    let url = format!(
+        "https://api.live.bilibili.com/xlive/web-room/v1/index/getDanmuInfo?id={}&type=0",
+        roomid
+    );
+    client.get_data(url).await
+}
+
+/// 弹幕服务器
+///
+/// # 说明
+/// - `url` 完整的连接地址，设置时忽略`host`和`wss_port`，`getDanmuInfo`返回的服务器没有这个字段
 #[derive(Debug, Deserialize, Clone)]
 pub struct Host {
     pub host: String,
     pub wss_port: u16,
+    #[serde(skip)]
+    pub url: Option<String>,
 }
 
 impl Host {
+    /// 使用`wss://{host}:{wss_port}/sub`连接
+    pub fn new(host: impl Into<String>, wss_port: u16) -> Self {
+        Self {
+            host: host.into(),
+            wss_port,
+            url: None,
+        }
+    }
+
+    /// 使用完整的地址连接，可以是`ws://`或`wss://`，`host`和`wss_port`从地址中解析，只用于[`Connector::rank_hosts`]
+    pub fn from_url(url: impl Into<String>) -> Self {
+        let url = url.into();
+        let parsed = reqwest::Url::parse(&url).ok();
+        Self {
+            host: parsed
+                .as_ref()
+                .and_then(|url| url.host_str())
+                .unwrap_or_default()
+                .to_owned(),
+            wss_port: parsed
+                .as_ref()
+                .and_then(|url| url.port_or_known_default())
+                .unwrap_or_default(),
+            url: Some(url),
+        }
+    }
+
     fn wss(&self) -> String {
+        if let Some(url) = &self.url {
+            return url.clone();
+        }
         let host = &self.host;
         let port = self.wss_port;
         format!("wss://{host}:{port}/sub")
@@ -152,6 +567,37 @@ impl Host {
 #[derive(Debug)]
 pub enum ConnectError {
     HostListIsEmpty,
-    HandshakeError,
-    WsError(String),
+    HandshakeError(WsConnectError),
+    /// 鉴权被拒绝后重新获取token失败
+    TokenRefresh(InitError),
+}
+
+impl ConnectError {
+    /// 是否因为token失效被拒绝
+    pub fn is_auth_rejected(&self) -> bool {
+        matches!(
+            self,
+            ConnectError::HandshakeError(WsConnectError::AuthRejected { .. })
+        )
+    }
+}
+
+impl std::fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectError::HostListIsEmpty => write!(f, "服务器列表为空"),
+            ConnectError::HandshakeError(e) => write!(f, "握手失败：{}", e),
+            ConnectError::TokenRefresh(e) => write!(f, "重新获取token失败：{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConnectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConnectError::HostListIsEmpty => None,
+            ConnectError::HandshakeError(e) => Some(e),
+            ConnectError::TokenRefresh(e) => Some(e),
+        }
+    }
 }
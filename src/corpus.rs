@@ -0,0 +1,96 @@
+//! 检查抓取到的原始消息
+//!
+//! [`check_corpus`]把一个目录中抓取到的原始消息依次解析为事件，报告解析失败的消息，可以在CI中发现解析的回归，
+//! 只需要`protocol`
+//!```no_run,ignore
+//!let report = bilive_danmaku::corpus::check_corpus("corpus")?;
+//!assert!(report.is_ok(), "{:#?}", report.failures);
+//!```
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::{
+    cmd::CmdDeserError,
+    packet::{Data, EventParseError},
+};
+
+/// 解析样本的结果
+///
+/// # 说明
+/// - `total` 消息总数
+/// - `events` 得到事件的消息数
+/// - `skipped` 被有意忽略或者还不认识的消息数，不算作失败
+/// - `failures` 解析失败的消息
+#[derive(Debug, Clone, Default)]
+pub struct CorpusReport {
+    pub total: usize,
+    pub events: usize,
+    pub skipped: usize,
+    pub failures: Vec<CorpusFailure>,
+}
+
+/// 解析失败的消息，`index`为消息在文件中的序号，文件中只有一条消息时为0
+#[derive(Debug, Clone)]
+pub struct CorpusFailure {
+    pub path: PathBuf,
+    pub index: usize,
+    pub error: String,
+}
+
+impl CorpusReport {
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// 递归读取目录中所有的`.json`文件并解析，每个文件可以是一条消息或消息的数组
+pub fn check_corpus(dir: impl AsRef<Path>) -> std::io::Result<CorpusReport> {
+    let mut report = CorpusReport::default();
+    let mut files = vec![];
+    collect_json_files(dir.as_ref(), &mut files)?;
+    files.sort();
+    for path in files {
+        let text = std::fs::read_to_string(&path)?;
+        let samples = match serde_json::from_str::<Value>(&text) {
+            Ok(Value::Array(samples)) => samples,
+            Ok(sample) => vec![sample],
+            Err(e) => {
+                report.total += 1;
+                report.failures.push(CorpusFailure {
+                    path,
+                    index: 0,
+                    error: e.to_string(),
+                });
+                continue;
+            }
+        };
+        for (index, sample) in samples.into_iter().enumerate() {
+            report.total += 1;
+            match Data::Json(sample).into_event(false, &Default::default()) {
+                Ok(Some(_)) => report.events += 1,
+                Ok(None) | Err(EventParseError::CmdDeserError(CmdDeserError::Ignored { .. })) => {
+                    report.skipped += 1
+                }
+                Err(e) => report.failures.push(CorpusFailure {
+                    path: path.clone(),
+                    index,
+                    error: e.to_string(),
+                }),
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn collect_json_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_json_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "json") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+//! 登录凭据
+//!
+//! 发送弹幕等需要登录的接口使用浏览器cookie中的`SESSDATA`和`bili_jct`
+//!```no_run,ignore
+//!use bilive_danmaku::credential::Credential;
+//!let credential = Credential::new(sessdata, bili_jct).with_uid(uid);
+//!```
+//!
+//! cookie会过期，启用`cookie_refresh`后，[`CredentialManager`]在接口返回未登录时用`refresh_token`刷新cookie并重试
+//!```no_run,ignore
+//!use bilive_danmaku::credential::CredentialManager;
+//!let manager = CredentialManager::new(credential).with_on_relogin(|e| eprintln!("需要重新登录：{e}"));
+//!manager.call(|c| async move { client.send_danmaku(&c, roomid, "hi").await }).await?;
+//!```
+#[cfg(feature = "cookie_refresh")]
+mod refresh;
+#[cfg(feature = "cookie_refresh")]
+pub use refresh::*;
+
+use crate::InitError;
+
+/// 接口返回的未登录错误码
+pub const CODE_NOT_LOGGED_IN: i64 = -101;
+
+/// 登录凭据
+///
+/// # 说明
+/// - `sessdata` cookie中的`SESSDATA`
+/// - `bili_jct` cookie中的`bili_jct`，同时作为csrf token
+/// - `uid` cookie中的`DedeUserID`，未知时为0
+/// - `buvid3` 设备标识，可以为空
+/// - `refresh_token` 刷新cookie时使用，扫码登录时得到，也可以从浏览器localStorage的`ac_time_value`中获取
+///
+/// [`Debug`]输出中隐去`sessdata`、`bili_jct`和`refresh_token`，可以放心打印到日志
+#[derive(Clone, Default)]
+pub struct Credential {
+    pub sessdata: String,
+    pub bili_jct: String,
+    pub uid: u64,
+    pub buvid3: String,
+    pub refresh_token: String,
+}
+
+impl Credential {
+    pub fn new(sessdata: impl Into<String>, bili_jct: impl Into<String>) -> Self {
+        Self {
+            sessdata: sessdata.into(),
+            bili_jct: bili_jct.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_uid(mut self, uid: u64) -> Self {
+        self.uid = uid;
+        self
+    }
+
+    pub fn with_buvid3(mut self, buvid3: impl Into<String>) -> Self {
+        self.buvid3 = buvid3.into();
+        self
+    }
+
+    pub fn with_refresh_token(mut self, refresh_token: impl Into<String>) -> Self {
+        self.refresh_token = refresh_token.into();
+        self
+    }
+
+    /// 请求头`Cookie`的值
+    pub fn cookie(&self) -> String {
+        let mut cookie = format!("SESSDATA={}; bili_jct={}", self.sessdata, self.bili_jct);
+        if self.uid != 0 {
+            cookie.push_str(&format!("; DedeUserID={}", self.uid));
+        }
+        if !self.buvid3.is_empty() {
+            cookie.push_str(&format!("; buvid3={}", self.buvid3));
+        }
+        cookie
+    }
+}
+
+impl std::fmt::Debug for Credential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        /// 非空时只显示为`***`
+        fn redact(secret: &str) -> &str {
+            if secret.is_empty() {
+                ""
+            } else {
+                "***"
+            }
+        }
+        f.debug_struct("Credential")
+            .field("sessdata", &redact(&self.sessdata))
+            .field("bili_jct", &redact(&self.bili_jct))
+            .field("uid", &self.uid)
+            .field("buvid3", &self.buvid3)
+            .field("refresh_token", &redact(&self.refresh_token))
+            .finish()
+    }
+}
+
+impl InitError {
+    /// 是否是凭据失效导致的错误，包括失效后刷新失败
+    pub fn is_credential_expired(&self) -> bool {
+        matches!(
+            self,
+            InitError::ApiError {
+                code: CODE_NOT_LOGGED_IN,
+                ..
+            } | InitError::RefreshFailed { .. }
+        )
+    }
+}
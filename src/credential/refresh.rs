@@ -0,0 +1,286 @@
+use std::{
+    fmt::Debug,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use rsa::{pkcs8::DecodePublicKey, Oaep, RsaPublicKey};
+use serde::Deserialize;
+
+use super::Credential;
+use crate::{
+    http::{parse_data, HttpClient},
+    InitError,
+};
+
+/// 生成`correspondPath`使用的公钥
+const CORRESPOND_PUBLIC_KEY: &str = "-----BEGIN PUBLIC KEY-----
+MIGfMA0GCSqGSIb3DQEBAQUAA4GNADCBiQKBgQDLgd2OAkcGVtoE3ThUREbio0Eg
+Uc/prcajMKXvkCKFCWhJYJcLkcM2DKKcSeFpD/j6Boy538YXnR6VhcuUJOhH2x71
+nzPjfdTcqMz7djHum0qSZA0AyCBDABUqCrfNgCiJ00Ra7GmRj+YCK1NJEuewlb40
+JNrRuoEUXpabUzGB8QIDAQAB
+-----END PUBLIC KEY-----";
+
+///
+/// api url:
+/// https://passport.bilibili.com/x/passport-login/web/cookie/info?csrf=xxx
+#[derive(Debug, Deserialize)]
+struct CookieInfoData {
+    refresh: bool,
+    timestamp: u64,
+}
+
+///
+/// api url:
+/// https://passport.bilibili.com/x/passport-login/web/cookie/refresh
+#[derive(Debug, Deserialize)]
+struct CookieRefreshData {
+    refresh_token: String,
+}
+
+impl HttpClient {
+    /// cookie是否需要刷新，cookie已经失效时返回[`CODE_NOT_LOGGED_IN`](super::CODE_NOT_LOGGED_IN)的错误
+    pub async fn cookie_needs_refresh(&self, credential: &Credential) -> Result<bool, InitError> {
+        Ok(self.cookie_info(credential).await?.refresh)
+    }
+
+    async fn cookie_info(&self, credential: &Credential) -> Result<CookieInfoData, InitError> {
+        let request = self
+            .client
+            .get(format!(
+                "https://passport.bilibili.com/x/passport-login/web/cookie/info?csrf={}",
+                credential.bili_jct
+            ))
+            .header("Cookie", credential.cookie());
+        self.send_data(request).await
+    }
+
+    /// 用`refresh_token`刷新cookie，返回新的凭据，旧的凭据随后失效
+    ///
+    /// `refresh_token`为空时返回[`InitError::MissingRefreshToken`]，已经使用过时同样只能重新登录
+    #[tracing::instrument(level = "debug", skip_all, fields(uid = credential.uid))]
+    pub async fn refresh_credential(
+        &self,
+        credential: &Credential,
+    ) -> Result<Credential, InitError> {
+        if credential.refresh_token.is_empty() {
+            return Err(InitError::MissingRefreshToken);
+        }
+        let CookieInfoData { timestamp, .. } = self.cookie_info(credential).await?;
+        let refresh_csrf = self.refresh_csrf(credential, timestamp).await?;
+        let form = [
+            ("csrf", credential.bili_jct.as_str()),
+            ("refresh_csrf", &refresh_csrf),
+            ("source", "main_web"),
+            ("refresh_token", &credential.refresh_token),
+        ];
+        let request = self
+            .client
+            .post("https://passport.bilibili.com/x/passport-login/web/cookie/refresh")
+            .header("Cookie", credential.cookie())
+            .form(&form);
+        let resp = self.send(request).await?;
+        let mut refreshed = Credential {
+            refresh_token: String::new(),
+            ..credential.clone()
+        };
+        for cookie in resp.headers().get_all(reqwest::header::SET_COOKIE) {
+            let Some((key, value)) = cookie
+                .to_str()
+                .ok()
+                .and_then(|cookie| cookie.split(';').next())
+                .and_then(|pair| pair.split_once('='))
+            else {
+                continue;
+            };
+            match key.trim() {
+                "SESSDATA" => refreshed.sessdata = value.to_owned(),
+                "bili_jct" => refreshed.bili_jct = value.to_owned(),
+                "DedeUserID" => refreshed.uid = value.parse().unwrap_or(refreshed.uid),
+                _ => {}
+            }
+        }
+        let CookieRefreshData { refresh_token } = parse_data(&resp.bytes().await?)?;
+        refreshed.refresh_token = refresh_token;
+        if refreshed.sessdata == credential.sessdata {
+            return Err(InitError::ParseError(
+                "刷新后没有返回新的SESSDATA".to_owned(),
+            ));
+        }
+        // 确认刷新，使旧的refresh_token失效
+        let form = [
+            ("csrf", refreshed.bili_jct.as_str()),
+            ("refresh_token", &credential.refresh_token),
+        ];
+        let request = self
+            .client
+            .post("https://passport.bilibili.com/x/passport-login/web/confirm/refresh")
+            .header("Cookie", refreshed.cookie())
+            .form(&form);
+        if let Err(e) = self.send_data::<serde::de::IgnoredAny>(request).await {
+            tracing::warn!(error = %e, "确认刷新cookie失败");
+        }
+        Ok(refreshed)
+    }
+
+    /// 从`correspond`页面中取出`refresh_csrf`
+    async fn refresh_csrf(
+        &self,
+        credential: &Credential,
+        timestamp: u64,
+    ) -> Result<String, InitError> {
+        let request = self
+            .client
+            .get(format!(
+                "https://www.bilibili.com/correspond/1/{}",
+                correspond_path(timestamp)?
+            ))
+            .header("Cookie", credential.cookie());
+        let html = self.send(request).await?.text().await?;
+        html.split_once(r#"<div id="1-name">"#)
+            .and_then(|(_, rest)| rest.split_once("</div>"))
+            .map(|(csrf, _)| csrf.trim().to_owned())
+            .filter(|csrf| !csrf.is_empty())
+            .ok_or_else(|| InitError::ParseError("correspond页面中没有refresh_csrf".to_owned()))
+    }
+}
+
+/// 用公钥加密`refresh_{timestamp}`得到的路径
+pub(crate) fn correspond_path(timestamp: u64) -> Result<String, InitError> {
+    let key = RsaPublicKey::from_public_key_pem(CORRESPOND_PUBLIC_KEY)
+        .map_err(|e| InitError::ParseError(e.to_string()))?;
+    let encrypted = key
+        .encrypt(
+            &mut rsa::rand_core::OsRng,
+            Oaep::new::<sha2::Sha256>(),
+            format!("refresh_{timestamp}").as_bytes(),
+        )
+        .map_err(|e| InitError::ParseError(e.to_string()))?;
+    Ok(hex::encode(encrypted))
+}
+
+pub type ReloginCallback = Arc<dyn Fn(&InitError) + Send + Sync>;
+
+/// 共享并自动刷新的凭据，克隆后共享同一份凭据
+///
+/// 同一时间只有一个刷新在进行，等待中的调用发现凭据已经被刷新过时直接使用新的凭据，
+/// 不会用已经失效的`refresh_token`再刷新一次
+///
+/// # 说明
+/// - `on_relogin` 刷新失败、只能重新登录时调用
+#[derive(Clone)]
+pub struct CredentialManager {
+    client: HttpClient,
+    credential: Arc<Mutex<Credential>>,
+    refreshing: Arc<futures::lock::Mutex<()>>,
+    on_relogin: Option<ReloginCallback>,
+}
+
+impl CredentialManager {
+    /// 默认使用[`HttpClient::shared`]，和其他接口共享限流
+    pub fn new(credential: Credential) -> Self {
+        Self {
+            client: HttpClient::shared().clone(),
+            credential: Arc::new(Mutex::new(credential)),
+            refreshing: Default::default(),
+            on_relogin: None,
+        }
+    }
+
+    pub fn with_client(mut self, client: HttpClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn with_on_relogin<F>(mut self, on_relogin: F) -> Self
+    where
+        F: Fn(&InitError) + Send + Sync + 'static,
+    {
+        self.on_relogin = Some(Arc::new(on_relogin));
+        self
+    }
+
+    /// 当前凭据
+    pub fn credential(&self) -> Credential {
+        self.credential.lock().expect("credential poisoned").clone()
+    }
+
+    /// 替换凭据，比如重新登录之后
+    pub fn set(&self, credential: Credential) {
+        *self.credential.lock().expect("credential poisoned") = credential;
+    }
+
+    /// 刷新凭据，失败时调用`on_relogin`，与其他刷新同时调用时只刷新一次
+    pub async fn refresh(&self) -> Result<(), InitError> {
+        self.refresh_from(&self.credential()).await
+    }
+
+    /// `stale`失效后刷新，等到锁时凭据已经被其他调用刷新过则不再刷新
+    async fn refresh_from(&self, stale: &Credential) -> Result<(), InitError> {
+        let _refreshing = self.refreshing.lock().await;
+        let current = self.credential();
+        if current.sessdata != stale.sessdata {
+            tracing::debug!(uid = current.uid, "凭据已经被刷新过");
+            return Ok(());
+        }
+        match self.client.refresh_credential(&current).await {
+            Ok(refreshed) => {
+                tracing::info!(uid = refreshed.uid, "cookie已刷新");
+                self.set(refreshed);
+                Ok(())
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "刷新cookie失败，需要重新登录");
+                if let Some(on_relogin) = &self.on_relogin {
+                    on_relogin(&e);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// 检查cookie，需要时刷新，返回是否刷新了
+    pub async fn refresh_if_needed(&self) -> Result<bool, InitError> {
+        let current = self.credential();
+        let needs_refresh = match self.client.cookie_needs_refresh(&current).await {
+            Ok(needs_refresh) => needs_refresh,
+            Err(e) if e.is_credential_expired() => true,
+            Err(e) => return Err(e),
+        };
+        if needs_refresh {
+            self.refresh_from(&current).await?;
+        }
+        Ok(needs_refresh)
+    }
+
+    /// 用当前凭据调用接口，返回未登录时刷新凭据后重试一次
+    ///
+    /// 刷新失败时返回[`InitError::RefreshFailed`]，其中包括刷新的错误和原来的错误
+    pub async fn call<T, F, Fut>(&self, f: F) -> Result<T, InitError>
+    where
+        F: Fn(Credential) -> Fut,
+        Fut: Future<Output = Result<T, InitError>>,
+    {
+        let current = self.credential();
+        match f(current.clone()).await {
+            Err(e) if e.is_credential_expired() => {
+                self.refresh_from(&current)
+                    .await
+                    .map_err(|refresh| InitError::RefreshFailed {
+                        source: Box::new(refresh),
+                        expired: Box::new(e),
+                    })?;
+                f(self.credential()).await
+            }
+            result => result,
+        }
+    }
+}
+
+impl Debug for CredentialManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CredentialManager")
+            .field("uid", &self.credential().uid)
+            .finish()
+    }
+}
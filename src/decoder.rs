@@ -0,0 +1,125 @@
+//! 不依赖异步运行时的协议层
+//!
+//! 使用tokio和wasm以外的运行时（比如async-std、smol）时，只启用`protocol`，自行建立websocket连接和心跳定时器，
+//! 用[`Decoder`]构造鉴权包、心跳包，并把收到的二进制帧解析为事件
+//!```no_run,ignore
+//!use bilive_danmaku::{decoder::Decoder, Auth};
+//!let decoder = Decoder::new();
+//!ws.send(Decoder::auth_frame(Auth::new(uid, roomid, Some(token)))).await?;
+//!// 每30秒发送一次 Decoder::heartbeat_frame()
+//!while let Some(frame) = ws.next().await {
+//!    for event in decoder.decode(&frame?)? {
+//!        println!("{:?}", event);
+//!    }
+//!}
+//!```
+use crate::{
+    cmd::Cmd,
+    event::Event,
+    filter::CmdFilter,
+    packet::{Auth, Data, Operation, PacketParseError, RawPacket},
+    parser::CmdParsers,
+};
+
+/// 二进制帧解码器，不保存连接状态，可以在多个连接间共用
+///
+/// # 说明
+/// - `cmd_filter` 按b站原始的cmd丢弃消息，见[`CmdFilter`]
+/// - `keep_raw` 在事件的`raw`字段中保留原始json
+/// - `parsers` 在内置解析之前尝试的解析器，见[`CmdParsers`]
+#[derive(Debug, Clone, Default)]
+pub struct Decoder {
+    pub cmd_filter: CmdFilter,
+    pub keep_raw: bool,
+    pub parsers: CmdParsers,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_cmd_filter(mut self, cmd_filter: CmdFilter) -> Self {
+        self.cmd_filter = cmd_filter;
+        self
+    }
+
+    pub fn with_keep_raw(mut self, keep_raw: bool) -> Self {
+        self.keep_raw = keep_raw;
+        self
+    }
+
+    pub fn with_parsers(mut self, parsers: CmdParsers) -> Self {
+        self.parsers = parsers;
+        self
+    }
+
+    /// 连接建立后需要立即发送的鉴权包
+    pub fn auth_frame(auth: Auth) -> Vec<u8> {
+        RawPacket::build(Operation::Auth, auth.ser()).ser()
+    }
+
+    /// 心跳包，需要每30秒发送一次
+    pub fn heartbeat_frame() -> Vec<u8> {
+        RawPacket::build(Operation::Heartbeat, b"[object Object]".to_vec()).ser()
+    }
+
+    /// 把一个二进制帧解析为事件，帧格式错误时返回错误，其中单条消息解析失败时记录日志并跳过
+    pub fn decode(&self, frame: &[u8]) -> Result<Vec<Event>, PacketParseError> {
+        let packet = RawPacket::from_buffer(frame)?;
+        let mut events = vec![];
+        for data in packet.get_datas(&mut 0, &self.cmd_filter) {
+            match data.into_event(self.keep_raw, &self.parsers) {
+                Ok(Some(event)) => events.push(event),
+                Ok(None) => {}
+                Err(e) => tracing::warn!(error = %e, cmd = e.cmd(), "解析数据包失败"),
+            }
+        }
+        Ok(events)
+    }
+
+    /// 把一个二进制帧解析为[`Cmd`]，不转换为事件，人气值等不是json消息的数据会被跳过
+    ///
+    /// 只使用内置的解析，不经过`parsers`（它们直接产生[`Event`]），也不保留消息的服务器时间戳和原始json
+    pub fn decode_cmds(&self, frame: &[u8]) -> Result<Vec<Cmd>, PacketParseError> {
+        let packet = RawPacket::from_buffer(frame)?;
+        let mut cmds = vec![];
+        for data in packet.get_datas(&mut 0, &self.cmd_filter) {
+            let Data::Json(json) = data else {
+                continue;
+            };
+            match Cmd::deser(json) {
+                Ok(cmd) => cmds.push(cmd),
+                Err(e) => tracing::warn!(error = %e, "解析数据包失败"),
+            }
+        }
+        Ok(cmds)
+    }
+
+    /// 解析为自定义的事件类型，`E::try_from`失败的消息被丢弃
+    ///
+    /// 与[`decode_cmds`](Self::decode_cmds)相同，不经过`parsers`。只有自行解码时可以使用自定义的事件类型，
+    /// [`Connection`](crate::Connection)等连接的事件流始终产生[`Event`]
+    ///```no_run,ignore
+    ///enum MyEvent {
+    ///    Watched(u64),
+    ///}
+    ///impl TryFrom<Cmd> for MyEvent {
+    ///    type Error = ();
+    ///    fn try_from(cmd: Cmd) -> Result<Self, ()> {
+    ///        match cmd {
+    ///            Cmd::WatchedChange { num, .. } => Ok(MyEvent::Watched(num)),
+    ///            _ => Err(()),
+    ///        }
+    ///    }
+    ///}
+    ///let events: Vec<MyEvent> = decoder.decode_as(&frame)?;
+    ///```
+    pub fn decode_as<E: TryFrom<Cmd>>(&self, frame: &[u8]) -> Result<Vec<E>, PacketParseError> {
+        Ok(self
+            .decode_cmds(frame)?
+            .into_iter()
+            .filter_map(|cmd| E::try_from(cmd).ok())
+            .collect())
+    }
+}
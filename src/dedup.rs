@@ -0,0 +1,99 @@
+//! 重复事件抑制
+//!
+//! 断线重连前后，b站有时会把同一条弹幕、礼物推送两次。[`Deduplicator`]在一个时间窗口内记录见过的消息，丢弃重复的消息。
+//!
+//! [`Deduplicator`]克隆后共享同一个窗口，把它放在[`ConnectConfig`](crate::connection::ConnectConfig)里，重连后的新连接也能识别出重复的消息
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde_json::Value;
+
+use crate::event::now_millis;
+
+#[derive(Debug, Clone)]
+pub struct Deduplicator {
+    window: Arc<Mutex<Window>>,
+}
+
+#[derive(Debug)]
+struct Window {
+    duration: u64,
+    seen: HashSet<String>,
+    order: VecDeque<(u64, String)>,
+}
+
+impl Deduplicator {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window: Arc::new(Mutex::new(Window {
+                duration: window.as_millis() as u64,
+                seen: HashSet::new(),
+                order: VecDeque::new(),
+            })),
+        }
+    }
+
+    /// 是否是窗口内第一次出现的`key`
+    pub fn check(&self, key: String) -> bool {
+        let now = now_millis();
+        let mut window = self.window.lock().expect("deduplicator poisoned");
+        while let Some((timestamp, _)) = window.order.front() {
+            if now.saturating_sub(*timestamp) <= window.duration {
+                break;
+            }
+            if let Some((_, expired)) = window.order.pop_front() {
+                window.seen.remove(&expired);
+            }
+        }
+        if window.seen.contains(&key) {
+            return false;
+        }
+        window.seen.insert(key.clone());
+        window.order.push_back((now, key));
+        true
+    }
+
+    /// 原始消息是否是第一次出现，没有唯一标识的消息总是返回`true`
+    pub fn check_json(&self, json: &Value) -> bool {
+        match dedup_key(json) {
+            Some(key) => self.check(key),
+            None => true,
+        }
+    }
+}
+
+/// 原始消息的唯一标识
+///
+/// - `DANMU_MSG` (uid, 发送时间, 文本)
+/// - `SEND_GIFT` tid
+/// - `SUPER_CHAT_MESSAGE` id
+/// - `GUARD_BUY` (uid, 开始时间)
+pub fn dedup_key(json: &Value) -> Option<String> {
+    let cmd = json["cmd"].as_str()?;
+    let data = &json["data"];
+    match cmd {
+        "DANMU_MSG" => {
+            let info = &json["info"];
+            let uid = info[2][0].as_u64()?;
+            let timestamp = info[0][4].as_u64()?;
+            let text = info[1].as_str()?;
+            Some(format!("{cmd}:{uid}:{timestamp}:{text}"))
+        }
+        "SEND_GIFT" => Some(format!("{cmd}:{}", data["tid"].as_str()?)),
+        // JPN版本的id是字符串
+        "SUPER_CHAT_MESSAGE" | "SUPER_CHAT_MESSAGE_JPN" => match &data["id"] {
+            Value::Number(id) => Some(format!("{cmd}:{id}")),
+            Value::String(id) => Some(format!("{cmd}:{id}")),
+            _ => None,
+        },
+        "GUARD_BUY" => Some(format!(
+            "{cmd}:{}:{}",
+            data["uid"].as_u64()?,
+            data["start_time"].as_u64()?
+        )),
+        _ => None,
+    }
+}
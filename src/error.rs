@@ -1,11 +1,12 @@
 use crate::cmd::CmdDeserError;
 use crate::connection::{EventStreamError, WsConnectError};
-use crate::InitError;
+use crate::{ConnectError, InitError};
 
 #[derive(Debug)]
 pub enum Error {
     CmdDeserialize(CmdDeserError),
     Init(InitError),
+    Connect(ConnectError),
     EventStream(EventStreamError),
     WsConnect(WsConnectError),
 }
@@ -15,8 +16,51 @@ impl std::fmt::Display for Error {
         match self {
             Error::CmdDeserialize(e) => f.write_fmt(format_args!("命令解析错误：{e}")),
             Error::Init(e) => f.write_fmt(format_args!("连接初始化错误：{e}")),
+            Error::Connect(e) => f.write_fmt(format_args!("连接错误：{e}")),
             Error::EventStream(e) => f.write_fmt(format_args!("事件流错误：{e}")),
             Error::WsConnect(e) => f.write_fmt(format_args!("建立websocket连接错误: {e}")),
         }
     }
 }
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::CmdDeserialize(e) => Some(e),
+            Error::Init(e) => Some(e),
+            Error::Connect(e) => Some(e),
+            Error::EventStream(e) => Some(e),
+            Error::WsConnect(e) => Some(e),
+        }
+    }
+}
+
+impl From<CmdDeserError> for Error {
+    fn from(e: CmdDeserError) -> Self {
+        Error::CmdDeserialize(e)
+    }
+}
+
+impl From<InitError> for Error {
+    fn from(e: InitError) -> Self {
+        Error::Init(e)
+    }
+}
+
+impl From<ConnectError> for Error {
+    fn from(e: ConnectError) -> Self {
+        Error::Connect(e)
+    }
+}
+
+impl From<EventStreamError> for Error {
+    fn from(e: EventStreamError) -> Self {
+        Error::EventStream(e)
+    }
+}
+
+impl From<WsConnectError> for Error {
+    fn from(e: WsConnectError) -> Self {
+        Error::WsConnect(e)
+    }
+}
@@ -2,8 +2,11 @@ use crate::model::*;
 
 use serde::{Deserialize, Serialize};
 
+mod display;
+
 macro_rules! define_event {
     ($(
+        $(#[$struct_attrs:meta])*
         $name:ident{$(
             $(#[$attrs:meta])*
             $arg:ident: $ty:ty
@@ -15,7 +18,25 @@ macro_rules! define_event {
             $($name ($name)),*
         }
 
+        impl EventData {
+            /// 事件类型名，与序列化时的`cmd`字段一致
+            pub fn cmd(&self) -> &'static str {
+                match self {
+                    $(EventData::$name(_) => stringify!($name)),*
+                }
+            }
+        }
+
+        impl std::fmt::Display for EventData {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    $(EventData::$name(e) => e.fmt(f)),*
+                }
+            }
+        }
+
         $(
+            $(#[$struct_attrs])*
             #[derive(Clone, Debug, Serialize, Deserialize)]
             pub struct $name {
                 $(
@@ -28,21 +49,59 @@ macro_rules! define_event {
                     EventData::$name(event)
                 }
             }
+            impl EventKind for $name {
+                const CMD: &'static str = stringify!($name);
+                fn from_data(data: EventData) -> Option<Self> {
+                    match data {
+                        EventData::$name(event) => Some(event),
+                        _ => None,
+                    }
+                }
+            }
         )*
     };
 }
 
+/// [`EventData`]中的一种事件，由`define_event!`为每个事件类型实现
+pub trait EventKind: Into<EventData> + Sized {
+    /// 与[`EventData::cmd`]一致
+    const CMD: &'static str;
+    /// 类型不符时返回`None`
+    fn from_data(data: EventData) -> Option<Self>;
+}
+
 define_event! {
     DanmakuEvent {
         /// 第一位：是否是抽奖弹幕，2~4位，舰长类型
         flag: u64,
         message: DanmakuMessage,
         user: User,
-        fans_medal: Option<FansMedal>
+        fans_medal: Option<FansMedal>,
+        /// 在本直播间的大航海等级，1，2，3分别为总督，提督，舰长；0为无
+        #[serde(default)]
+        guard_level: u64,
+        /// 用户等级(UL)
+        #[serde(default)]
+        user_level: u64,
+        /// 弹幕模式，1为滚动，4为底部，5为顶部
+        #[serde(default = "default_danmaku_mode")]
+        mode: u64,
+        /// 字号，默认为25
+        #[serde(default = "default_danmaku_font_size")]
+        font_size: u64,
+        /// 颜色，`0xRRGGBB`
+        #[serde(default = "default_danmaku_color")]
+        color: u32,
     },
     EnterRoomEvent {
         user: User,
-        fans_medal: Option<FansMedal>
+        fans_medal: Option<FansMedal>,
+        /// 在本直播间的大航海等级，1，2，3分别为总督，提督，舰长；0为无
+        #[serde(default)]
+        guard_level: u64,
+        /// 用户等级(UL)，消息中没有时为0
+        #[serde(default)]
+        user_level: u64,
     },
     BlindboxGiftEvent {
         user: User,
@@ -62,10 +121,14 @@ define_event! {
         user: User
     },
     SuperChatEvent {
+        /// 醒目留言的id，同一条醒目留言的中文和日文消息id相同
+        #[serde(default)]
+        id: u64,
         user: User,
         fans_medal: Option<FansMedal>,
         price: u64,
         message: String,
+        /// 日文翻译，来自`SUPER_CHAT_MESSAGE_JPN`或`SUPER_CHAT_MESSAGE`的`message_trans`
         message_jpn: Option<String>
     },
     WatchedUpdateEvent {
@@ -74,8 +137,15 @@ define_event! {
     PopularityUpdateEvent {
         popularity: u32,
     },
+    /// 带有进场特效的用户进入直播间，来自`ENTRY_EFFECT`，消息中的用户名可能被截断
     GuardEnterRoomEvent {
         user: User,
+        /// 大航海等级，1，2，3分别为总督，提督，舰长；0为无，比如高等级用户的进场特效
+        #[serde(default)]
+        guard_level: u64,
+        /// 去掉了用户名标记的欢迎文案，比如"欢迎舰长 xxx 进入直播间"
+        #[serde(default)]
+        copy_writing: String,
     },
     HotRankChangedEvent {
         area: String,
@@ -90,27 +160,274 @@ define_event! {
     },
     StopLiveEvent{
         room_id_list: Vec<u64>
+    },
+    /// 人气红包开始，在`duration`秒内发送口令弹幕`danmu`即可参与
+    RedPocketStartEvent {
+        lot_id: u64,
+        sender: User,
+        /// 红包总价，单位为金瓜子
+        total_price: u64,
+        duration: u64,
+        danmu: String,
+        awards: Vec<RedPocketAward>,
+    },
+    /// 人气红包开奖
+    RedPocketWinnerEvent {
+        lot_id: u64,
+        total_num: u64,
+        winners: Vec<RedPocketWinner>,
+    },
+    /// 天选时刻开始，在`duration`秒内满足`require_text`的条件并发送口令弹幕`danmu`即可参与
+    AnchorLotStartEvent {
+        id: u64,
+        award_name: String,
+        award_num: u64,
+        require_text: String,
+        /// 参与需要发送的口令弹幕，可能为空
+        danmu: String,
+        /// 参与需要赠送的礼物，不需要时为`None`
+        gift: Option<GiftType>,
+        duration: u64,
+    },
+    /// 天选时刻结束
+    AnchorLotEndEvent {
+        id: u64,
+    },
+    /// 天选时刻开奖
+    AnchorLotAwardEvent {
+        id: u64,
+        award_name: String,
+        award_num: u64,
+        winners: Vec<User>,
+    },
+    /// 直播间的标题或分区发生变化，`area_name`为子分区，`parent_area_name`为父分区
+    RoomChangeEvent {
+        title: String,
+        area_id: u64,
+        area_name: String,
+        parent_area_id: u64,
+        parent_area_name: String,
+    },
+    /// 开播、下播或开始轮播
+    LiveStatusEvent {
+        status: LiveStatus,
+        /// 开播时间，秒级时间戳，只有开播时才有
+        live_time: Option<u64>,
+    },
+    /// 用户为主播点赞，`text`为"为主播点赞了"之类的文案
+    LikeClickEvent {
+        user: User,
+        fans_medal: Option<FansMedal>,
+        text: String,
+    },
+    /// 本场直播的点赞总数
+    LikeCountUpdateEvent {
+        count: u64,
+    },
+    /// 粉丝数和粉丝团人数更新，来自`ROOM_REAL_TIME_MESSAGE_UPDATE`
+    FansUpdateEvent {
+        fans: u64,
+        fans_club: u64,
+    },
+    /// 短时间内大量相同的弹幕被合并显示，常见于天选时刻和红包的口令弹幕
+    DanmakuAggregationEvent {
+        message: String,
+        count: u64,
+        /// 触发聚合的活动id，比如天选时刻的id
+        activity_id: String,
+    },
+    /// 高能榜（在线贡献榜）更新
+    OnlineRankUpdateEvent {
+        /// 榜单类型，比如`gold-rank`
+        rank_type: String,
+        list: Vec<OnlineRankUser>,
+    },
+    /// 高能榜前三名变化，`messages`为"恭喜 xxx 成为高能榜"之类的文案，按名次排列
+    OnlineRankTop3Event {
+        messages: Vec<String>,
+    },
+    /// 全站或分区的广播，比如其他直播间的大额礼物、抽奖中奖
+    NoticeEvent {
+        /// 广播类型，比如2为道具抽奖
+        msg_type: u64,
+        /// 广播样式的名称，比如"分区道具抽奖广播样式"
+        name: String,
+        /// 广播文案，`<%`和`%>`之间为高亮的部分，见[`NoticeEvent::text`]
+        template: String,
+        /// 在`roomid`直播间中显示的文案
+        template_self: String,
+        /// 广播相关的直播间，系统广播为0
+        roomid: u64,
+        /// 点击广播跳转的地址，可能为空
+        link_url: String,
+    },
+    /// 用户在直播间中被禁言
+    UserBlockedEvent {
+        user: User,
+        operator: BlockOperator,
+    },
+    /// 匹配到PK对手，PK即将开始
+    PkBattlePreEvent {
+        opponent: User,
+        opponent_roomid: u64,
+        /// PK值的名称，比如`PK值`、`乱斗值`
+        votes_name: String,
+    },
+    /// PK开始，`init`为发起方，`matched`为被匹配的一方，本直播间可能是任意一方，按`roomid`区分
+    PkBattleStartEvent {
+        init: PkRoom,
+        matched: PkRoom,
+        /// 开始和结束时间，秒级时间戳
+        start_time: u64,
+        end_time: u64,
+        votes_name: String,
+    },
+    /// PK过程中双方的PK值更新
+    PkBattleProcessEvent {
+        init: PkRoom,
+        matched: PkRoom,
+    },
+    /// PK结束，`winner`为获胜方的直播间号，平局时为`None`
+    PkBattleEndEvent {
+        init: PkRoom,
+        matched: PkRoom,
+        winner: Option<u64>,
+    },
+    /// 由[`CmdParsers`](crate::parser::CmdParsers)中注册的解析器产生的、库中还没有建模的消息
+    CustomEvent {
+        /// b站原始的cmd
+        cmd: String,
+        /// 消息的`data`字段，没有时为整条消息
+        data: serde_json::Value,
+    },
+    /// [`DanmakuSampler`](crate::filter::DanmakuSampler)在上一个窗口中丢弃的弹幕数
+    DanmakuThrottledEvent {
+        dropped: u64,
+        /// 窗口时长，毫秒
+        window: u64,
+    },
+    /// 连接出错或停滞后自动切换了服务器
+    HostSwitchEvent {
+        from: String,
+        to: String,
+        reason: String,
+    },
+    /// 滑动窗口内的统计，由[`RollingStats`](crate::stats::RollingStats)产生
+    StatsEvent {
+        /// 窗口时长，秒
+        window: u64,
+        danmaku_count: u64,
+        danmaku_per_minute: f64,
+        /// 发送过弹幕的用户数
+        unique_chatters: u64,
+        /// 金瓜子礼物的价值，1000金瓜子为1元
+        gift_gold: u64,
+        super_chat_count: u64,
+        /// 醒目留言的总价，元
+        super_chat_price: u64,
+    }
+}
+
+fn default_danmaku_mode() -> u64 {
+    1
+}
+
+fn default_danmaku_font_size() -> u64 {
+    25
+}
+
+fn default_danmaku_color() -> u32 {
+    0xFFFFFF
+}
+
+/// [`EventData::user`]和[`EventData::user_mut`]共用的匹配，`$ref`为`&`或`&mut`
+macro_rules! event_user {
+    ($data:expr, $($ref:tt)+) => {
+        match $data {
+            EventData::DanmakuEvent(e) => Some($($ref)+ e.user),
+            EventData::EnterRoomEvent(e) => Some($($ref)+ e.user),
+            EventData::BlindboxGiftEvent(e) => Some($($ref)+ e.user),
+            EventData::GiftEvent(e) => Some($($ref)+ e.user),
+            EventData::GuardBuyEvent(e) => Some($($ref)+ e.user),
+            EventData::SuperChatEvent(e) => Some($($ref)+ e.user),
+            EventData::GuardEnterRoomEvent(e) => Some($($ref)+ e.user),
+            EventData::RedPocketStartEvent(e) => Some($($ref)+ e.sender),
+            EventData::LikeClickEvent(e) => Some($($ref)+ e.user),
+            EventData::UserBlockedEvent(e) => Some($($ref)+ e.user),
+            EventData::PkBattlePreEvent(e) => Some($($ref)+ e.opponent),
+            _ => None,
+        }
+    };
+}
+
+impl EventData {
+    /// 产生事件的用户，禁言事件为被禁言的用户，PK匹配事件为对手主播
+    pub fn user(&self) -> Option<&User> {
+        event_user!(self, &)
+    }
+
+    /// 同[`user`](Self::user)，用来补全用户信息
+    pub fn user_mut(&mut self) -> Option<&mut User> {
+        event_user!(self, &mut)
+    }
+}
+
+/// 当前的毫秒时间戳，wasm32-unknown-unknown上`SystemTime::now()`会panic，所以使用js的`Date.now()`
+pub(crate) fn now_millis() -> u64 {
+    #[cfg(all(feature = "rt_wasm", target_arch = "wasm32"))]
+    {
+        js_sys::Date::now() as u64
+    }
+    #[cfg(not(all(feature = "rt_wasm", target_arch = "wasm32")))]
+    {
+        use std::time::*;
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("时间倒流")
+            .as_millis() as u64
+    }
+}
+
+impl NoticeEvent {
+    /// 去掉高亮标记后的文案
+    pub fn text(&self) -> String {
+        self.template.replace("<%", "").replace("%>", "")
+    }
+
+    /// 文案中高亮的部分，通常是用户名和主播名
+    pub fn highlights(&self) -> Vec<&str> {
+        self.template
+            .split("<%")
+            .skip(1)
+            .filter_map(|part| part.split_once("%>").map(|(highlight, _)| highlight))
+            .collect()
     }
 }
 
 impl From<EventData> for Event {
     fn from(val: EventData) -> Self {
-        use std::time::*;
         Event {
             data: val,
-            timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .expect("时间倒流")
-                .as_millis() as u64,
+            timestamp: now_millis(),
+            server_timestamp: None,
+            raw: None,
         }
     }
 }
 
+/// # 说明
+/// - `timestamp` 收到事件时的本地毫秒时间戳
+/// - `server_timestamp` 消息中携带的服务器毫秒时间戳，比如弹幕的发送时间，消息中没有时为`None`
+/// - `raw` 产生这个事件的原始json，只有开启了`keep_raw`时才有，可以从中取出还没有建模的字段
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Event {
     #[serde(flatten)]
     pub data: EventData,
     pub timestamp: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_timestamp: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw: Option<serde_json::Value>,
 }
 
 #[cfg(feature = "bincode")]
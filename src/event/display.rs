@@ -0,0 +1,355 @@
+//! 事件的单行文本格式，用于日志和命令行
+use std::fmt::{Display, Formatter, Result};
+
+use super::*;
+use crate::stats::gold_to_cny;
+
+const RESET: &str = "\x1b[0m";
+const GREY: &str = "\x1b[90m";
+const CYAN: &str = "\x1b[36m";
+const YELLOW: &str = "\x1b[33m";
+const RED: &str = "\x1b[31m";
+const MAGENTA: &str = "\x1b[35m";
+
+fn guard_name(level: u64) -> &'static str {
+    match level {
+        1 => "总督",
+        2 => "提督",
+        _ => "舰长",
+    }
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        self.data.fmt(f)
+    }
+}
+
+impl Event {
+    /// 带有ANSI颜色的单行文本，见[`EventData::format_colored`]
+    pub fn format_colored(&self) -> String {
+        self.data.format_colored()
+    }
+}
+
+impl EventData {
+    /// 带有ANSI颜色的单行文本，弹幕的用户名为青色，礼物类为黄色，醒目留言为红色，大航海为紫色，其余为灰色
+    pub fn format_colored(&self) -> String {
+        match self {
+            EventData::DanmakuEvent(e) => {
+                let medal = match &e.fans_medal {
+                    Some(medal) => format!("{GREY}{medal}{RESET} "),
+                    None => String::new(),
+                };
+                format!("{medal}{CYAN}{}{RESET}: {}", e.user.uname, e.message)
+            }
+            EventData::GiftEvent(_)
+            | EventData::BlindboxGiftEvent(_)
+            | EventData::RedPocketStartEvent(_)
+            | EventData::RedPocketWinnerEvent(_)
+            | EventData::AnchorLotStartEvent(_)
+            | EventData::AnchorLotAwardEvent(_) => format!("{YELLOW}{self}{RESET}"),
+            EventData::SuperChatEvent(_) => format!("{RED}{self}{RESET}"),
+            EventData::GuardBuyEvent(_) | EventData::GuardEnterRoomEvent(_) => {
+                format!("{MAGENTA}{self}{RESET}")
+            }
+            _ => format!("{GREY}{self}{RESET}"),
+        }
+    }
+}
+
+impl Display for DanmakuEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        if let Some(medal) = &self.fans_medal {
+            write!(f, "{medal} ")?;
+        }
+        write!(f, "{}: {}", self.user.uname, self.message)
+    }
+}
+
+impl Display for EnterRoomEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{} 进入直播间", self.user.uname)
+    }
+}
+
+impl Display for BlindboxGiftEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "{} {} ({}开出)",
+            self.user.uname, self.gift, self.blindbox_gift_type.gift_name
+        )
+    }
+}
+
+impl Display for GiftEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{} {}", self.user.uname, self.gift)?;
+        if let Some(blindbox) = &self.blindbox {
+            write!(f, " ({}开出)", blindbox.gift_name)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for GuardBuyEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{} 开通了{}", self.user.uname, guard_name(self.level))
+    }
+}
+
+impl Display for SuperChatEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "[醒目留言 ¥{}] {}: {}",
+            self.price, self.user.uname, self.message
+        )
+    }
+}
+
+impl Display for WatchedUpdateEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}人看过", self.num)
+    }
+}
+
+impl Display for PopularityUpdateEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "人气值 {}", self.popularity)
+    }
+}
+
+impl Display for GuardEnterRoomEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        if self.copy_writing.is_empty() {
+            write!(f, "{} 进入直播间", self.user.uname)
+        } else {
+            f.write_str(self.copy_writing.trim())
+        }
+    }
+}
+
+impl Display for HotRankChangedEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}热门榜第{}名", self.area, self.rank)
+    }
+}
+
+impl Display for HotRankSettlementEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{} 获得{}热门榜第{}名", self.uname, self.area, self.rank)
+    }
+}
+
+impl Display for StopLiveEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}个直播间下播", self.room_id_list.len())
+    }
+}
+
+impl Display for RedPocketStartEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "{} 发出了¥{:.2}的红包，口令: {}",
+            self.sender.uname,
+            gold_to_cny(self.total_price),
+            self.danmu
+        )
+    }
+}
+
+impl Display for RedPocketWinnerEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "红包开奖，{}人中奖", self.winners.len())
+    }
+}
+
+impl Display for AnchorLotStartEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "天选时刻: {}x{}", self.award_name, self.award_num)?;
+        if !self.danmu.is_empty() {
+            write!(f, "，口令: {}", self.danmu)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for AnchorLotEndEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.write_str("天选时刻结束")
+    }
+}
+
+impl Display for AnchorLotAwardEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let winners = self
+            .winners
+            .iter()
+            .map(|user| user.uname.as_str())
+            .collect::<Vec<_>>()
+            .join("，");
+        write!(f, "天选时刻开奖: {}，中奖者: {}", self.award_name, winners)
+    }
+}
+
+impl Display for RoomChangeEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "直播间标题变为《{}》，分区: {}/{}",
+            self.title, self.parent_area_name, self.area_name
+        )
+    }
+}
+
+impl Display for LiveStatusEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self.status {
+            LiveStatus::Live => write!(f, "开播了"),
+            LiveStatus::Preparing => write!(f, "下播了"),
+            LiveStatus::Round => write!(f, "下播了，开始轮播"),
+        }
+    }
+}
+
+impl Display for LikeClickEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{} {}", self.user.uname, self.text)
+    }
+}
+
+impl Display for LikeCountUpdateEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "点赞数 {}", self.count)
+    }
+}
+
+impl Display for NoticeEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.write_str(&self.text())
+    }
+}
+
+impl Display for UserBlockedEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let operator = match self.operator {
+            BlockOperator::Admin => "房管",
+            BlockOperator::Anchor => "主播",
+            BlockOperator::Unknown(_) => "管理员",
+        };
+        write!(f, "{} 被{}禁言", self.user.uname, operator)
+    }
+}
+
+impl Display for PkBattlePreEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "匹配到PK对手 {}（直播间{}）",
+            self.opponent.uname, self.opponent_roomid
+        )
+    }
+}
+
+impl Display for PkBattleStartEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "PK开始 直播间{} vs 直播间{}",
+            self.init.roomid, self.matched.roomid
+        )
+    }
+}
+
+impl Display for PkBattleProcessEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "PK中 直播间{} {} : {} 直播间{}",
+            self.init.roomid, self.init.votes, self.matched.votes, self.matched.roomid
+        )
+    }
+}
+
+impl Display for PkBattleEndEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "PK结束 直播间{} {} : {} 直播间{}，",
+            self.init.roomid, self.init.votes, self.matched.votes, self.matched.roomid
+        )?;
+        match self.winner {
+            Some(winner) => write!(f, "直播间{}获胜", winner),
+            None => write!(f, "平局"),
+        }
+    }
+}
+
+impl Display for CustomEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{} {}", self.cmd, self.data)
+    }
+}
+
+impl Display for DanmakuThrottledEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}毫秒内有{}条弹幕被限流", self.window, self.dropped)
+    }
+}
+
+impl Display for FansUpdateEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "粉丝数 {}，粉丝团 {}人", self.fans, self.fans_club)
+    }
+}
+
+impl Display for DanmakuAggregationEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{} x{}", self.message, self.count)
+    }
+}
+
+impl Display for OnlineRankUpdateEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.write_str("高能榜:")?;
+        for item in self.list.iter().take(3) {
+            write!(f, " {}.{}({})", item.rank, item.user.uname, item.score)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for OnlineRankTop3Event {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.write_str(&self.messages.join("，"))
+    }
+}
+
+impl Display for StatsEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "最近{}秒: 弹幕{}条({:.1}条/分钟, {}人), 礼物{:.2}元, 醒目留言{}条({}元)",
+            self.window,
+            self.danmaku_count,
+            self.danmaku_per_minute,
+            self.unique_chatters,
+            gold_to_cny(self.gift_gold),
+            self.super_chat_count,
+            self.super_chat_price
+        )
+    }
+}
+
+impl Display for HostSwitchEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "切换服务器 {} -> {}: {}",
+            self.from, self.to, self.reason
+        )
+    }
+}
@@ -0,0 +1,241 @@
+//! 事件过滤
+//!
+//! 在连接上安装过滤器后，被过滤的事件不会出现在事件流中
+//!```no_run,ignore
+//!use bilive_danmaku::filter::EventFilter;
+//!let filter = EventFilter::new()
+//!    .cmds(["DanmakuEvent", "SuperChatEvent"])
+//!    .keyword("广告")
+//!    .block_uid(10086)
+//!    .min_medal_level(5);
+//!connector.config.filter = Some(filter);
+//!```
+use std::{collections::HashSet, time::Duration};
+
+use crate::event::{DanmakuThrottledEvent, Event, EventData};
+
+/// # 说明
+/// - `cmds` 只保留这些类型的事件，为`None`时不限制，类型名见[`EventData::cmd`]
+/// - `blocked_uids` 过滤这些用户产生的所有事件
+/// - `keywords`，`patterns` 过滤包含关键字或匹配正则的弹幕
+/// - `min_guard_level` 只保留大航海等级不低于此的用户的弹幕，1，2，3分别为总督，提督，舰长
+/// - `min_medal_level` 只保留粉丝牌等级不低于此的用户的弹幕
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    pub cmds: Option<HashSet<String>>,
+    pub blocked_uids: HashSet<u64>,
+    pub keywords: Vec<String>,
+    #[cfg(feature = "regex")]
+    pub patterns: Vec<regex::Regex>,
+    pub min_guard_level: Option<u64>,
+    pub min_medal_level: Option<u64>,
+}
+
+impl EventFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cmds<S: Into<String>>(mut self, cmds: impl IntoIterator<Item = S>) -> Self {
+        self.cmds
+            .get_or_insert_with(HashSet::new)
+            .extend(cmds.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn block_uid(mut self, uid: u64) -> Self {
+        self.blocked_uids.insert(uid);
+        self
+    }
+
+    pub fn keyword(mut self, keyword: impl Into<String>) -> Self {
+        self.keywords.push(keyword.into());
+        self
+    }
+
+    #[cfg(feature = "regex")]
+    pub fn pattern(mut self, pattern: regex::Regex) -> Self {
+        self.patterns.push(pattern);
+        self
+    }
+
+    pub fn min_guard_level(mut self, level: u64) -> Self {
+        self.min_guard_level = Some(level);
+        self
+    }
+
+    pub fn min_medal_level(mut self, level: u64) -> Self {
+        self.min_medal_level = Some(level);
+        self
+    }
+
+    /// 事件是否通过过滤
+    pub fn accept(&self, event: &Event) -> bool {
+        if let Some(cmds) = &self.cmds {
+            if !cmds.contains(event.data.cmd()) {
+                return false;
+            }
+        }
+        if let Some(user) = event.data.user() {
+            if self.blocked_uids.contains(&user.uid) {
+                return false;
+            }
+        }
+        match &event.data {
+            EventData::DanmakuEvent(danmaku) => {
+                let text = danmaku.message.to_string();
+                if self.keywords.iter().any(|k| text.contains(k.as_str())) {
+                    return false;
+                }
+                #[cfg(feature = "regex")]
+                if self.patterns.iter().any(|p| p.is_match(&text)) {
+                    return false;
+                }
+                if let Some(min) = self.min_guard_level {
+                    if danmaku.guard_level == 0 || danmaku.guard_level > min {
+                        return false;
+                    }
+                }
+                if let Some(min) = self.min_medal_level {
+                    let medal = danmaku.fans_medal.as_ref();
+                    let medal_level = medal.map(|m| m.medal_level).unwrap_or_default();
+                    if medal_level < min {
+                        return false;
+                    }
+                }
+                true
+            }
+            _ => true,
+        }
+    }
+}
+
+/// 按原始消息的`cmd`过滤，在解析json之前进行，被丢弃的消息不会被解析
+///```no_run,ignore
+///connector.config.cmd_filter = CmdFilter::new().ignore(["STOP_LIVE_ROOM_LIST", "WIDGET_BANNER"]);
+///// 只关心弹幕和礼物
+///connector.config.cmd_filter = CmdFilter::new().only(["DANMU_MSG", "SEND_GIFT"]);
+///```
+///
+/// # 说明
+/// - `ignored` 丢弃这些cmd的消息，cmd为b站原始的名称，比如`DANMU_MSG`
+/// - `only` 只解析这些cmd的消息，为`None`时不限制，繁忙的直播间中可以省下大部分解析开销
+#[derive(Debug, Clone, Default)]
+pub struct CmdFilter {
+    pub ignored: HashSet<String>,
+    pub only: Option<HashSet<String>>,
+}
+
+impl CmdFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ignore<S: Into<String>>(mut self, cmds: impl IntoIterator<Item = S>) -> Self {
+        self.ignored.extend(cmds.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn only<S: Into<String>>(mut self, cmds: impl IntoIterator<Item = S>) -> Self {
+        self.only
+            .get_or_insert_with(HashSet::new)
+            .extend(cmds.into_iter().map(Into::into));
+        self
+    }
+
+    /// 这个cmd的消息是否需要解析
+    pub fn accept(&self, cmd: &str) -> bool {
+        if let Some(only) = &self.only {
+            if !only.contains(cmd) {
+                return false;
+            }
+        }
+        !self.ignored.contains(cmd)
+    }
+}
+
+/// 弹幕限流，每个窗口内最多放行`max_danmaku`条弹幕，其余的丢弃，礼物、醒目留言等其他事件不受影响
+///
+/// 按事件的`timestamp`划分窗口，窗口切换由收到的事件驱动
+///
+/// # 说明
+/// - `max_danmaku` 每个窗口最多放行的弹幕数
+/// - `window` 窗口时长，默认为1秒
+/// - `summary` 为`true`时，窗口内有弹幕被丢弃的话，在下一个窗口的第一个事件之前产生[`DanmakuThrottledEvent`]，默认不产生
+///```no_run,ignore
+///use bilive_danmaku::filter::DanmakuSampler;
+///// 每秒最多20条弹幕
+///connector.config.sampler = Some(DanmakuSampler::new(20).with_summary(true));
+///```
+#[derive(Debug, Clone)]
+pub struct DanmakuSampler {
+    pub max_danmaku: u64,
+    pub window: Duration,
+    pub summary: bool,
+    window_start: Option<u64>,
+    passed: u64,
+    dropped: u64,
+}
+
+impl DanmakuSampler {
+    pub fn new(max_danmaku: u64) -> Self {
+        Self {
+            max_danmaku,
+            window: Duration::from_secs(1),
+            summary: false,
+            window_start: None,
+            passed: 0,
+            dropped: 0,
+        }
+    }
+
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn with_summary(mut self, summary: bool) -> Self {
+        self.summary = summary;
+        self
+    }
+
+    /// 处理一个事件，依次返回需要交付的事件：窗口切换时可能有的汇总事件，和没有被丢弃的事件本身
+    pub fn sample(&mut self, event: Event) -> impl Iterator<Item = Event> {
+        let summary = self.roll(event.timestamp);
+        let keep = match event.data {
+            EventData::DanmakuEvent(_) if self.passed >= self.max_danmaku => {
+                self.dropped += 1;
+                false
+            }
+            EventData::DanmakuEvent(_) => {
+                self.passed += 1;
+                true
+            }
+            _ => true,
+        };
+        summary.into_iter().chain(keep.then_some(event))
+    }
+
+    /// 进入新的窗口时清空计数，返回上一个窗口的汇总
+    fn roll(&mut self, timestamp: u64) -> Option<Event> {
+        let window = self.window.as_millis() as u64;
+        let start = *self.window_start.get_or_insert(timestamp);
+        if timestamp.saturating_sub(start) < window {
+            return None;
+        }
+        self.window_start = Some(timestamp);
+        self.passed = 0;
+        let dropped = std::mem::take(&mut self.dropped);
+        if !self.summary || dropped == 0 {
+            return None;
+        }
+        Some(Event {
+            timestamp,
+            ..EventData::from(DanmakuThrottledEvent {
+                dropped,
+                window: self.window.as_millis() as u64,
+            })
+            .into()
+        })
+    }
+}
@@ -0,0 +1,82 @@
+//! 以回调的方式处理事件
+//!
+//! 实现[`EventHandler`]中关心的方法，交给[`drive`]驱动，不需要自己`match`事件
+//!```no_run,ignore
+//!use bilive_danmaku::{event::DanmakuEvent, handler::{drive, EventHandler}};
+//!struct Bot;
+//!impl EventHandler for Bot {
+//!    fn on_danmaku(&mut self, danmaku: DanmakuEvent, _timestamp: u64) {
+//!        println!("{}: {}", danmaku.user.uname, danmaku.message);
+//!    }
+//!}
+//!let stream = connector.connect().await?;
+//!drive(stream, &mut Bot).await?;
+//!```
+use futures::{Stream, StreamExt};
+
+use crate::event::*;
+
+/// 所有方法都有空的默认实现，`timestamp`为收到事件时的毫秒时间戳
+pub trait EventHandler {
+    fn on_danmaku(&mut self, danmaku: DanmakuEvent, timestamp: u64) {
+        let _ = (danmaku, timestamp);
+    }
+
+    fn on_enter_room(&mut self, enter: EnterRoomEvent, timestamp: u64) {
+        let _ = (enter, timestamp);
+    }
+
+    fn on_gift(&mut self, gift: GiftEvent, timestamp: u64) {
+        let _ = (gift, timestamp);
+    }
+
+    fn on_super_chat(&mut self, super_chat: SuperChatEvent, timestamp: u64) {
+        let _ = (super_chat, timestamp);
+    }
+
+    fn on_guard_buy(&mut self, guard_buy: GuardBuyEvent, timestamp: u64) {
+        let _ = (guard_buy, timestamp);
+    }
+
+    fn on_watched_update(&mut self, watched: WatchedUpdateEvent, timestamp: u64) {
+        let _ = (watched, timestamp);
+    }
+
+    fn on_popularity_update(&mut self, popularity: PopularityUpdateEvent, timestamp: u64) {
+        let _ = (popularity, timestamp);
+    }
+
+    /// 没有单独回调的事件
+    fn on_unknown(&mut self, data: EventData, timestamp: u64) {
+        let _ = (data, timestamp);
+    }
+
+    /// 把事件分发到对应的回调
+    fn handle(&mut self, event: Event) {
+        let Event {
+            data, timestamp, ..
+        } = event;
+        match data {
+            EventData::DanmakuEvent(e) => self.on_danmaku(e, timestamp),
+            EventData::EnterRoomEvent(e) => self.on_enter_room(e, timestamp),
+            EventData::GiftEvent(e) => self.on_gift(e, timestamp),
+            EventData::SuperChatEvent(e) => self.on_super_chat(e, timestamp),
+            EventData::GuardBuyEvent(e) => self.on_guard_buy(e, timestamp),
+            EventData::WatchedUpdateEvent(e) => self.on_watched_update(e, timestamp),
+            EventData::PopularityUpdateEvent(e) => self.on_popularity_update(e, timestamp),
+            data => self.on_unknown(data, timestamp),
+        }
+    }
+}
+
+/// 把事件流中的事件依次交给`handler`，直到事件流结束或出错
+pub async fn drive<S, E, H>(mut stream: S, handler: &mut H) -> Result<(), E>
+where
+    S: Stream<Item = Result<Event, E>> + Unpin,
+    H: EventHandler + ?Sized,
+{
+    while let Some(event) = stream.next().await {
+        handler.handle(event?);
+    }
+    Ok(())
+}
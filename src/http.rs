@@ -0,0 +1,243 @@
+//! 访问b站http接口的客户端
+//!
+//! 同时初始化大量房间时，接口会返回412，可以在多个[`Connector`](crate::Connector)之间共享同一个带有限流的[`HttpClient`]
+//!```no_run,ignore
+//!use bilive_danmaku::{http::{HttpClient, RateLimiter}, Connector};
+//!let client = HttpClient::new().with_rate_limit(RateLimiter::new(2.0, 4));
+//!for roomid in roomids {
+//!    let connector = Connector::init_with(roomid, &client).await?;
+//!}
+//!```
+use std::{
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
+
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{connection::timeout, event::now_millis, retry::RetryPolicy, InitError};
+
+/// 桌面版Chrome的User-Agent，部分接口会拒绝没有UA的请求
+pub const BROWSER_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36";
+
+/// 浏览器访问直播间时携带的`User-Agent`、`Referer`和`Origin`，http请求和websocket握手都可以使用
+///```no_run,ignore
+///let client = HttpClient::new().with_headers(browser_headers());
+///connector.config.headers = browser_headers();
+///```
+pub fn browser_headers() -> Vec<(String, String)> {
+    [
+        ("User-Agent", BROWSER_USER_AGENT),
+        ("Referer", "https://live.bilibili.com/"),
+        ("Origin", "https://live.bilibili.com"),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_owned(), value.to_owned()))
+    .collect()
+}
+
+/// 令牌桶限流器，克隆后共享同一个桶
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    per_second: f64,
+    burst: f64,
+    tokens: f64,
+    updated_at: u64,
+}
+
+impl RateLimiter {
+    /// - `per_second` 每秒允许的请求数，最小为0.001
+    /// - `burst` 最多允许连续发出的请求数，最小为1
+    pub fn new(per_second: f64, burst: u32) -> Self {
+        let burst = burst.max(1) as f64;
+        Self {
+            bucket: Arc::new(Mutex::new(Bucket {
+                per_second: per_second.max(0.001),
+                burst,
+                tokens: burst,
+                updated_at: now_millis(),
+            })),
+        }
+    }
+
+    /// 等待直到获得一个令牌
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().expect("rate limiter poisoned");
+                let now = now_millis();
+                let elapsed = now.saturating_sub(bucket.updated_at) as f64 / 1000.0;
+                bucket.tokens = (bucket.tokens + elapsed * bucket.per_second).min(bucket.burst);
+                bucket.updated_at = now;
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - bucket.tokens) / bucket.per_second)
+            };
+            crate::connection::sleep(wait).await;
+        }
+    }
+}
+
+/// b站接口通用的返回格式
+#[derive(Debug, Deserialize)]
+struct ApiResponse<T> {
+    code: i64,
+    #[serde(default)]
+    message: String,
+    data: Option<T>,
+}
+
+/// [`HttpClient`]默认的超时时间
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    pub(crate) client: reqwest::Client,
+    rate_limiter: Option<RateLimiter>,
+    retry: Option<RetryPolicy>,
+    timeout: Option<Duration>,
+    headers: Vec<(String, String)>,
+    base_url: Option<String>,
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self {
+            client: reqwest::Client::default(),
+            rate_limiter: None,
+            retry: None,
+            timeout: Some(DEFAULT_TIMEOUT),
+            headers: vec![],
+            base_url: None,
+        }
+    }
+}
+
+impl HttpClient {
+    /// 不限流的客户端，超时时间为10秒，见[`with_timeout`](Self::with_timeout)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// [`Connector::init`](crate::Connector::init)使用的全局客户端，限制为每秒5个请求，最多连续10个
+    pub fn shared() -> &'static Self {
+        static SHARED: OnceLock<HttpClient> = OnceLock::new();
+        SHARED.get_or_init(|| Self::new().with_rate_limit(RateLimiter::new(5.0, 10)))
+    }
+
+    pub fn with_rate_limit(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// 每个请求都会带上的请求头，不合法的请求头在发送时产生[`InitError::HttpError`]
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn with_headers(mut self, headers: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.headers.extend(headers);
+        self
+    }
+
+    /// 等待响应头和读取响应体分别不能超过`timeout`，超时产生[`InitError::Timeout`]，默认为10秒
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// GET请求失败时按`retry`重试，其他请求不会重试
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// 把所有请求的协议、域名和端口替换为`base_url`的，路径和查询参数不变，
+    /// 用于反向代理或者测试，例如指向`test_util::MockServer::http_url`
+    ///
+    /// 不合法的`base_url`在发送时产生[`InitError::ParseError`]
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// 请求接口，检查返回的`code`，并取出`data`字段
+    pub(crate) async fn get_data<T: DeserializeOwned>(&self, url: String) -> Result<T, InitError> {
+        match &self.retry {
+            Some(retry) => {
+                retry
+                    .retry(|| self.send_data(self.client.get(url.as_str())))
+                    .await
+            }
+            None => self.send_data(self.client.get(url)).await,
+        }
+    }
+
+    /// 发送自行构造的请求，返回值的处理与[`get_data`](Self::get_data)相同
+    pub(crate) async fn send_data<T: DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T, InitError> {
+        let resp = self.send(request).await?;
+        let body = timeout(self.timeout, resp.bytes())
+            .await
+            .ok_or(InitError::Timeout)??;
+        parse_data(&body)
+    }
+
+    /// 限流后发送请求，只检查http状态码，需要读取响应头时使用
+    pub(crate) async fn send(
+        &self,
+        mut request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, InitError> {
+        for (name, value) in &self.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        let mut request = request.build()?;
+        if let Some(base_url) = &self.base_url {
+            rebase(request.url_mut(), base_url)?;
+        }
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire().await;
+        }
+        let resp = timeout(self.timeout, self.client.execute(request))
+            .await
+            .ok_or(InitError::Timeout)??;
+        let status = resp.status();
+        if !status.is_success() {
+            return Err(InitError::HttpStatus(status));
+        }
+        Ok(resp)
+    }
+}
+
+/// 把`url`的协议、域名和端口替换为`base_url`的
+fn rebase(url: &mut reqwest::Url, base_url: &str) -> Result<(), InitError> {
+    let invalid = || InitError::ParseError(format!("invalid base url: {base_url}"));
+    let base = reqwest::Url::parse(base_url).map_err(|_| invalid())?;
+    url.set_scheme(base.scheme()).map_err(|_| invalid())?;
+    url.set_host(base.host_str()).map_err(|_| invalid())?;
+    url.set_port(base.port()).map_err(|_| invalid())?;
+    Ok(())
+}
+
+/// 检查返回的`code`，并取出`data`字段
+pub(crate) fn parse_data<T: DeserializeOwned>(body: &[u8]) -> Result<T, InitError> {
+    let ApiResponse {
+        code,
+        message,
+        data,
+    } = serde_json::from_slice::<ApiResponse<T>>(body)?;
+    if code != 0 {
+        return Err(InitError::ApiError { code, message });
+    }
+    data.ok_or_else(|| InitError::ParseError("missing field `data`".to_string()))
+}
@@ -2,23 +2,26 @@
 //!
 //!
 //!```no_run,ignore
-//!use bilive_danmaku::{RoomService}
+//!use bilive_danmaku::{room::Room, Connector};
 //!async fn service() {
-//!    let service = RoomService::new(477317922).init().await.unwrap();
-//!    let service = service.connect().await.unwrap();
-//!    // 这里会获得一个 broadcast::Reciever<Event>
-//!    let mut events_rx = service.subscribe();
-//!    while let Some(evt) = events_rx.recv().await {
+//!    let room = Room::spawn(Connector::init(477317922).await.unwrap(), 1024);
+//!    // 这里会获得一个 broadcast::Receiver<Event>，断线重连后仍然有效
+//!    let mut events_rx = room.subscribe();
+//!    while let Ok(evt) = events_rx.recv().await {
 //!        // 处理事件
 //!        todo!()
 //!    }
-//!    let service = service.close();
+//!    room.close().await;
 //!}
 //!```
 
 // #![allow(dead_code)]
 #![deny(clippy::unwrap_used, clippy::print_stdout, clippy::panic)]
-#![feature(split_array)]
+#[cfg(all(
+    feature = "connect",
+    not(any(feature = "rt_tokio", feature = "rt_wasm"))
+))]
+compile_error!("`connect`需要配合`rt_tokio`（`native`）或`rt_wasm`（`wasm`）使用");
 #[cfg(feature = "connect")]
 pub mod connection;
 #[cfg(feature = "connect")]
@@ -28,19 +31,68 @@ pub use crate::connector::*;
 #[cfg(feature = "connect")]
 pub use connection::Connection;
 #[cfg(feature = "connect")]
+pub mod api;
+#[cfg(feature = "protocol")]
 pub(crate) mod cmd;
+#[cfg(feature = "protocol")]
+pub mod corpus;
+#[cfg(feature = "connect")]
+pub mod credential;
+#[cfg(feature = "protocol")]
+pub mod decoder;
+#[cfg(feature = "connect")]
+pub mod dedup;
+#[cfg(feature = "connect")]
+pub mod http;
+#[cfg(feature = "connect")]
+pub mod login;
+#[cfg(feature = "open_live")]
+pub mod open_live;
+#[cfg(feature = "connect")]
+pub mod retry;
+#[cfg(feature = "rt_tokio")]
+pub mod room;
+#[cfg(feature = "connect")]
+pub mod sender;
+#[cfg(any(feature = "test-util", all(test, feature = "rt_tokio")))]
+pub mod test_util;
 
 #[cfg(feature = "event")]
 pub mod event;
 #[cfg(feature = "event")]
+pub mod filter;
+#[cfg(feature = "event")]
+pub mod handler;
+#[cfg(feature = "event")]
+pub mod middleware;
+#[cfg(feature = "event")]
 pub mod model;
+#[cfg(feature = "protocol")]
+pub mod parser;
+#[cfg(feature = "record")]
+pub mod record;
+#[cfg(feature = "sink")]
+pub mod sink;
+#[cfg(feature = "event")]
+pub mod stats;
+#[cfg(feature = "event")]
+pub mod text;
 
 #[cfg(test)]
 mod tests;
 
 #[cfg(feature = "connect")]
 mod error;
-#[cfg(feature = "connect")]
+#[cfg(feature = "protocol")]
 mod packet;
+#[cfg(feature = "protocol")]
+pub use cmd::{
+    AnchorLotAwardUser, BlindGiftInfo, Cmd, CmdDeserError, NoEventError, OnlineRankTop3ListItem,
+    OnlineRankV2ListItem, OpenPlatformMedal, OpenPlatformUser, PkRoomInfo, RedPocketAwardInfo,
+};
 #[cfg(feature = "connect")]
 pub use error::Error;
+#[cfg(feature = "protocol")]
+pub use packet::{
+    Auth, Diagnostic, Operation, PacketParseError, RawPacket, RawPacketHead, RawPackets,
+};
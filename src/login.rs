@@ -0,0 +1,139 @@
+//! 扫码登录
+//!
+//! 用b站手机客户端扫描二维码后得到[`Credential`]
+//!```no_run,ignore
+//!use bilive_danmaku::http::HttpClient;
+//!let client = HttpClient::new();
+//!let qr = client.qr_login().await?;
+//!// 把qr.url显示为二维码
+//!let credential = qr.wait(&client, Duration::from_secs(2)).await?;
+//!```
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::{credential::Credential, http::HttpClient, InitError};
+
+/// 二维码已失效
+pub const CODE_QR_EXPIRED: i64 = 86038;
+const CODE_QR_SCANNED: i64 = 86090;
+const CODE_QR_WAITING: i64 = 86101;
+
+/// 一次扫码登录
+///
+/// # 说明
+/// - `url` 二维码的内容
+/// - `qrcode_key` 查询扫码状态时使用，有效期180秒
+///
+/// api url:
+/// https://passport.bilibili.com/x/passport-login/web/qrcode/generate
+#[derive(Debug, Clone, Deserialize)]
+pub struct QrLogin {
+    pub url: String,
+    pub qrcode_key: String,
+}
+
+/// 扫码状态
+#[derive(Debug, Clone)]
+pub enum QrLoginStatus {
+    /// 还没有扫码
+    Waiting,
+    /// 已扫码，等待在手机上确认
+    Scanned,
+    /// 二维码已失效，需要重新生成
+    Expired,
+    Confirmed(Credential),
+}
+
+///
+/// api url:
+/// https://passport.bilibili.com/x/passport-login/web/qrcode/poll?qrcode_key=xxx
+#[derive(Debug, Deserialize)]
+struct QrPollData {
+    url: String,
+    refresh_token: String,
+    code: i64,
+    message: String,
+}
+
+impl HttpClient {
+    /// 生成登录二维码
+    pub async fn qr_login(&self) -> Result<QrLogin, InitError> {
+        self.get_data(
+            "https://passport.bilibili.com/x/passport-login/web/qrcode/generate".to_owned(),
+        )
+        .await
+    }
+
+    /// 查询一次扫码状态
+    pub async fn qr_login_poll(&self, qrcode_key: &str) -> Result<QrLoginStatus, InitError> {
+        let url = format!(
+            "https://passport.bilibili.com/x/passport-login/web/qrcode/poll?qrcode_key={}",
+            qrcode_key
+        );
+        let QrPollData {
+            url,
+            refresh_token,
+            code,
+            message,
+        } = self.get_data(url).await?;
+        match code {
+            0 => {
+                let mut credential = credential_from_url(&url)?;
+                credential.refresh_token = refresh_token;
+                Ok(QrLoginStatus::Confirmed(credential))
+            }
+            CODE_QR_WAITING => Ok(QrLoginStatus::Waiting),
+            CODE_QR_SCANNED => Ok(QrLoginStatus::Scanned),
+            CODE_QR_EXPIRED => Ok(QrLoginStatus::Expired),
+            code => Err(InitError::ApiError { code, message }),
+        }
+    }
+}
+
+impl QrLogin {
+    /// 每隔`interval`查询一次，直到确认登录，二维码失效时返回[`CODE_QR_EXPIRED`]的[`InitError::ApiError`]
+    pub async fn wait(
+        &self,
+        client: &HttpClient,
+        interval: Duration,
+    ) -> Result<Credential, InitError> {
+        loop {
+            match client.qr_login_poll(&self.qrcode_key).await? {
+                QrLoginStatus::Confirmed(credential) => return Ok(credential),
+                QrLoginStatus::Expired => {
+                    return Err(InitError::ApiError {
+                        code: CODE_QR_EXPIRED,
+                        message: "二维码已失效".to_owned(),
+                    })
+                }
+                QrLoginStatus::Waiting | QrLoginStatus::Scanned => {
+                    crate::connection::sleep(interval).await
+                }
+            }
+        }
+    }
+}
+
+/// 登录成功后返回的跨域地址，查询参数中带有cookie，值保持url编码，与浏览器中的cookie相同
+pub(crate) fn credential_from_url(url: &str) -> Result<Credential, InitError> {
+    let query = url
+        .split_once('?')
+        .map(|(_, query)| query)
+        .unwrap_or_default();
+    let mut credential = Credential::default();
+    for (key, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+        match key {
+            "SESSDATA" => credential.sessdata = value.to_owned(),
+            "bili_jct" => credential.bili_jct = value.to_owned(),
+            "DedeUserID" => credential.uid = value.parse().unwrap_or_default(),
+            _ => {}
+        }
+    }
+    if credential.sessdata.is_empty() || credential.bili_jct.is_empty() {
+        return Err(InitError::ParseError(format!(
+            "登录地址中没有SESSDATA或bili_jct: {url}"
+        )));
+    }
+    Ok(credential)
+}
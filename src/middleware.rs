@@ -0,0 +1,63 @@
+//! 事件中间件
+//!
+//! 中间件按注册顺序依次处理通过过滤器的事件，可以修改事件，返回`None`时丢弃事件
+//!```no_run,ignore
+//!use bilive_danmaku::{event::EventData, middleware::Middlewares, model::DanmakuMessage};
+//!connector.config.middlewares = Middlewares::new().then(|mut event| {
+//!    if let EventData::DanmakuEvent(danmaku) = &mut event.data {
+//!        if let DanmakuMessage::Plain { message } = &mut danmaku.message {
+//!            *message = message.replace("坏词", "**");
+//!        }
+//!    }
+//!    Some(event)
+//!});
+//!```
+use std::{fmt::Debug, sync::Arc};
+
+use crate::event::Event;
+
+pub type Middleware = Arc<dyn Fn(Event) -> Option<Event> + Send + Sync>;
+
+/// 中间件链，克隆后共享同样的中间件
+#[derive(Clone, Default)]
+pub struct Middlewares {
+    chain: Vec<Middleware>,
+}
+
+impl Middlewares {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 在链的末尾追加一个中间件
+    pub fn then<F>(mut self, middleware: F) -> Self
+    where
+        F: Fn(Event) -> Option<Event> + Send + Sync + 'static,
+    {
+        self.chain.push(Arc::new(middleware));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chain.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.chain.len()
+    }
+
+    /// 依次应用所有中间件，任意一个返回`None`时停止
+    pub fn apply(&self, event: Event) -> Option<Event> {
+        self.chain
+            .iter()
+            .try_fold(event, |event, middleware| middleware(event))
+    }
+}
+
+impl Debug for Middlewares {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Middlewares")
+            .field("len", &self.chain.len())
+            .finish()
+    }
+}
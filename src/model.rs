@@ -9,10 +9,27 @@ pub struct Emoticon {
     pub width: u64,
     pub url: String,
 }
+/// 语音弹幕
+///
+/// # 说明
+/// - `url` 音频文件地址
+/// - `duration` 时长，单位为秒
+/// - `text` 语音转写的文本
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Voice {
+    pub url: String,
+    pub file_format: String,
+    pub duration: u64,
+    pub text: String,
+}
+
 ///
 /// # 说明
 /// - `guard_level`字段，1，2，3分别为总督，提督，舰长；0为无。
 /// - `anchor_roomid` 大航海房间id
+/// - `anchor_uname` 粉丝牌对应主播的用户名，部分消息中没有
+/// - `target_id` 粉丝牌对应主播的uid
+/// - `is_lighted` 粉丝牌是否点亮
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct FansMedal {
     pub anchor_roomid: u64,
@@ -20,6 +37,32 @@ pub struct FansMedal {
     pub guard_level: u64,
     pub medal_level: u64,
     pub medal_name: String,
+    #[serde(default, deserialize_with = "deser_non_empty")]
+    pub anchor_uname: Option<String>,
+    #[serde(default)]
+    pub target_id: u64,
+    #[serde(default, deserialize_with = "deser_bool_or_int")]
+    pub is_lighted: bool,
+}
+
+/// b站的布尔值常用0和1表示
+fn deser_bool_or_int<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum BoolOrInt {
+        Bool(bool),
+        Int(i64),
+    }
+    Ok(match BoolOrInt::deserialize(deserializer)? {
+        BoolOrInt::Bool(b) => b,
+        BoolOrInt::Int(i) => i != 0,
+    })
+}
+
+fn deser_non_empty<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.filter(|s| !s.is_empty()))
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -29,10 +72,55 @@ pub struct User {
     pub face: Option<String>,
 }
 
+/// 醒目留言消息中的用户信息
+#[cfg(feature = "protocol")]
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub(crate) struct SuperChatUser {
-    pub(crate) uname: String,
-    pub(crate) face: String,
+pub struct SuperChatUser {
+    pub uname: String,
+    pub face: String,
+}
+
+/// 直播状态，对应接口中的0，1，2
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(from = "u8", into = "u8")]
+pub enum LiveStatus {
+    /// 未开播
+    #[default]
+    Preparing,
+    /// 直播中
+    Live,
+    /// 轮播中
+    Round,
+}
+
+impl From<u8> for LiveStatus {
+    fn from(val: u8) -> Self {
+        match val {
+            1 => LiveStatus::Live,
+            2 => LiveStatus::Round,
+            _ => LiveStatus::Preparing,
+        }
+    }
+}
+
+impl From<LiveStatus> for u8 {
+    fn from(val: LiveStatus) -> Self {
+        match val {
+            LiveStatus::Preparing => 0,
+            LiveStatus::Live => 1,
+            LiveStatus::Round => 2,
+        }
+    }
+}
+
+impl Display for LiveStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LiveStatus::Preparing => f.write_str("未开播"),
+            LiveStatus::Live => f.write_str("直播中"),
+            LiveStatus::Round => f.write_str("轮播中"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Copy, PartialEq, Eq)]
@@ -60,6 +148,65 @@ pub struct GiftType {
     pub gift_id: u64,
 }
 
+/// 高能榜上的用户
+///
+/// # 说明
+/// - `rank` 从1开始的名次
+/// - `score` 贡献值
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OnlineRankUser {
+    pub user: User,
+    pub rank: u64,
+    pub score: u64,
+    pub guard_level: u64,
+}
+
+/// 禁言用户的操作者
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum BlockOperator {
+    /// 房管
+    Admin,
+    /// 主播
+    Anchor,
+    /// 还不认识的操作者类型
+    Unknown(u64),
+}
+
+/// PK中一方的直播间
+///
+/// # 说明
+/// - `votes` 这一方的PK值
+/// - `best_uname` 这一方贡献最多的用户，没有时为空
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PkRoom {
+    pub roomid: u64,
+    pub votes: u64,
+    pub best_uname: String,
+}
+
+/// 红包中的一种礼物
+///
+/// # 说明
+/// - `num` 这种礼物的个数
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RedPocketAward {
+    pub gift_id: u64,
+    pub gift_name: String,
+    pub num: u64,
+}
+
+/// 红包的中奖者
+///
+/// # 说明
+/// - `gift_price` 抽中礼物的价格，单位为金瓜子
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RedPocketWinner {
+    pub user: User,
+    pub gift_id: u64,
+    pub gift_name: String,
+    pub gift_price: u64,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(tag = "tag", content = "data")]
 pub enum DanmakuMessage {
@@ -70,6 +217,9 @@ pub enum DanmakuMessage {
         emoticon: Emoticon,
         alt_message: String,
     },
+    Voice {
+        voice: Voice,
+    },
 }
 
 impl Display for FansMedal {
@@ -86,6 +236,19 @@ impl Display for DanmakuMessage {
                 emoticon: _,
                 alt_message,
             } => f.write_fmt(format_args!("[表情:{}]", alt_message)),
+            DanmakuMessage::Voice { voice } => {
+                f.write_fmt(format_args!("[语音{}秒:{}]", voice.duration, voice.text))
+            }
+        }
+    }
+}
+
+impl Gift {
+    /// 礼物的金瓜子价值，银瓜子礼物为0
+    pub fn gold_value(&self) -> u64 {
+        match self.coin_type {
+            CoinType::Gold => self.price * self.num,
+            CoinType::Silver => 0,
         }
     }
 }
@@ -97,7 +260,7 @@ impl Display for Gift {
             self.action,
             self.gift_name,
             self.num,
-            ((self.price * self.num) as f32) / 1000.0
+            crate::stats::gold_to_cny(self.price * self.num)
         ))
     }
 }
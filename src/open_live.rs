@@ -0,0 +1,235 @@
+//! b站直播开放平台
+//!
+//! 开放平台是官方授权的弹幕接入方式，需要在开放平台申请`app_id`和`access_key`，主播提供身份码后开启一场"游戏"，
+//! 之后的连接与普通连接相同，产生同样的[`Event`](crate::event::Event)
+//!```no_run,ignore
+//!use bilive_danmaku::open_live::OpenLive;
+//!let open_live = OpenLive::new(app_id, access_key_id, access_key_secret);
+//!let game = open_live.start(code).await?;
+//!let mut stream = game.connect(&Default::default()).await?;
+//!// 每20秒需要发送一次心跳，否则游戏会被关闭
+//!tokio::spawn(async move { open_live.keep_alive(&game.game_id).await });
+//!```
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use hmac::{Hmac, Mac};
+use md5::{Digest, Md5};
+use serde::{de::IgnoredAny, Deserialize};
+use sha2::Sha256;
+
+use crate::{
+    connection::{ConnectConfig, Connection},
+    event::now_millis,
+    http::HttpClient,
+    packet::Auth,
+    ConnectError, InitError,
+};
+
+const API_HOST: &str = "https://live-open.biliapi.com";
+
+/// 开放平台的客户端，保存签名用的密钥
+#[derive(Debug, Clone)]
+pub struct OpenLive {
+    pub app_id: u64,
+    access_key_id: String,
+    access_key_secret: String,
+    client: HttpClient,
+}
+
+/// 一场游戏
+///
+/// # 说明
+/// - `game_id` 心跳和关闭游戏时使用，互动玩法之外的应用为空字符串
+/// - `auth_body` 连接弹幕服务器时使用的鉴权包
+/// - `wss_link` 弹幕服务器地址
+#[derive(Debug, Clone)]
+pub struct Game {
+    pub game_id: String,
+    pub anchor: OpenLiveAnchor,
+    pub auth_body: String,
+    pub wss_link: Vec<String>,
+}
+
+/// 开启游戏的主播
+///
+/// # 说明
+/// - `uid` 开放平台逐渐不再提供uid，可能为0，可以使用`open_id`区分用户
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpenLiveAnchor {
+    pub room_id: u64,
+    pub uname: String,
+    pub uface: String,
+    #[serde(default)]
+    pub uid: u64,
+    #[serde(default)]
+    pub open_id: String,
+}
+
+///
+/// api url:
+/// https://live-open.biliapi.com/v2/app/start
+#[derive(Debug, Deserialize)]
+struct StartData {
+    game_info: GameInfo,
+    websocket_info: WebsocketInfo,
+    anchor_info: OpenLiveAnchor,
+}
+
+#[derive(Debug, Deserialize)]
+struct GameInfo {
+    game_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct WebsocketInfo {
+    auth_body: String,
+    wss_link: Vec<String>,
+}
+
+impl OpenLive {
+    /// 默认使用[`HttpClient::shared`]，和其他接口共享限流
+    pub fn new(
+        app_id: u64,
+        access_key_id: impl Into<String>,
+        access_key_secret: impl Into<String>,
+    ) -> Self {
+        Self {
+            app_id,
+            access_key_id: access_key_id.into(),
+            access_key_secret: access_key_secret.into(),
+            client: HttpClient::shared().clone(),
+        }
+    }
+
+    pub fn with_client(mut self, client: HttpClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// 使用主播的身份码开启游戏
+    #[tracing::instrument(level = "debug", skip(self, code), fields(app_id = self.app_id))]
+    pub async fn start(&self, code: &str) -> Result<Game, InitError> {
+        let body = serde_json::json!({ "code": code, "app_id": self.app_id });
+        let StartData {
+            game_info,
+            websocket_info,
+            anchor_info,
+        } = self.post("/v2/app/start", body).await?;
+        Ok(Game {
+            game_id: game_info.game_id,
+            anchor: anchor_info,
+            auth_body: websocket_info.auth_body,
+            wss_link: websocket_info.wss_link,
+        })
+    }
+
+    /// 游戏心跳，需要每20秒发送一次
+    pub async fn heartbeat(&self, game_id: &str) -> Result<(), InitError> {
+        let body = serde_json::json!({ "game_id": game_id });
+        self.post::<IgnoredAny>("/v2/app/heartbeat", body).await?;
+        Ok(())
+    }
+
+    /// 关闭游戏
+    pub async fn end(&self, game_id: &str) -> Result<(), InitError> {
+        let body = serde_json::json!({ "app_id": self.app_id, "game_id": game_id });
+        self.post::<IgnoredAny>("/v2/app/end", body).await?;
+        Ok(())
+    }
+
+    /// 每20秒发送一次心跳，直到心跳失败
+    pub async fn keep_alive(&self, game_id: &str) -> Result<(), InitError> {
+        loop {
+            crate::connection::sleep(Duration::from_secs(20)).await;
+            self.heartbeat(game_id).await.map_err(|e| {
+                tracing::warn!(error = %e, game_id, "开放平台心跳失败");
+                e
+            })?;
+        }
+    }
+
+    async fn post<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        body: serde_json::Value,
+    ) -> Result<T, InitError> {
+        let body = body.to_string();
+        let mut request = self
+            .client
+            .client
+            .post(format!("{API_HOST}{path}"))
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json");
+        for (key, value) in self.sign(&body) {
+            request = request.header(key, value);
+        }
+        self.client.send_data(request.body(body)).await
+    }
+
+    fn sign(&self, body: &str) -> Vec<(&'static str, String)> {
+        static NONCE: AtomicU64 = AtomicU64::new(0);
+        let now = now_millis();
+        let nonce = format!("{now}{}", NONCE.fetch_add(1, Ordering::Relaxed));
+        sign_headers(
+            &self.access_key_id,
+            &self.access_key_secret,
+            body,
+            now / 1000,
+            nonce,
+        )
+    }
+}
+
+/// 签名需要的请求头，`Authorization`为按字典序拼接的`x-bili-*`头的HMAC-SHA256，`timestamp`为秒级时间戳
+pub(crate) fn sign_headers(
+    access_key_id: &str,
+    access_key_secret: &str,
+    body: &str,
+    timestamp: u64,
+    nonce: String,
+) -> Vec<(&'static str, String)> {
+    let mut headers = vec![
+        ("x-bili-accesskeyid", access_key_id.to_owned()),
+        ("x-bili-content-md5", hex::encode(Md5::digest(body))),
+        ("x-bili-signature-method", "HMAC-SHA256".to_owned()),
+        ("x-bili-signature-nonce", nonce),
+        ("x-bili-signature-version", "1.0".to_owned()),
+        ("x-bili-timestamp", timestamp.to_string()),
+    ];
+    let canonical = headers
+        .iter()
+        .map(|(key, value)| format!("{key}:{value}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut mac = Hmac::<Sha256>::new_from_slice(access_key_secret.as_bytes())
+        .expect("hmac accepts keys of any size");
+    mac.update(canonical.as_bytes());
+    let signature = hex::encode(mac.finalize().into_bytes());
+    headers.push(("Authorization", signature));
+    headers
+}
+
+impl Game {
+    /// 依次尝试`wss_link`中的地址
+    #[tracing::instrument(name = "room", skip(self, config), fields(roomid = self.anchor.room_id))]
+    pub async fn connect(&self, config: &ConnectConfig) -> Result<Connection, ConnectError> {
+        let mut last_error = None;
+        for url in &self.wss_link {
+            let auth = Auth::raw(self.anchor.room_id, self.auth_body.clone());
+            match Connection::connect(url.clone(), auth, config).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => {
+                    tracing::warn!(error = %e, url, "handshake error");
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(match last_error {
+            Some(e) => ConnectError::HandshakeError(e),
+            None => ConnectError::HostListIsEmpty,
+        })
+    }
+}
@@ -1,39 +1,167 @@
 use std::{fmt::Display, io::Write};
 
+use bytes::Bytes;
+
 fn write_u32_be(writer: &mut [u8], val: u32) -> &mut [u8] {
-    let (write, writer) = writer.split_array_mut::<4>();
+    let (write, writer) = writer
+        .split_first_chunk_mut::<4>()
+        .expect("写入u32时，缓冲区长度不足");
     *write = val.to_be_bytes();
     writer
 }
 
 fn write_u16_be(writer: &mut [u8], val: u16) -> &mut [u8] {
-    let (write, writer) = writer.split_array_mut::<2>();
+    let (write, writer) = writer
+        .split_first_chunk_mut::<2>()
+        .expect("写入u16时，缓冲区长度不足");
     *write = val.to_be_bytes();
     writer
 }
 
 fn read_u32_be(buffer: &[u8]) -> (u32, &[u8]) {
-    let (read, tail) = buffer.split_array_ref::<4>();
+    let (read, tail) = buffer
+        .split_first_chunk::<4>()
+        .expect("读取u32时，缓冲区长度不足");
     (u32::from_be_bytes(*read), tail)
 }
 
 fn read_u16_be(buffer: &[u8]) -> (u16, &[u8]) {
-    let (read, tail) = buffer.split_array_ref::<2>();
+    let (read, tail) = buffer
+        .split_first_chunk::<2>()
+        .expect("读取u16时，缓冲区长度不足");
     (u16::from_be_bytes(*read), tail)
 }
 
+const HEAD_SIZE: usize = 16;
+
+/// 数据包格式错误
+#[derive(Debug, Clone)]
+pub enum PacketParseError {
+    /// 缓冲区比包头声明的长度短
+    TooShort { expected: usize, actual: usize },
+    /// 包头中的长度字段不合法
+    InvalidHead(RawPacketHead),
+}
+
+impl Display for PacketParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PacketParseError::TooShort { expected, actual } => {
+                write!(
+                    f,
+                    "数据包长度不足，需要{}字节，实际{}字节",
+                    expected, actual
+                )
+            }
+            PacketParseError::InvalidHead(head) => write!(f, "数据包头不合法：{:?}", head),
+        }
+    }
+}
+
+impl std::error::Error for PacketParseError {}
+
 #[derive(Debug, Clone)]
 pub enum Data {
     Json(serde_json::Value),
     Popularity(u32),
-    Deflate(String),
+    Deflate(#[allow(dead_code)] String),
+    /// 无法解出数据的包，不产生事件
+    Invalid(#[cfg_attr(not(feature = "connect"), allow(dead_code))] Diagnostic),
 }
 
+/// 处理数据时遇到的问题，附带原始数据，见[`ConnectConfig::diagnostics`](crate::connection::ConnectConfig)
+///
+/// 这些问题不会中断连接，同时也会以`tracing`日志的形式输出
+#[derive(Debug, Clone)]
+pub enum Diagnostic {
+    /// 数据包格式错误，`raw`为整个websocket帧或解压后的数据
+    MalformedPacket { error: String, raw: Bytes },
+    /// 数据包的内容不是合法的json
+    InvalidJson { error: String, raw: Bytes },
+    /// 解压失败
+    Decompress { error: String, raw: Bytes },
+    /// 不支持的协议版本
+    UnsupportedProto { proto_code: u16, raw: Bytes },
+    /// deflate压缩的消息，不解析为事件，`text`为解压后的内容，没有启用`deflate`特性时为空
+    Deflate { text: String },
+    /// json无法解析为事件，`raw`为原始json，不包括被忽略的cmd
+    CmdParse {
+        cmd: String,
+        error: String,
+        raw: String,
+    },
+    /// 服务器发送了Close帧
+    Closed { code: u16, reason: String },
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Diagnostic::MalformedPacket { error, raw } => {
+                write!(f, "数据包格式错误（{}字节）：{}", raw.len(), error)
+            }
+            Diagnostic::InvalidJson { error, raw } => {
+                write!(f, "json格式错误（{}字节）：{}", raw.len(), error)
+            }
+            Diagnostic::Decompress { error, raw } => {
+                write!(f, "解压失败（{}字节）：{}", raw.len(), error)
+            }
+            Diagnostic::UnsupportedProto { proto_code, .. } => {
+                write!(f, "不支持的协议版本：{}", proto_code)
+            }
+            Diagnostic::Deflate { .. } => write!(f, "不解析deflate压缩的消息"),
+            Diagnostic::CmdParse { cmd, error, .. } => write!(f, "解析{}失败：{}", cmd, error),
+            Diagnostic::Closed { code, reason } => write!(f, "服务器关闭连接：{} {}", code, reason),
+        }
+    }
+}
+
+#[derive(Debug)]
 pub enum EventParseError {
     CmdDeserError(CmdDeserError),
     DeflateMessage,
 }
 
+impl EventParseError {
+    /// 解析失败的消息的cmd，没有时为空字符串
+    pub fn cmd(&self) -> String {
+        match self {
+            EventParseError::CmdDeserError(CmdDeserError::Ignored { tag }) => tag.clone(),
+            EventParseError::CmdDeserError(
+                CmdDeserError::CannotDeser { cmd, .. } | CmdDeserError::Malformed { cmd, .. },
+            ) => cmd.clone(),
+            _ => String::new(),
+        }
+    }
+
+    /// 对应的[`Diagnostic`]，被忽略的cmd没有
+    #[cfg_attr(not(feature = "connect"), allow(dead_code))]
+    pub(crate) fn diagnostic(&self) -> Option<Diagnostic> {
+        let (cmd, error, raw) = match self {
+            EventParseError::DeflateMessage => return None,
+            EventParseError::CmdDeserError(e) => match e {
+                CmdDeserError::Ignored { .. } => return None,
+                CmdDeserError::CannotDeser {
+                    cmd,
+                    path,
+                    json_error,
+                    text,
+                } => (cmd.clone(), format!("字段{}：{}", path, json_error), text),
+                CmdDeserError::Malformed { cmd, path, text } => {
+                    (cmd.clone(), format!("字段{}格式错误", path), text)
+                }
+                CmdDeserError::Untagged { text } => (String::new(), "缺少cmd".to_owned(), text),
+                CmdDeserError::Custom { text } => (String::new(), text.clone(), &String::new()),
+            },
+        };
+        Some(Diagnostic::CmdParse {
+            cmd,
+            error,
+            raw: raw.clone(),
+        })
+    }
+}
+
 impl Display for EventParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -43,33 +171,72 @@ impl Display for EventParseError {
     }
 }
 
+impl std::error::Error for EventParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EventParseError::CmdDeserError(e) => Some(e),
+            EventParseError::DeflateMessage => None,
+        }
+    }
+}
+
 impl Data {
-    pub fn into_event(self) -> Result<Option<Event>, EventParseError> {
-        let data = match self {
-            Data::Json(json_val) => match crate::cmd::Cmd::deser(json_val) {
-                Ok(cmd) => cmd.into_event(),
-                Err(e) => return Err(EventParseError::CmdDeserError(e)),
-            },
-            Data::Popularity(popularity) => Some(PopularityUpdateEvent { popularity }.into()),
+    /// `keep_raw`为`true`时，在事件中保留原始json，先尝试`parsers`中注册的解析器，没有时使用内置的解析
+    pub fn into_event(
+        self,
+        keep_raw: bool,
+        parsers: &CmdParsers,
+    ) -> Result<Option<Event>, EventParseError> {
+        let (data, server_timestamp, raw) = match self {
+            Data::Json(json_val) => {
+                let server_timestamp = crate::cmd::server_timestamp(&json_val);
+                let raw = keep_raw.then(|| json_val.clone());
+                if let Some(mut event) = parsers.parse(&json_val) {
+                    event.server_timestamp = event.server_timestamp.or(server_timestamp);
+                    event.raw = event.raw.or(raw);
+                    return Ok(Some(event));
+                }
+                match crate::cmd::Cmd::deser(json_val) {
+                    Ok(cmd) => (cmd.into_event(), server_timestamp, raw),
+                    Err(e) => return Err(EventParseError::CmdDeserError(e)),
+                }
+            }
+            Data::Popularity(popularity) => (
+                Some(PopularityUpdateEvent { popularity }.into()),
+                None,
+                None,
+            ),
             Data::Deflate(_) => return Err(EventParseError::DeflateMessage),
+            Data::Invalid(_) => (None, None, None),
         };
-        Ok(data.map(Into::into))
+        Ok(data.map(|data| Event {
+            server_timestamp,
+            raw,
+            ..data.into()
+        }))
     }
 }
 
+/// 数据包头，共16字节，均为大端序
+///
+/// # 说明
+/// - `size` 包括头在内的整个包的长度
+/// - `proto_code` 协议版本，0为json，1为人气值，2为zlib压缩，3为brotli压缩
+/// - `opcode` 操作码，见[`Operation`]
 #[derive(Debug, Clone)]
-struct RawPacketHead {
-    size: u32,
-    header_size: u16,
-    proto_code: u16,
-    opcode: u32,
-    sequence: u32,
+pub struct RawPacketHead {
+    pub size: u32,
+    pub header_size: u16,
+    pub proto_code: u16,
+    pub opcode: u32,
+    pub sequence: u32,
 }
 
 #[repr(transparent)]
 #[derive(Debug, Clone)]
-struct RawPacketData(Vec<u8>);
+struct RawPacketData(Bytes);
 
+/// 弹幕服务器的原始数据包
 #[derive(Debug, Clone)]
 pub struct RawPacket {
     head: RawPacketHead,
@@ -77,6 +244,26 @@ pub struct RawPacket {
 }
 
 impl RawPacket {
+    pub fn head(&self) -> &RawPacketHead {
+        &self.head
+    }
+
+    /// 包体，`proto_code`为2或3时是压缩后的数据
+    pub fn body(&self) -> &[u8] {
+        &self.data.0
+    }
+
+    /// 鉴权回复中的`code`，0为成功，不是鉴权回复或无法解析时为`None`
+    pub fn auth_reply_code(&self) -> Option<i64> {
+        if self.head.opcode != Operation::AuthReply as u32 {
+            return None;
+        }
+        serde_json::from_slice::<serde_json::Value>(self.body())
+            .ok()?
+            .get("code")?
+            .as_i64()
+    }
+
     pub fn heartbeat() -> Self {
         RawPacket {
             head: RawPacketHead {
@@ -86,16 +273,27 @@ impl RawPacket {
                 opcode: 2,
                 sequence: 1,
             },
-            data: RawPacketData(b"[object Object]".to_vec()),
+            data: RawPacketData(Bytes::from_static(b"[object Object]")),
         }
     }
 
-    pub fn from_buffer(buffer: &[u8]) -> Self {
-        let (size, buffer) = read_u32_be(buffer);
-        let (header_size, buffer) = read_u16_be(buffer);
-        let (version, buffer) = read_u16_be(buffer);
-        let (opcode, buffer) = read_u32_be(buffer);
-        let (sequence, buffer) = read_u32_be(buffer);
+    pub fn from_buffer(buffer: &[u8]) -> Result<Self, PacketParseError> {
+        Self::from_bytes(Bytes::copy_from_slice(buffer))
+    }
+
+    /// 包体与`buffer`共享内存，不会复制
+    pub fn from_bytes(buffer: Bytes) -> Result<Self, PacketParseError> {
+        if buffer.len() < HEAD_SIZE {
+            return Err(PacketParseError::TooShort {
+                expected: HEAD_SIZE,
+                actual: buffer.len(),
+            });
+        }
+        let (size, tail) = read_u32_be(&buffer);
+        let (header_size, tail) = read_u16_be(tail);
+        let (version, tail) = read_u16_be(tail);
+        let (opcode, tail) = read_u32_be(tail);
+        let (sequence, _) = read_u32_be(tail);
         let head = RawPacketHead {
             size,
             header_size,
@@ -103,25 +301,25 @@ impl RawPacket {
             opcode,
             sequence,
         };
+        let (size, header_size) = (size as usize, header_size as usize);
+        if header_size < HEAD_SIZE || size < header_size {
+            return Err(PacketParseError::InvalidHead(head));
+        }
+        if buffer.len() < size {
+            return Err(PacketParseError::TooShort {
+                expected: size,
+                actual: buffer.len(),
+            });
+        }
 
-        let data = RawPacketData(buffer.to_owned());
+        let data = RawPacketData(buffer.slice(header_size..size));
 
-        RawPacket { head, data }
+        Ok(RawPacket { head, data })
     }
 
-    fn from_buffers(buffer: &[u8]) -> Vec<Self> {
-        let mut packets = vec![];
-        let mut ptr = 0;
-        loop {
-            let (size, _) = read_u32_be(&buffer[ptr..ptr + 4]);
-            let size = size as usize;
-            packets.push(Self::from_buffer(&buffer[ptr..ptr + size]));
-            ptr += size;
-            if ptr >= buffer.len() {
-                break;
-            }
-        }
-        packets
+    /// 逐个解析首尾相接的多个数据包，见[`RawPackets`]
+    pub fn from_buffers(buffer: Bytes) -> RawPackets {
+        RawPackets { buffer, offset: 0 }
     }
 
     pub fn build(op: Operation, data: Vec<u8>) -> Self {
@@ -136,12 +334,23 @@ impl RawPacket {
                 opcode,
                 sequence: 1,
             },
-            data: RawPacketData(data),
+            data: RawPacketData(data.into()),
         }
     }
 
+    /// 修改序号，[`build`](Self::build)默认为1，连接发出数据包时会按发送顺序重新编号
+    pub fn with_sequence(mut self, sequence: u32) -> Self {
+        self.head.sequence = sequence;
+        self
+    }
+
+    /// 修改协议版本，[`build`](Self::build)默认为1
+    pub fn with_proto_code(mut self, proto_code: u16) -> Self {
+        self.head.proto_code = proto_code;
+        self
+    }
+
     pub fn ser(self) -> Vec<u8> {
-        const HEAD_SIZE: usize = 16;
         let head = self.head;
         let data = self.data.0;
         let mut buffer = Vec::<u8>::with_capacity(128 + data.len());
@@ -156,64 +365,164 @@ impl RawPacket {
         buffer
     }
 
-    pub fn get_datas(self) -> Vec<Data> {
+    /// 解出包中的数据，`decompressed`累加解压后的字节数
+    pub(crate) fn get_datas(self, decompressed: &mut u64, cmd_filter: &CmdFilter) -> Vec<Data> {
         match self.head.proto_code {
             // raw json
             0 => {
-                if let Ok(data_json) = serde_json::from_slice::<serde_json::Value>(&self.data.0) {
-                    vec![Data::Json(data_json)]
-                } else {
-                    // println!("cannot deser {}", String::from_utf8(self.data.0).unwrap() );
-                    vec![]
+                if scan_cmd(&self.data.0).is_some_and(|cmd| !cmd_filter.accept(cmd)) {
+                    return vec![];
+                }
+                match serde_json::from_slice::<serde_json::Value>(&self.data.0) {
+                    Ok(data_json) => vec![Data::Json(data_json)],
+                    Err(e) => {
+                        tracing::warn!(error = %e, "数据包的内容不是合法的json");
+                        vec![Data::Invalid(Diagnostic::InvalidJson {
+                            error: e.to_string(),
+                            raw: self.data.0,
+                        })]
+                    }
                 }
             }
-            1 => {
-                let (bytes, _) = self.data.0.split_array_ref::<4>();
-                let popularity = u32::from_be_bytes(*bytes);
-                vec![Data::Popularity(popularity)]
-            }
+            1 => match self.data.0.first_chunk::<4>() {
+                Some(popularity) => vec![Data::Popularity(u32::from_be_bytes(*popularity))],
+                None => {
+                    tracing::warn!(len = self.data.0.len(), "人气值数据包长度不足");
+                    vec![Data::Invalid(Diagnostic::MalformedPacket {
+                        error: "人气值数据包长度不足".to_owned(),
+                        raw: self.data.0,
+                    })]
+                }
+            },
             2 => {
                 #[cfg(feature = "deflate")]
-                {
-                    let deflated = deflate::deflate_bytes(&self.data.0);
-                    let utf8 = String::from_utf8(deflated).unwrap();
-                    return vec![Data::Deflate(utf8)];
-                }
+                let text =
+                    String::from_utf8_lossy(&deflate::deflate_bytes(&self.data.0)).into_owned();
                 #[cfg(not(feature = "deflate"))]
-                vec![Data::Deflate("".to_string())]
+                let text = String::new();
+                vec![Data::Deflate(text)]
             }
             3 => {
                 use std::io::Read;
-                let read_stream = std::io::Cursor::new(self.data.0);
+                let read_stream = std::io::Cursor::new(self.data.0.clone());
                 let mut input = brotli::Decompressor::new(read_stream, 4096);
                 let mut buffer = Vec::new();
                 match input.read_to_end(&mut buffer) {
-                    Ok(_size) => {
-                        let unpacked = RawPacket::from_buffers(&buffer);
+                    Ok(size) => {
+                        *decompressed += size as u64;
                         let mut packets = vec![];
-                        for p in unpacked {
-                            for sub_p in p.get_datas() {
-                                packets.push(sub_p)
+                        let buffer = Bytes::from(buffer);
+                        for p in RawPacket::from_buffers(buffer.clone()) {
+                            match p {
+                                Ok(p) => packets.extend(p.get_datas(decompressed, cmd_filter)),
+                                Err(e) => {
+                                    tracing::warn!(error = %e, "解压后的数据包格式错误");
+                                    packets.push(Data::Invalid(Diagnostic::MalformedPacket {
+                                        error: e.to_string(),
+                                        raw: buffer.clone(),
+                                    }));
+                                }
                             }
                         }
                         packets
                     }
                     Err(e) => {
-                        log::error!("读取数据包解压结果错误：{e}");
-                        vec![]
+                        tracing::error!(error = %e, "读取数据包解压结果错误");
+                        vec![Data::Invalid(Diagnostic::Decompress {
+                            error: e.to_string(),
+                            raw: self.data.0,
+                        })]
                     }
                 }
             }
             _ => {
-                log::warn!("不支持的操作码：{}", self.head.proto_code);
-                vec![]
+                tracing::warn!(proto_code = self.head.proto_code, "不支持的协议版本");
+                vec![Data::Invalid(Diagnostic::UnsupportedProto {
+                    proto_code: self.head.proto_code,
+                    raw: self.data.0,
+                })]
             } //
         }
     }
 }
 
-#[allow(dead_code)]
-#[derive(Debug)]
+/// 不解析json，直接从原始消息中找出`cmd`的值
+///
+/// b站的消息都以`{"cmd":"..."`开头，找不到或带有转义时返回`None`
+pub(crate) fn scan_cmd(json: &[u8]) -> Option<&str> {
+    const KEY: &[u8] = b"\"cmd\":";
+    let start = json.windows(KEY.len()).position(|w| w == KEY)? + KEY.len();
+    let rest = json[start..].trim_ascii_start().strip_prefix(b"\"")?;
+    let end = rest.iter().position(|b| *b == b'"' || *b == b'\\')?;
+    if rest[end] != b'"' {
+        return None;
+    }
+    std::str::from_utf8(&rest[..end]).ok()
+}
+
+/// 首尾相接的多个数据包，由[`RawPacket::from_buffers`]得到
+///
+/// 每个数据包依据包头中的`size`切分，包体与原始数据共享内存。
+/// 遇到不合法的包头时产生一个错误，并向后寻找下一个看起来合法的包头继续解析；
+/// 剩余的数据不足一个完整的包时产生一个错误并结束
+#[derive(Debug, Clone)]
+pub struct RawPackets {
+    buffer: Bytes,
+    offset: usize,
+}
+
+impl RawPackets {
+    /// 从`offset`开始寻找下一个可能的包头，找不到时返回数据的长度
+    fn resync(&self, from: usize) -> usize {
+        (from..self.buffer.len())
+            .find(|&offset| is_plausible_head(&self.buffer[offset..]))
+            .unwrap_or(self.buffer.len())
+    }
+}
+
+impl Iterator for RawPackets {
+    type Item = Result<RawPacket, PacketParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.buffer.len() {
+            return None;
+        }
+        match RawPacket::from_bytes(self.buffer.slice(self.offset..)) {
+            Ok(packet) => {
+                // from_bytes保证了size不小于包头长度，每次至少前进16字节
+                self.offset += packet.head.size as usize;
+                Some(Ok(packet))
+            }
+            Err(e @ PacketParseError::InvalidHead(_)) => {
+                self.offset = self.resync(self.offset + 1);
+                Some(Err(e))
+            }
+            Err(e) => {
+                self.offset = self.buffer.len();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// 包头长度为16、协议版本和操作码已知，并且`size`不超过剩余数据
+fn is_plausible_head(buffer: &[u8]) -> bool {
+    if buffer.len() < HEAD_SIZE {
+        return false;
+    }
+    let (size, tail) = read_u32_be(buffer);
+    let (header_size, tail) = read_u16_be(tail);
+    let (version, tail) = read_u16_be(tail);
+    let (opcode, _) = read_u32_be(tail);
+    header_size as usize == HEAD_SIZE
+        && (size as usize) >= HEAD_SIZE
+        && (size as usize) <= buffer.len()
+        && version <= 3
+        && opcode <= Operation::UnregisterReply as u32
+}
+
+/// 操作码，按顺序从0开始
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Operation {
     Handshake,
     HandshakeReply,
@@ -239,15 +548,29 @@ use serde::Serialize;
 use crate::{
     cmd::CmdDeserError,
     event::{Event, PopularityUpdateEvent},
+    filter::CmdFilter,
+    parser::CmdParsers,
 };
+/// 鉴权包
+///
+/// # 说明
+/// - `protover` 协议版本，1为不压缩的json，2为zlib压缩（目前不会解压），3为brotli压缩，默认为3
+/// - `platform` 客户端平台，默认为`web`
+/// - `type` 默认为2
+/// - `buvid` 设备标识，即cookie中的`buvid3`，部分服务器会校验
 #[derive(Debug, Clone, Serialize)]
 pub struct Auth {
     uid: u64,
     roomid: u64,
     protover: i32,
-    platform: &'static str,
+    platform: String,
     r#type: i32,
     key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    buvid: Option<String>,
+    /// 开放平台直接给出完整的鉴权包
+    #[serde(skip)]
+    raw: Option<String>,
 }
 
 impl Auth {
@@ -256,13 +579,50 @@ impl Auth {
             uid,
             roomid,
             protover: 3,
-            platform: "web",
+            platform: "web".to_owned(),
             r#type: 2,
             key,
+            buvid: None,
+            raw: None,
         }
     }
 
+    /// 使用接口返回的`auth_body`作为鉴权包
+    pub fn raw(roomid: u64, body: String) -> Self {
+        Self {
+            raw: Some(body),
+            ..Self::new(0, roomid, None)
+        }
+    }
+
+    pub fn with_protover(mut self, protover: i32) -> Self {
+        self.protover = protover;
+        self
+    }
+
+    pub fn with_platform(mut self, platform: impl Into<String>) -> Self {
+        self.platform = platform.into();
+        self
+    }
+
+    pub fn with_type(mut self, r#type: i32) -> Self {
+        self.r#type = r#type;
+        self
+    }
+
+    pub fn with_buvid(mut self, buvid: impl Into<String>) -> Self {
+        self.buvid = Some(buvid.into());
+        self
+    }
+
+    pub fn roomid(&self) -> u64 {
+        self.roomid
+    }
+
     pub fn ser(self) -> Vec<u8> {
+        if let Some(raw) = self.raw {
+            return raw.into_bytes();
+        }
         let jsval = serde_json::json!(self);
         jsval.to_string().as_bytes().to_owned()
     }
@@ -0,0 +1,92 @@
+//! 第三方的cmd解析器
+//!
+//! 注册的解析器在内置的解析之前调用，可以支持库中还没有的cmd，或者替换内置的解析方式。
+//! 解析器返回`None`时继续使用内置的解析
+//!```no_run,ignore
+//!use bilive_danmaku::{event::*, parser::CmdParsers};
+//!connector.config.parsers = CmdParsers::new()
+//!    // 原样转发为CustomEvent
+//!    .passthrough(["WIDGET_BANNER", "POPULARITY_RED_POCKET_V2_NEW"])
+//!    .register("WATCHED_CHANGE", |json| {
+//!        let num = json["data"]["num"].as_u64()?;
+//!        Some(EventData::from(WatchedUpdateEvent { num }).into())
+//!    });
+//!```
+use std::{collections::HashMap, fmt::Debug, sync::Arc};
+
+use serde_json::Value;
+
+use crate::event::{CustomEvent, Event, EventData};
+
+pub type CmdParser = Arc<dyn Fn(&Value) -> Option<Event> + Send + Sync>;
+
+/// 按cmd注册的解析器，克隆后共享同样的解析器
+#[derive(Clone, Default)]
+pub struct CmdParsers {
+    parsers: HashMap<String, CmdParser>,
+}
+
+impl CmdParsers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 为`cmd`注册解析器，同一个cmd只保留最后注册的解析器
+    ///
+    /// `cmd`为完整的cmd，或者`DANMU_MSG:4:0:2:2:2:0`这类带后缀的cmd中冒号前的部分
+    pub fn register<F>(mut self, cmd: impl Into<String>, parser: F) -> Self
+    where
+        F: Fn(&Value) -> Option<Event> + Send + Sync + 'static,
+    {
+        self.parsers.insert(cmd.into(), Arc::new(parser));
+        self
+    }
+
+    /// 把这些cmd原样转发为[`CustomEvent`]
+    pub fn passthrough<I, S>(mut self, cmds: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        for cmd in cmds {
+            self = self.register(cmd, |json| {
+                let cmd = json["cmd"].as_str()?.to_owned();
+                let data = match json.get("data") {
+                    Some(data) => data.clone(),
+                    None => json.clone(),
+                };
+                Some(EventData::from(CustomEvent { cmd, data }).into())
+            });
+        }
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parsers.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.parsers.len()
+    }
+
+    /// 使用注册的解析器解析，没有对应的解析器或者解析器返回`None`时为`None`
+    pub fn parse(&self, json: &Value) -> Option<Event> {
+        if self.parsers.is_empty() {
+            return None;
+        }
+        let cmd = json["cmd"].as_str()?;
+        let parser = self
+            .parsers
+            .get(cmd)
+            .or_else(|| self.parsers.get(cmd.split(':').next()?))?;
+        parser(json)
+    }
+}
+
+impl Debug for CmdParsers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut cmds: Vec<_> = self.parsers.keys().collect();
+        cmds.sort();
+        f.debug_struct("CmdParsers").field("cmds", &cmds).finish()
+    }
+}
@@ -0,0 +1,204 @@
+//! 以 NDJSON（每行一个json）的格式录制事件
+//!
+//!```no_run,ignore
+//!use bilive_danmaku::record::{Recorder, RecordConfig};
+//!let mut recorder = Recorder::new("./records", 851181, RecordConfig::default())?;
+//!while let Some(Ok(evt)) = stream.next().await {
+//!    recorder.record(&evt)?;
+//!}
+//!```
+//!
+//! 使用[`ReplaySource`]回放录制的文件
+//!```no_run,ignore
+//!use bilive_danmaku::record::ReplaySource;
+//!// 两倍速回放
+//!let mut stream = ReplaySource::open("./records/851181-1651240292348-0.ndjson")?.into_stream(Some(2.0));
+//!while let Some(Ok(evt)) = stream.next().await {
+//!    // 处理事件
+//!}
+//!```
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Lines, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::event::Event;
+
+/// 录制文件的一行
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Record {
+    pub roomid: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+#[derive(Serialize)]
+struct RecordRef<'a> {
+    roomid: u64,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+/// # 说明
+/// - `max_size` 单个文件的最大字节数，超过后切换到新文件
+/// - `max_duration` 单个文件的最长录制时间，超过后切换到新文件
+///
+/// 都为`None`时不分割文件
+#[derive(Clone, Debug, Default)]
+pub struct RecordConfig {
+    pub max_size: Option<u64>,
+    pub max_duration: Option<Duration>,
+}
+
+/// 事件录制器，文件名为`{roomid}-{开始录制的毫秒时间戳}-{序号}.ndjson`
+pub struct Recorder {
+    dir: PathBuf,
+    roomid: u64,
+    config: RecordConfig,
+    path: PathBuf,
+    writer: BufWriter<File>,
+    written: u64,
+    opened_at: Instant,
+    sequence: u32,
+}
+
+impl Recorder {
+    pub fn new(dir: impl AsRef<Path>, roomid: u64, config: RecordConfig) -> io::Result<Self> {
+        let dir = dir.as_ref().to_owned();
+        std::fs::create_dir_all(&dir)?;
+        let (path, writer) = Self::open(&dir, roomid, 0)?;
+        Ok(Self {
+            dir,
+            roomid,
+            config,
+            path,
+            writer,
+            written: 0,
+            opened_at: Instant::now(),
+            sequence: 0,
+        })
+    }
+
+    fn open(dir: &Path, roomid: u64, sequence: u32) -> io::Result<(PathBuf, BufWriter<File>)> {
+        let timestamp = crate::event::now_millis();
+        let path = dir.join(format!("{roomid}-{timestamp}-{sequence}.ndjson"));
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok((path, BufWriter::new(file)))
+    }
+
+    /// 当前正在写入的文件
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&mut self, event: &Event) -> io::Result<()> {
+        if self.should_rotate() {
+            self.rotate()?;
+        }
+        let mut line = serde_json::to_vec(&RecordRef {
+            roomid: self.roomid,
+            event,
+        })?;
+        line.push(b'\n');
+        self.writer.write_all(&line)?;
+        self.written += line.len() as u64;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    fn should_rotate(&self) -> bool {
+        if self.written == 0 {
+            return false;
+        }
+        let size_exceeded = matches!(self.config.max_size, Some(max) if self.written >= max);
+        let time_exceeded =
+            matches!(self.config.max_duration, Some(max) if self.opened_at.elapsed() >= max);
+        size_exceeded || time_exceeded
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        self.sequence += 1;
+        let (path, writer) = Self::open(&self.dir, self.roomid, self.sequence)?;
+        tracing::debug!(path = %path.display(), "切换录制文件");
+        self.path = path;
+        self.writer = writer;
+        self.written = 0;
+        self.opened_at = Instant::now();
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.writer.flush() {
+            tracing::error!(error = %e, "录制文件写入错误");
+        }
+    }
+}
+
+/// 读取录制文件，按行产出[`Record`]
+pub struct ReplaySource {
+    lines: Lines<BufReader<File>>,
+}
+
+impl ReplaySource {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(Self {
+            lines: BufReader::new(file).lines(),
+        })
+    }
+
+    /// 转换为和[`crate::Connection`]相同的事件流
+    ///
+    /// `speed`为`None`时不等待，尽快产出所有事件；否则按照原始时间间隔除以`speed`等待，`Some(1.0)`即为原速，非正数视为`None`
+    ///
+    /// 无法解析的行会被跳过
+    #[cfg(feature = "rt_tokio")]
+    pub fn into_stream(
+        self,
+        speed: Option<f64>,
+    ) -> impl futures_util::Stream<Item = Result<Event, crate::connection::EventStreamError>> {
+        let records = self.filter_map(|record| match record {
+            Ok(record) => Some(record.event),
+            Err(e) => {
+                tracing::warn!(error = %e, "跳过无法读取的录制行");
+                None
+            }
+        });
+        futures_util::stream::unfold(
+            (records, None::<u64>),
+            move |(mut records, last_timestamp)| async move {
+                let event = records.next()?;
+                if let (Some(speed), Some(last)) = (speed.filter(|s| *s > 0.0), last_timestamp) {
+                    let interval = event.timestamp.saturating_sub(last) as f64 / speed;
+                    tokio::time::sleep(Duration::from_secs_f64(interval / 1000.0)).await;
+                }
+                let timestamp = event.timestamp;
+                Some((Ok(event), (records, Some(timestamp))))
+            },
+        )
+    }
+}
+
+impl Iterator for ReplaySource {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.lines.next()? {
+                Ok(line) if line.trim().is_empty() => continue,
+                Ok(line) => return Some(serde_json::from_str(&line).map_err(Into::into)),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
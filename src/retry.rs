@@ -0,0 +1,192 @@
+//! 重试策略
+//!
+//! [`RetryPolicy`]统一描述初始化、连接和重连时的重试方式：最多尝试几次、每次间隔多久、哪些错误值得重试。
+//!```no_run,ignore
+//!use bilive_danmaku::retry::{ErrorClass, RetryPolicy};
+//!let policy = RetryPolicy::default()
+//!    .with_max_attempts(5)
+//!    .with_retryable(&[ErrorClass::Network, ErrorClass::Server]);
+//!// 初始化时的http请求
+//!let client = HttpClient::new().with_retry(policy.clone());
+//!let mut connector = Connector::init_with(roomid, &client).await?;
+//!// 连接和重连
+//!connector.config.retry = policy;
+//!let stream = connector.connect_retrying().await?;
+//!```
+use std::{
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+use crate::{connection::EventStreamError, connection::WsConnectError, ConnectError, InitError};
+
+/// 错误的分类，用来决定是否重试
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorClass {
+    /// 网络错误，包括请求失败和websocket握手失败
+    Network,
+    /// http状态码为5xx、412（请求过于频繁被风控）或429
+    Server,
+    /// 接口返回的`code`不为0
+    Api,
+    /// 鉴权被拒绝，重试前会重新获取token
+    AuthRejected,
+    /// 已经建立的连接断开或停滞
+    Disconnected,
+    /// 重试也不会成功的错误，例如解析失败、直播间不存在
+    Fatal,
+}
+
+/// 可以按[`ErrorClass`]分类的错误
+pub trait Retryable {
+    fn error_class(&self) -> ErrorClass;
+}
+
+impl Retryable for InitError {
+    fn error_class(&self) -> ErrorClass {
+        match self {
+            InitError::HttpError(_) | InitError::Timeout => ErrorClass::Network,
+            InitError::HttpStatus(status)
+                if status.is_server_error() || matches!(status.as_u16(), 412 | 429) =>
+            {
+                ErrorClass::Server
+            }
+            InitError::ApiError { .. } => ErrorClass::Api,
+            _ => ErrorClass::Fatal,
+        }
+    }
+}
+
+impl Retryable for WsConnectError {
+    fn error_class(&self) -> ErrorClass {
+        match self {
+            WsConnectError::AuthRejected { .. } => ErrorClass::AuthRejected,
+            _ => ErrorClass::Network,
+        }
+    }
+}
+
+impl Retryable for ConnectError {
+    fn error_class(&self) -> ErrorClass {
+        match self {
+            ConnectError::HostListIsEmpty => ErrorClass::Fatal,
+            ConnectError::HandshakeError(e) => e.error_class(),
+            ConnectError::TokenRefresh(e) => e.error_class(),
+        }
+    }
+}
+
+impl Retryable for EventStreamError {
+    fn error_class(&self) -> ErrorClass {
+        ErrorClass::Disconnected
+    }
+}
+
+/// 重试策略
+///
+/// # 说明
+/// - `max_attempts` 包括第一次在内的最多尝试次数，`None`表示不限次数，默认不限
+/// - `base_delay` 第一次重试前的等待时间，之后每次翻倍，默认1秒
+/// - `max_delay` 等待时间的上限，默认30秒
+/// - `jitter` 随机缩短等待时间的比例，0到1之间，默认0.2，即等待时间在计算值的80%到100%之间，避免多个连接同时重试
+/// - `retryable` 值得重试的错误分类，默认除[`ErrorClass::Api`]和[`ErrorClass::Fatal`]之外的所有分类
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: Option<u32>,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub jitter: f64,
+    pub retryable: Vec<ErrorClass>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: None,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            jitter: 0.2,
+            retryable: vec![
+                ErrorClass::Network,
+                ErrorClass::Server,
+                ErrorClass::AuthRejected,
+                ErrorClass::Disconnected,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 不重试
+    pub fn never() -> Self {
+        Self::default().with_max_attempts(1)
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts.max(1));
+        self
+    }
+
+    pub fn with_delay(mut self, base_delay: Duration, max_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self.max_delay = max_delay.max(base_delay);
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_retryable(mut self, retryable: &[ErrorClass]) -> Self {
+        self.retryable = retryable.to_vec();
+        self
+    }
+
+    /// 第`attempt`次尝试失败后（从1开始），是否应该重试
+    pub fn should_retry<E: Retryable>(&self, error: &E, attempt: u32) -> bool {
+        self.max_attempts.is_none_or(|max| attempt < max)
+            && self.retryable.contains(&error.error_class())
+    }
+
+    /// 第`attempt`次尝试失败后（从1开始）的等待时间，不含随机部分
+    pub fn base_delay_for(&self, attempt: u32) -> Duration {
+        let exp = attempt.saturating_sub(1).min(31);
+        self.base_delay.saturating_mul(1 << exp).min(self.max_delay)
+    }
+
+    /// 第`attempt`次尝试失败后（从1开始）的等待时间
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay_for(attempt);
+        if self.jitter <= 0.0 {
+            return delay;
+        }
+        // 每个RandomState都有不同的随机种子，不需要额外的依赖
+        let random = std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish() as f64
+            / u64::MAX as f64;
+        delay.mul_f64(1.0 - self.jitter.clamp(0.0, 1.0) * random)
+    }
+
+    /// 按策略重复执行`f`，直到成功、遇到不可重试的错误或者用完尝试次数
+    pub async fn retry<T, E, F, Fut>(&self, mut f: F) -> Result<T, E>
+    where
+        E: Retryable + std::fmt::Display,
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match f().await {
+                Err(e) if self.should_retry(&e, attempt) => {
+                    let delay = self.delay_for(attempt);
+                    tracing::warn!(error = %e, attempt, ?delay, "稍后重试");
+                    crate::connection::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
@@ -0,0 +1,401 @@
+//! 在后台维持的直播间连接
+//!
+//! [`Room`]在后台任务中连接直播间，断线后自动切换服务器重连（见[`Connector::connect_switching`]），
+//! 所有连接的事件都通过同一个broadcast发出，订阅者在重连前后持续收到事件，不需要重新订阅
+//!```no_run,ignore
+//!use bilive_danmaku::{room::Room, Connector};
+//!let room = Room::spawn(Connector::init(851181).await?, 1024);
+//!let mut events = room.subscribe();
+//!while let Ok(event) = events.recv().await {
+//!    println!("{event}");
+//!}
+//!```
+//!
+//! 只关心部分事件时，[`Room::subscribe_filtered`]只会收到指定类型的事件，其他事件不会被复制给这个订阅者
+//!```no_run,ignore
+//!let mut danmaku = room.subscribe_filtered::<DanmakuEvent>();
+//!while let Ok(danmaku) = danmaku.recv().await {
+//!    println!("{}", danmaku.message);
+//!}
+//!```
+//!
+//! 订阅者跟不上时按[`LagPolicy`]处理，默认跳过积压的事件，跳过的数量见[`RoomReceiver::lagged`]和[`Room::lagged`]
+//!
+//! 直播状态见[`Room::watch_live_status`]，初始值来自[`Connector::live_status`]，之后随开播、下播的消息更新
+//!
+//! 连接的生命周期见[`RoomState`]，[`Room::disconnect`]断开后得到原来的[`Connector`]，可以再次[`Room::spawn`]。
+//! 这里没有另外实现`RoomService<Connected>`、`RoomService<Disconnected>`这样的类型状态：
+//! [`Connector`]和[`Room`]本身就是断开和已连接两个状态，`spawn`和`disconnect`都会消耗原来的值，
+//! 断开后继续使用[`Room`]或者重复断开在编译时就会报错；
+//! 而重连发生在后台任务中，调用方无法在类型上等待它，所以[`RoomState::Reconnecting`]只能作为运行时的状态提供给观察者。
+//! 重试策略放弃重连时，等待`retry.max_delay`后重新开始，只有取消时才会停止，停止后所有订阅者收到[`broadcast::error::RecvError::Closed`]
+//!```no_run,ignore
+//!let mut state = room.watch_state();
+//!while state.changed().await.is_ok() {
+//!    println!("{:?}", *state.borrow());
+//!}
+//!```
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use tokio::{
+    sync::{broadcast, watch},
+    task::JoinSet,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    event::{Event, EventData, EventKind},
+    model::LiveStatus,
+    Connector,
+};
+
+/// 按事件类型分发的broadcast，只有被订阅过的类型才有
+type KindSenders = Arc<Mutex<HashMap<&'static str, broadcast::Sender<Event>>>>;
+
+/// 直播间连接的状态，由后台任务更新，用于显示和观察，不能用来驱动状态转换
+///
+/// 状态转换由类型决定：[`Room::spawn`]从[`Connector`]得到[`Room`]，[`Room::disconnect`]再回到[`Connector`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoomState {
+    /// 正在建立第一个连接
+    Connecting,
+    /// 已经收到事件
+    Connected,
+    /// 连接断开或失败，正在切换服务器重连，`reason`为断开的原因
+    Reconnecting { reason: String },
+    /// 已经取消，不会再重连
+    Disconnected,
+}
+
+/// 订阅者积压的事件超过`capacity`时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LagPolicy {
+    /// 跳过积压的事件，从还在缓冲区中的最早的事件继续接收，并记录跳过的数量
+    #[default]
+    Skip,
+    /// 断开跟不上的订阅者，之后只会收到[`broadcast::error::RecvError::Closed`]
+    Disconnect,
+}
+
+/// 后台维持的直播间连接，drop时断开并abort后台任务
+///
+/// 发送端只由后台任务持有，任务停止后订阅者收到[`broadcast::error::RecvError::Closed`]
+///
+/// # 说明
+/// - `capacity` 每个订阅者最多积压的事件数，超过后按`lag_policy`处理，连接读取前的积压上限见[`ConnectConfig::buffer_capacity`](crate::connection::ConnectConfig)
+/// - `lag_policy` 订阅者跟不上时的处理方式，见[`LagPolicy`]，只影响之后的订阅
+#[derive(Debug)]
+pub struct Room {
+    roomid: u64,
+    capacity: usize,
+    lag_policy: LagPolicy,
+    lagged: Arc<AtomicU64>,
+    sender: broadcast::WeakSender<Event>,
+    kinds: KindSenders,
+    state: watch::Receiver<RoomState>,
+    live_status: watch::Receiver<LiveStatus>,
+    connector: Connector,
+    cancel: CancellationToken,
+    tasks: JoinSet<()>,
+}
+
+impl Room {
+    /// 在当前的tokio运行时中启动后台任务
+    pub fn spawn(mut connector: Connector, capacity: usize) -> Self {
+        let roomid = connector.roomid;
+        let capacity = capacity.max(1);
+        let (sender, _) = broadcast::channel(capacity);
+        let kinds = KindSenders::default();
+        let cancel = match &connector.config.cancel {
+            Some(parent) => parent.child_token(),
+            None => CancellationToken::new(),
+        };
+        let original = connector.clone();
+        connector.config.cancel = Some(cancel.clone());
+        let (state_tx, state) = watch::channel(RoomState::Connecting);
+        let (live_status_tx, live_status) =
+            watch::channel(connector.live_status.unwrap_or_default());
+        let weak_sender = sender.downgrade();
+        let task_kinds = kinds.clone();
+        let task_cancel = cancel.clone();
+        let task = async move {
+            use futures_util::StreamExt;
+            loop {
+                let mut stream = std::pin::pin!(connector.clone().connect_switching());
+                let mut last_error = None;
+                while let Some(item) = task_cancel
+                    .run_until_cancelled(stream.next())
+                    .await
+                    .flatten()
+                {
+                    let next_state = match &item {
+                        Ok(Event {
+                            data: EventData::HostSwitchEvent(switch),
+                            ..
+                        }) => RoomState::Reconnecting {
+                            reason: switch.reason.clone(),
+                        },
+                        Ok(_) => RoomState::Connected,
+                        Err(e) => RoomState::Reconnecting {
+                            reason: e.to_string(),
+                        },
+                    };
+                    state_tx.send_if_modified(|state| {
+                        let changed = *state != next_state;
+                        *state = next_state;
+                        changed
+                    });
+                    if let Ok(Event {
+                        data: EventData::LiveStatusEvent(e),
+                        ..
+                    }) = &item
+                    {
+                        live_status_tx.send_if_modified(|status| {
+                            let changed = *status != e.status;
+                            *status = e.status;
+                            changed
+                        });
+                    }
+                    match item {
+                        // 没有订阅者时丢弃
+                        Ok(event) => {
+                            // 重连时不再重复产生弹幕历史
+                            connector.config.backfill_history = false;
+                            send_kind(&task_kinds, &event);
+                            let _ = sender.send(event);
+                        }
+                        Err(e) => {
+                            tracing::warn!(error = %e, "连接失败");
+                            last_error = Some(e.to_string());
+                        }
+                    }
+                }
+                if task_cancel.is_cancelled() {
+                    break;
+                }
+                // 重试策略放弃了，稍后从头开始
+                let reason = last_error.unwrap_or_else(|| "连接结束".to_owned());
+                tracing::warn!(reason, "重试次数用完，稍后重新连接");
+                state_tx.send_replace(RoomState::Reconnecting { reason });
+                let delay = connector.config.retry.max_delay;
+                if task_cancel
+                    .run_until_cancelled(tokio::time::sleep(delay))
+                    .await
+                    .is_none()
+                {
+                    break;
+                }
+            }
+            // 关闭所有发送端，订阅者收到Closed
+            drop(sender);
+            task_kinds.lock().expect("room kinds poisoned").clear();
+            state_tx.send_replace(RoomState::Disconnected);
+            tracing::debug!("room task stopped");
+        };
+        let span = tracing::info_span!("room", roomid);
+        let mut tasks = JoinSet::new();
+        tasks.spawn(tracing::Instrument::instrument(task, span));
+        Self {
+            roomid,
+            capacity,
+            lag_policy: LagPolicy::default(),
+            lagged: Arc::default(),
+            sender: weak_sender,
+            kinds,
+            state,
+            live_status,
+            connector: original,
+            cancel,
+            tasks,
+        }
+    }
+
+    pub fn with_lag_policy(mut self, lag_policy: LagPolicy) -> Self {
+        self.lag_policy = lag_policy;
+        self
+    }
+
+    pub fn roomid(&self) -> u64 {
+        self.roomid
+    }
+
+    /// 所有订阅者因为跟不上而跳过的事件总数
+    pub fn lagged(&self) -> u64 {
+        self.lagged.load(Ordering::Relaxed)
+    }
+
+    /// 订阅之后的事件，重连不影响已有的订阅，后台任务已经停止时立即收到`Closed`
+    pub fn subscribe(&self) -> RoomReceiver {
+        let receiver = match self.sender.upgrade() {
+            Some(sender) => sender.subscribe(),
+            None => closed_receiver(),
+        };
+        self.receiver(receiver)
+    }
+
+    fn receiver(&self, receiver: broadcast::Receiver<Event>) -> RoomReceiver {
+        RoomReceiver {
+            receiver,
+            roomid: self.roomid,
+            lag_policy: self.lag_policy,
+            lagged: 0,
+            room_lagged: self.lagged.clone(),
+        }
+    }
+
+    /// 只订阅`T`类型的事件，其他类型的事件不会发给这个订阅者
+    pub fn subscribe_filtered<T: EventKind>(&self) -> FilteredReceiver<T> {
+        // 持有锁时检查，避免在后台任务清理之后插入新的发送端
+        let mut kinds = self.kinds.lock().expect("room kinds poisoned");
+        let receiver = if self.sender.strong_count() == 0 {
+            closed_receiver()
+        } else {
+            kinds
+                .entry(T::CMD)
+                .or_insert_with(|| broadcast::channel(self.capacity).0)
+                .subscribe()
+        };
+        FilteredReceiver {
+            receiver: self.receiver(receiver),
+            kind: PhantomData,
+        }
+    }
+
+    /// 当前[`subscribe`](Self::subscribe)的订阅者数量，不包括[`subscribe_filtered`](Self::subscribe_filtered)
+    pub fn receiver_count(&self) -> usize {
+        self.sender
+            .upgrade()
+            .map(|sender| sender.receiver_count())
+            .unwrap_or_default()
+    }
+
+    /// 当前的连接状态
+    pub fn state(&self) -> RoomState {
+        self.state.borrow().clone()
+    }
+
+    /// 订阅连接状态的变化
+    pub fn watch_state(&self) -> watch::Receiver<RoomState> {
+        self.state.clone()
+    }
+
+    /// 当前的直播状态
+    pub fn live_status(&self) -> LiveStatus {
+        *self.live_status.borrow()
+    }
+
+    /// 订阅直播状态的变化，重复的开播、下播消息不会触发变化
+    pub fn watch_live_status(&self) -> watch::Receiver<LiveStatus> {
+        self.live_status.clone()
+    }
+
+    /// 断开连接并等待后台任务结束，订阅者随后收到[`broadcast::error::RecvError::Closed`]
+    pub async fn close(self) {
+        self.disconnect().await;
+    }
+
+    /// 断开连接，返回启动时的[`Connector`]，可以用来重新启动
+    ///
+    /// 相当于从已连接转换到断开的状态，消耗`self`，断开后不能再订阅或者再次断开
+    pub async fn disconnect(mut self) -> Connector {
+        self.cancel.cancel();
+        while self.tasks.join_next().await.is_some() {}
+        self.connector.clone()
+    }
+}
+
+/// 发送端已经关闭的接收端
+fn closed_receiver() -> broadcast::Receiver<Event> {
+    broadcast::channel(1).1
+}
+
+/// 发给订阅了这个事件类型的订阅者，顺便清理没有订阅者的类型
+fn send_kind(kinds: &KindSenders, event: &Event) {
+    let mut kinds = kinds.lock().expect("room kinds poisoned");
+    let cmd = event.data.cmd();
+    if let Some(sender) = kinds.get(cmd) {
+        if sender.send(event.clone()).is_err() {
+            kinds.remove(cmd);
+        }
+    }
+}
+
+/// [`Room`]的订阅者，由[`Room::subscribe`]得到，跟不上时按[`LagPolicy`]处理，不会收到`Lagged`
+#[derive(Debug)]
+pub struct RoomReceiver {
+    receiver: broadcast::Receiver<Event>,
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    roomid: u64,
+    lag_policy: LagPolicy,
+    lagged: u64,
+    room_lagged: Arc<AtomicU64>,
+}
+
+impl RoomReceiver {
+    /// 下一个事件，后台任务停止或者按[`LagPolicy::Disconnect`]断开后返回`Closed`
+    pub async fn recv(&mut self) -> Result<Event, broadcast::error::RecvError> {
+        loop {
+            match self.receiver.recv().await {
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    self.lagged += skipped;
+                    self.room_lagged.fetch_add(skipped, Ordering::Relaxed);
+                    #[cfg(feature = "metrics")]
+                    metrics::counter!("bilive_danmaku_lagged_events_total", "roomid" => self.roomid.to_string())
+                        .increment(skipped);
+                    tracing::warn!(skipped, lag_policy = ?self.lag_policy, "订阅者跟不上，跳过了部分事件");
+                    if self.lag_policy == LagPolicy::Disconnect {
+                        self.receiver = closed_receiver();
+                        return Err(broadcast::error::RecvError::Closed);
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// 这个订阅者因为跟不上而跳过的事件数
+    pub fn lagged(&self) -> u64 {
+        self.lagged
+    }
+}
+
+/// 只收到一种事件的订阅者，由[`Room::subscribe_filtered`]得到
+#[derive(Debug)]
+pub struct FilteredReceiver<T> {
+    receiver: RoomReceiver,
+    kind: PhantomData<fn() -> T>,
+}
+
+impl<T: EventKind> FilteredReceiver<T> {
+    /// 下一个`T`类型的事件，需要时间戳等信息时使用[`recv_event`](Self::recv_event)
+    pub async fn recv(&mut self) -> Result<T, broadcast::error::RecvError> {
+        loop {
+            if let Some(event) = T::from_data(self.receiver.recv().await?.data) {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// 下一个事件，`data`一定是`T`类型
+    pub async fn recv_event(&mut self) -> Result<Event, broadcast::error::RecvError> {
+        self.receiver.recv().await
+    }
+
+    /// 这个订阅者因为跟不上而跳过的事件数
+    pub fn lagged(&self) -> u64 {
+        self.receiver.lagged()
+    }
+}
+
+impl Drop for Room {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
@@ -0,0 +1,125 @@
+//! 发送弹幕的队列
+//!
+//! b站限制同一直播间的发送间隔和单条弹幕的长度，[`DanmakuSender`]按顺序发送，自动分段、等待间隔，并在临时错误时重试
+//!```no_run,ignore
+//!use bilive_danmaku::{credential::Credential, sender::DanmakuSender};
+//!let sender = DanmakuSender::new(roomid, Credential::new(sessdata, bili_jct));
+//!sender.send("一条很长很长的弹幕会被拆成多条依次发送").await?;
+//!```
+use std::{sync::Arc, time::Duration};
+
+use futures_util::lock::Mutex;
+
+use crate::{credential::Credential, event::now_millis, http::HttpClient, InitError};
+
+/// 发送频率过快
+const CODE_TOO_FAST: i64 = 10030;
+
+/// 按顺序发送弹幕，克隆后共享同一个队列
+///
+/// # 说明
+/// - `interval` 两条弹幕之间的最小间隔，默认1秒
+/// - `max_len` 单条弹幕的最大字数，超过时按字拆分，默认20，直播间等级较高的用户可以设置为40
+/// - `max_retries` 临时错误（网络错误、5xx、发送频率过快）的重试次数，默认3
+#[derive(Debug, Clone)]
+pub struct DanmakuSender {
+    pub roomid: u64,
+    pub interval: Duration,
+    pub max_len: usize,
+    pub max_retries: u32,
+    credential: Credential,
+    client: HttpClient,
+    /// 上一次发送的时间
+    last_send: Arc<Mutex<u64>>,
+}
+
+impl DanmakuSender {
+    /// 默认使用[`HttpClient::shared`]，和其他接口共享限流
+    pub fn new(roomid: u64, credential: Credential) -> Self {
+        Self {
+            roomid,
+            interval: Duration::from_secs(1),
+            max_len: 20,
+            max_retries: 3,
+            credential,
+            client: HttpClient::shared().clone(),
+            last_send: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len.max(1);
+        self
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_client(mut self, client: HttpClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// 发送弹幕，超长时拆分为多条依次发送，返回发送的条数
+    ///
+    /// 并发调用时按获得队列的顺序发送，某一段失败时不再发送剩余的部分
+    pub async fn send(&self, text: &str) -> Result<usize, InitError> {
+        let chunks = split_message(text, self.max_len);
+        let mut last_send = self.last_send.lock().await;
+        for chunk in &chunks {
+            self.send_chunk(chunk, &mut last_send).await?;
+        }
+        Ok(chunks.len())
+    }
+
+    async fn send_chunk(&self, chunk: &str, last_send: &mut u64) -> Result<(), InitError> {
+        let interval = self.interval.as_millis() as u64;
+        let mut retries = 0;
+        loop {
+            let wait = (*last_send + interval).saturating_sub(now_millis());
+            if wait > 0 {
+                crate::connection::sleep(Duration::from_millis(wait)).await;
+            }
+            let result = self
+                .client
+                .send_danmaku(&self.credential, self.roomid, chunk)
+                .await;
+            *last_send = now_millis();
+            match result {
+                Err(e) if retries < self.max_retries && is_transient(&e) => {
+                    retries += 1;
+                    tracing::warn!(roomid = self.roomid, error = %e, retries, "发送弹幕失败，重试");
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+fn is_transient(error: &InitError) -> bool {
+    match error {
+        InitError::HttpError(_) => true,
+        InitError::HttpStatus(status) => {
+            status.is_server_error() || *status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        }
+        // 重复弹幕（10031）重试也会被拒绝，不重试
+        InitError::ApiError { code, .. } => *code == CODE_TOO_FAST,
+        _ => false,
+    }
+}
+
+/// 按字数拆分弹幕，空白的弹幕返回空列表
+pub fn split_message(text: &str, max_len: usize) -> Vec<String> {
+    let chars: Vec<char> = text.trim().chars().collect();
+    chars
+        .chunks(max_len.max(1))
+        .map(|chunk| chunk.iter().collect())
+        .collect()
+}
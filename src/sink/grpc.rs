@@ -0,0 +1,391 @@
+use std::{net::SocketAddr, pin::Pin};
+
+use futures_util::Stream;
+use tokio::sync::broadcast;
+use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+use tonic::{Request, Response, Status};
+
+use crate::{
+    event::{Event, EventData},
+    filter::EventFilter,
+    model::{BlockOperator, CoinType, DanmakuMessage, FansMedal, Gift, GiftType, PkRoom, User},
+};
+
+use super::SinkError;
+
+/// 由`proto/bilive_danmaku.proto`生成的消息类型和服务
+pub mod proto {
+    tonic::include_proto!("bilive_danmaku");
+}
+
+pub use proto::event_stream_server::EventStreamServer;
+
+/// 通过gRPC推送事件的服务端，消息格式见`proto/bilive_danmaku.proto`
+///
+/// 每种事件都有对应的消息类型，只有没有固定结构的[`CustomEvent`](crate::event::CustomEvent)的数据以json传输。
+/// 订阅者跟不上时会跳过积压的事件
+///
+/// # 说明
+/// - `filter` 只推送通过过滤的事件
+///
+///```no_run,ignore
+///use bilive_danmaku::sink::GrpcSink;
+///let sink = GrpcSink::new(1024);
+///tokio::spawn(sink.clone().serve("0.0.0.0:50051".parse()?));
+///while let Some(Ok(event)) = stream.next().await {
+///    sink.publish(roomid, &event)?;
+///}
+///```
+#[derive(Debug, Clone)]
+pub struct GrpcSink {
+    pub filter: Option<EventFilter>,
+    tx: broadcast::Sender<proto::Event>,
+}
+
+impl GrpcSink {
+    /// `capacity`为每个订阅者最多积压的事件数
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        Self { filter: None, tx }
+    }
+
+    pub fn with_filter(mut self, filter: EventFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// 推送事件，返回订阅者数量，被过滤的事件返回0
+    pub fn publish(&self, roomid: u64, event: &Event) -> Result<usize, SinkError> {
+        if self.filter.as_ref().is_some_and(|f| !f.accept(event)) {
+            return Ok(0);
+        }
+        let event = to_proto(roomid, event);
+        Ok(self.tx.send(event).unwrap_or_default())
+    }
+
+    /// 可以和其他服务一起挂载到[`tonic::transport::Server`]上
+    pub fn service(&self) -> EventStreamServer<Self> {
+        EventStreamServer::new(self.clone())
+    }
+
+    /// 在`addr`上启动只有这一个服务的gRPC服务器
+    pub async fn serve(self, addr: SocketAddr) -> Result<(), SinkError> {
+        tonic::transport::Server::builder()
+            .add_service(self.service())
+            .serve(addr)
+            .await?;
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl proto::event_stream_server::EventStream for GrpcSink {
+    type SubscribeStream = Pin<Box<dyn Stream<Item = Result<proto::Event, Status>> + Send>>;
+
+    async fn subscribe(
+        &self,
+        request: Request<proto::SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let proto::SubscribeRequest { roomids, cmds } = request.into_inner();
+        let stream = BroadcastStream::new(self.tx.subscribe()).filter_map(move |event| {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    tracing::warn!(error = %e, "gRPC订阅者跟不上，跳过了部分事件");
+                    return None;
+                }
+            };
+            let accepted = (roomids.is_empty() || roomids.contains(&event.roomid))
+                && (cmds.is_empty() || cmds.contains(&event.cmd));
+            accepted.then_some(Ok(event))
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+fn user_to_proto(user: &User) -> proto::User {
+    proto::User {
+        uid: user.uid,
+        uname: user.uname.clone(),
+        face: user.face.clone().unwrap_or_default(),
+    }
+}
+
+fn medal_to_proto(medal: &FansMedal) -> proto::FansMedal {
+    proto::FansMedal {
+        anchor_roomid: medal.anchor_roomid,
+        guard_level: medal.guard_level,
+        medal_level: medal.medal_level,
+        medal_name: medal.medal_name.clone(),
+        target_id: medal.target_id,
+        is_lighted: medal.is_lighted,
+    }
+}
+
+fn gift_type_to_proto(gift: &GiftType) -> proto::GiftType {
+    proto::GiftType {
+        action: gift.action.clone(),
+        gift_name: gift.gift_name.clone(),
+        gift_id: gift.gift_id,
+    }
+}
+
+fn gift_to_proto(
+    user: &User,
+    fans_medal: Option<&FansMedal>,
+    gift: &Gift,
+    blindbox: Option<&GiftType>,
+) -> proto::Gift {
+    proto::Gift {
+        user: Some(user_to_proto(user)),
+        fans_medal: fans_medal.map(medal_to_proto),
+        action: gift.action.clone(),
+        gift_id: gift.gift_id,
+        gift_name: gift.gift_name.clone(),
+        num: gift.num,
+        price: gift.price,
+        gold: gift.coin_type == CoinType::Gold,
+        coin_count: gift.coin_count,
+        blindbox: blindbox.map(gift_type_to_proto),
+    }
+}
+
+fn pk_room_to_proto(room: &PkRoom) -> proto::PkRoom {
+    proto::PkRoom {
+        roomid: room.roomid,
+        votes: room.votes,
+        best_uname: room.best_uname.clone(),
+    }
+}
+
+/// 转换为gRPC消息
+pub fn to_proto(roomid: u64, event: &Event) -> proto::Event {
+    use proto::event::Data;
+    let data = match &event.data {
+        EventData::DanmakuEvent(e) => Data::Danmaku(proto::Danmaku {
+            user: Some(user_to_proto(&e.user)),
+            fans_medal: e.fans_medal.as_ref().map(medal_to_proto),
+            message: match &e.message {
+                DanmakuMessage::Plain { message } => message.clone(),
+                DanmakuMessage::Emoticon { alt_message, .. } => alt_message.clone(),
+                DanmakuMessage::Voice { voice } => voice.text.clone(),
+            },
+            guard_level: e.guard_level,
+            user_level: e.user_level,
+            mode: e.mode,
+            font_size: e.font_size,
+            color: e.color,
+        }),
+        EventData::GiftEvent(e) => Data::Gift(gift_to_proto(
+            &e.user,
+            e.fans_medal.as_ref(),
+            &e.gift,
+            e.blindbox.as_ref(),
+        )),
+        EventData::BlindboxGiftEvent(e) => Data::BlindboxGift(gift_to_proto(
+            &e.user,
+            e.fans_medal.as_ref(),
+            &e.gift,
+            Some(&e.blindbox_gift_type),
+        )),
+        EventData::SuperChatEvent(e) => Data::SuperChat(proto::SuperChat {
+            id: e.id,
+            user: Some(user_to_proto(&e.user)),
+            fans_medal: e.fans_medal.as_ref().map(medal_to_proto),
+            price: e.price,
+            message: e.message.clone(),
+            message_jpn: e.message_jpn.clone().unwrap_or_default(),
+        }),
+        EventData::GuardBuyEvent(e) => Data::GuardBuy(proto::GuardBuy {
+            user: Some(user_to_proto(&e.user)),
+            level: e.level,
+            price: e.price,
+        }),
+        EventData::EnterRoomEvent(e) => Data::EnterRoom(proto::EnterRoom {
+            user: Some(user_to_proto(&e.user)),
+            fans_medal: e.fans_medal.as_ref().map(medal_to_proto),
+            guard_level: e.guard_level,
+            user_level: e.user_level,
+        }),
+        EventData::GuardEnterRoomEvent(e) => Data::GuardEnterRoom(proto::GuardEnterRoom {
+            user: Some(user_to_proto(&e.user)),
+            guard_level: e.guard_level,
+            copy_writing: e.copy_writing.clone(),
+        }),
+        EventData::WatchedUpdateEvent(e) => {
+            Data::WatchedUpdate(proto::WatchedUpdate { num: e.num })
+        }
+        EventData::PopularityUpdateEvent(e) => Data::PopularityUpdate(proto::PopularityUpdate {
+            popularity: e.popularity,
+        }),
+        EventData::HotRankChangedEvent(e) => Data::HotRankChanged(proto::HotRankChanged {
+            area: e.area.clone(),
+            rank: e.rank,
+            description: e.description.clone(),
+        }),
+        EventData::HotRankSettlementEvent(e) => Data::HotRankSettlement(proto::HotRankSettlement {
+            uname: e.uname.clone(),
+            face: e.face.clone(),
+            area: e.area.clone(),
+            rank: e.rank,
+        }),
+        EventData::StopLiveEvent(e) => Data::StopLive(proto::StopLive {
+            room_id_list: e.room_id_list.clone(),
+        }),
+        EventData::RedPocketStartEvent(e) => Data::RedPocketStart(proto::RedPocketStart {
+            lot_id: e.lot_id,
+            sender: Some(user_to_proto(&e.sender)),
+            total_price: e.total_price,
+            duration: e.duration,
+            danmu: e.danmu.clone(),
+            awards: e
+                .awards
+                .iter()
+                .map(|award| proto::RedPocketAward {
+                    gift_id: award.gift_id,
+                    gift_name: award.gift_name.clone(),
+                    num: award.num,
+                })
+                .collect(),
+        }),
+        EventData::RedPocketWinnerEvent(e) => Data::RedPocketWinner(proto::RedPocketResult {
+            lot_id: e.lot_id,
+            total_num: e.total_num,
+            winners: e
+                .winners
+                .iter()
+                .map(|winner| proto::RedPocketWinner {
+                    user: Some(user_to_proto(&winner.user)),
+                    gift_id: winner.gift_id,
+                    gift_name: winner.gift_name.clone(),
+                    gift_price: winner.gift_price,
+                })
+                .collect(),
+        }),
+        EventData::AnchorLotStartEvent(e) => Data::AnchorLotStart(proto::AnchorLotStart {
+            id: e.id,
+            award_name: e.award_name.clone(),
+            award_num: e.award_num,
+            require_text: e.require_text.clone(),
+            danmu: e.danmu.clone(),
+            gift: e.gift.as_ref().map(gift_type_to_proto),
+            duration: e.duration,
+        }),
+        EventData::AnchorLotEndEvent(e) => Data::AnchorLotEnd(proto::AnchorLotEnd { id: e.id }),
+        EventData::AnchorLotAwardEvent(e) => Data::AnchorLotAward(proto::AnchorLotAward {
+            id: e.id,
+            award_name: e.award_name.clone(),
+            award_num: e.award_num,
+            winners: e.winners.iter().map(user_to_proto).collect(),
+        }),
+        EventData::RoomChangeEvent(e) => Data::RoomChange(proto::RoomChange {
+            title: e.title.clone(),
+            area_id: e.area_id,
+            area_name: e.area_name.clone(),
+            parent_area_id: e.parent_area_id,
+            parent_area_name: e.parent_area_name.clone(),
+        }),
+        EventData::LiveStatusEvent(e) => Data::LiveStatus(proto::LiveStatus {
+            status: u8::from(e.status).into(),
+            live_time: e.live_time.unwrap_or_default(),
+        }),
+        EventData::LikeClickEvent(e) => Data::LikeClick(proto::LikeClick {
+            user: Some(user_to_proto(&e.user)),
+            fans_medal: e.fans_medal.as_ref().map(medal_to_proto),
+            text: e.text.clone(),
+        }),
+        EventData::LikeCountUpdateEvent(e) => {
+            Data::LikeCountUpdate(proto::LikeCountUpdate { count: e.count })
+        }
+        EventData::FansUpdateEvent(e) => Data::FansUpdate(proto::FansUpdate {
+            fans: e.fans,
+            fans_club: e.fans_club,
+        }),
+        EventData::DanmakuAggregationEvent(e) => {
+            Data::DanmakuAggregation(proto::DanmakuAggregation {
+                message: e.message.clone(),
+                count: e.count,
+                activity_id: e.activity_id.clone(),
+            })
+        }
+        EventData::OnlineRankUpdateEvent(e) => Data::OnlineRankUpdate(proto::OnlineRankUpdate {
+            rank_type: e.rank_type.clone(),
+            list: e
+                .list
+                .iter()
+                .map(|user| proto::OnlineRankUser {
+                    user: Some(user_to_proto(&user.user)),
+                    rank: user.rank,
+                    score: user.score,
+                    guard_level: user.guard_level,
+                })
+                .collect(),
+        }),
+        EventData::OnlineRankTop3Event(e) => Data::OnlineRankTop3(proto::OnlineRankTop3 {
+            messages: e.messages.clone(),
+        }),
+        EventData::NoticeEvent(e) => Data::Notice(proto::Notice {
+            msg_type: e.msg_type,
+            name: e.name.clone(),
+            template: e.template.clone(),
+            template_self: e.template_self.clone(),
+            roomid: e.roomid,
+            link_url: e.link_url.clone(),
+        }),
+        EventData::UserBlockedEvent(e) => Data::UserBlocked(proto::UserBlocked {
+            user: Some(user_to_proto(&e.user)),
+            operator: match e.operator {
+                BlockOperator::Admin => 1,
+                BlockOperator::Anchor => 2,
+                BlockOperator::Unknown(other) => other,
+            },
+        }),
+        EventData::PkBattlePreEvent(e) => Data::PkBattlePre(proto::PkBattlePre {
+            opponent: Some(user_to_proto(&e.opponent)),
+            opponent_roomid: e.opponent_roomid,
+            votes_name: e.votes_name.clone(),
+        }),
+        EventData::PkBattleStartEvent(e) => Data::PkBattleStart(proto::PkBattleStart {
+            init: Some(pk_room_to_proto(&e.init)),
+            matched: Some(pk_room_to_proto(&e.matched)),
+            start_time: e.start_time,
+            end_time: e.end_time,
+            votes_name: e.votes_name.clone(),
+        }),
+        EventData::PkBattleProcessEvent(e) => Data::PkBattleProcess(proto::PkBattleProcess {
+            init: Some(pk_room_to_proto(&e.init)),
+            matched: Some(pk_room_to_proto(&e.matched)),
+        }),
+        EventData::PkBattleEndEvent(e) => Data::PkBattleEnd(proto::PkBattleEnd {
+            init: Some(pk_room_to_proto(&e.init)),
+            matched: Some(pk_room_to_proto(&e.matched)),
+            winner: e.winner.unwrap_or_default(),
+        }),
+        EventData::DanmakuThrottledEvent(e) => Data::DanmakuThrottled(proto::DanmakuThrottled {
+            dropped: e.dropped,
+            window: e.window,
+        }),
+        EventData::HostSwitchEvent(e) => Data::HostSwitch(proto::HostSwitch {
+            from: e.from.clone(),
+            to: e.to.clone(),
+            reason: e.reason.clone(),
+        }),
+        EventData::StatsEvent(e) => Data::Stats(proto::Stats {
+            window: e.window,
+            danmaku_count: e.danmaku_count,
+            danmaku_per_minute: e.danmaku_per_minute,
+            unique_chatters: e.unique_chatters,
+            gift_gold: e.gift_gold,
+            super_chat_count: e.super_chat_count,
+            super_chat_price: e.super_chat_price,
+        }),
+        EventData::CustomEvent(e) => Data::Json(e.data.to_string()),
+    };
+    proto::Event {
+        roomid,
+        cmd: event.data.cmd().to_owned(),
+        timestamp: event.timestamp,
+        server_timestamp: event.server_timestamp.unwrap_or_default(),
+        data: Some(data),
+    }
+}
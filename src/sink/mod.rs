@@ -0,0 +1,117 @@
+//! 把事件转发到外部服务
+//!
+//! 每种sink由单独的feature启用
+use std::fmt::Display;
+
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "grpc")]
+pub use grpc::*;
+#[cfg(feature = "redis")]
+mod redis;
+#[cfg(feature = "redis")]
+pub use self::redis::*;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "sqlite")]
+pub use sqlite::*;
+#[cfg(feature = "sse")]
+mod sse;
+#[cfg(feature = "sse")]
+pub use sse::*;
+#[cfg(feature = "webhook")]
+mod webhook;
+#[cfg(feature = "webhook")]
+pub use webhook::*;
+
+#[derive(Debug)]
+pub enum SinkError {
+    Serialize(serde_json::Error),
+    #[cfg(feature = "grpc")]
+    Grpc(tonic::transport::Error),
+    #[cfg(feature = "sse")]
+    Io(std::io::Error),
+    #[cfg(feature = "webhook")]
+    HttpError(reqwest::Error),
+    /// http状态码不是2xx
+    #[cfg(feature = "webhook")]
+    HttpStatus(reqwest::StatusCode),
+    #[cfg(feature = "redis")]
+    Redis(::redis::RedisError),
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Error),
+}
+
+impl Display for SinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SinkError::Serialize(e) => write!(f, "序列化错误：{}", e),
+            #[cfg(feature = "grpc")]
+            SinkError::Grpc(e) => write!(f, "GrpcError: {}", e),
+            #[cfg(feature = "sse")]
+            SinkError::Io(e) => write!(f, "IoError: {}", e),
+            #[cfg(feature = "webhook")]
+            SinkError::HttpError(e) => write!(f, "HttpError: {}", e),
+            #[cfg(feature = "webhook")]
+            SinkError::HttpStatus(status) => write!(f, "HttpStatus: {}", status),
+            #[cfg(feature = "redis")]
+            SinkError::Redis(e) => write!(f, "RedisError: {}", e),
+            #[cfg(feature = "sqlite")]
+            SinkError::Sqlite(e) => write!(f, "SqliteError: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SinkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SinkError::Serialize(e) => Some(e),
+            #[cfg(feature = "grpc")]
+            SinkError::Grpc(e) => Some(e),
+            #[cfg(feature = "sse")]
+            SinkError::Io(e) => Some(e),
+            #[cfg(feature = "webhook")]
+            SinkError::HttpError(e) => Some(e),
+            #[cfg(feature = "webhook")]
+            SinkError::HttpStatus(_) => None,
+            #[cfg(feature = "redis")]
+            SinkError::Redis(e) => Some(e),
+            #[cfg(feature = "sqlite")]
+            SinkError::Sqlite(e) => Some(e),
+        }
+    }
+}
+
+impl From<serde_json::Error> for SinkError {
+    fn from(e: serde_json::Error) -> Self {
+        SinkError::Serialize(e)
+    }
+}
+
+#[cfg(feature = "grpc")]
+impl From<tonic::transport::Error> for SinkError {
+    fn from(e: tonic::transport::Error) -> Self {
+        SinkError::Grpc(e)
+    }
+}
+
+#[cfg(feature = "sse")]
+impl From<std::io::Error> for SinkError {
+    fn from(e: std::io::Error) -> Self {
+        SinkError::Io(e)
+    }
+}
+
+#[cfg(feature = "redis")]
+impl From<::redis::RedisError> for SinkError {
+    fn from(e: ::redis::RedisError) -> Self {
+        SinkError::Redis(e)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for SinkError {
+    fn from(e: rusqlite::Error) -> Self {
+        SinkError::Sqlite(e)
+    }
+}
@@ -0,0 +1,61 @@
+use redis::AsyncCommands;
+
+use crate::{event::Event, filter::EventFilter};
+
+use super::SinkError;
+
+/// 把事件序列化为json后发布到redis频道，每个直播间一个频道
+///
+/// # 说明
+/// - `channel_prefix` 频道名为`{channel_prefix}:{roomid}`，默认为`bilive_danmaku`
+/// - `filter` 只发布通过过滤的事件
+///
+///```no_run,ignore
+///use bilive_danmaku::sink::RedisSink;
+///let mut sink = RedisSink::connect("redis://127.0.0.1/").await?;
+///while let Some(Ok(event)) = stream.next().await {
+///    sink.publish(roomid, &event).await?;
+///}
+///```
+#[derive(Clone)]
+pub struct RedisSink {
+    pub channel_prefix: String,
+    pub filter: Option<EventFilter>,
+    conn: redis::aio::MultiplexedConnection,
+}
+
+impl RedisSink {
+    pub async fn connect(url: &str) -> Result<Self, SinkError> {
+        let client = redis::Client::open(url)?;
+        let conn = client.get_multiplexed_tokio_connection().await?;
+        Ok(Self {
+            channel_prefix: "bilive_danmaku".to_owned(),
+            filter: None,
+            conn,
+        })
+    }
+
+    pub fn with_channel_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.channel_prefix = prefix.into();
+        self
+    }
+
+    pub fn with_filter(mut self, filter: EventFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    pub fn channel(&self, roomid: u64) -> String {
+        format!("{}:{}", self.channel_prefix, roomid)
+    }
+
+    /// 发布事件，返回收到消息的订阅者数量，被过滤的事件返回0
+    pub async fn publish(&mut self, roomid: u64, event: &Event) -> Result<usize, SinkError> {
+        if self.filter.as_ref().is_some_and(|f| !f.accept(event)) {
+            return Ok(0);
+        }
+        let payload = serde_json::to_string(event)?;
+        let channel = self.channel(roomid);
+        Ok(self.conn.publish(channel, payload).await?)
+    }
+}
@@ -0,0 +1,183 @@
+use std::path::Path;
+
+use rusqlite::{params, Connection};
+
+use crate::{
+    event::{Event, EventData},
+    model::CoinType,
+};
+
+use super::SinkError;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS danmaku (
+    id INTEGER PRIMARY KEY,
+    roomid INTEGER NOT NULL,
+    uid INTEGER NOT NULL,
+    uname TEXT NOT NULL,
+    message TEXT NOT NULL,
+    medal_name TEXT,
+    medal_level INTEGER,
+    guard_level INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS danmaku_room_time ON danmaku (roomid, timestamp);
+CREATE INDEX IF NOT EXISTS danmaku_uid ON danmaku (uid);
+
+CREATE TABLE IF NOT EXISTS gift (
+    id INTEGER PRIMARY KEY,
+    roomid INTEGER NOT NULL,
+    uid INTEGER NOT NULL,
+    uname TEXT NOT NULL,
+    gift_id INTEGER NOT NULL,
+    gift_name TEXT NOT NULL,
+    num INTEGER NOT NULL,
+    price INTEGER NOT NULL,
+    coin_type TEXT NOT NULL,
+    coin_count INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS gift_room_time ON gift (roomid, timestamp);
+CREATE INDEX IF NOT EXISTS gift_uid ON gift (uid);
+
+CREATE TABLE IF NOT EXISTS super_chat (
+    id INTEGER PRIMARY KEY,
+    roomid INTEGER NOT NULL,
+    uid INTEGER NOT NULL,
+    uname TEXT NOT NULL,
+    price INTEGER NOT NULL,
+    message TEXT NOT NULL,
+    message_jpn TEXT,
+    timestamp INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS super_chat_room_time ON super_chat (roomid, timestamp);
+CREATE INDEX IF NOT EXISTS super_chat_uid ON super_chat (uid);
+
+CREATE TABLE IF NOT EXISTS guard (
+    id INTEGER PRIMARY KEY,
+    roomid INTEGER NOT NULL,
+    uid INTEGER NOT NULL,
+    uname TEXT NOT NULL,
+    level INTEGER NOT NULL,
+    price INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS guard_room_time ON guard (roomid, timestamp);
+CREATE INDEX IF NOT EXISTS guard_uid ON guard (uid);
+";
+
+/// 把弹幕、礼物、醒目留言和大航海写入sqlite数据库
+///
+/// # 说明
+/// - 每种事件一张表：`danmaku`，`gift`，`super_chat`，`guard`，都有`(roomid, timestamp)`和`uid`索引
+/// - `timestamp` 为收到事件时的毫秒时间戳
+/// - 其他事件不会写入
+///
+///```no_run,ignore
+///use bilive_danmaku::sink::SqliteSink;
+///let sink = SqliteSink::open("danmaku.db")?;
+///while let Some(Ok(event)) = stream.next().await {
+///    sink.write(roomid, &event)?;
+///}
+///```
+pub struct SqliteSink {
+    conn: Connection,
+}
+
+impl SqliteSink {
+    /// 打开数据库，不存在的表会被创建
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SinkError> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self, SinkError> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    pub fn with_connection(conn: Connection) -> Result<Self, SinkError> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    /// 用于查询已经写入的数据
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// 写入一个事件，返回是否写入
+    pub fn write(&self, roomid: u64, event: &Event) -> Result<bool, SinkError> {
+        insert(&self.conn, roomid, event)
+    }
+
+    /// 在一个事务中写入多个事件，返回写入的数量
+    pub fn write_all<'a>(
+        &mut self,
+        roomid: u64,
+        events: impl IntoIterator<Item = &'a Event>,
+    ) -> Result<usize, SinkError> {
+        let tx = self.conn.transaction()?;
+        let mut count = 0;
+        for event in events {
+            if insert(&tx, roomid, event)? {
+                count += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(count)
+    }
+}
+
+fn insert(conn: &Connection, roomid: u64, event: &Event) -> Result<bool, SinkError> {
+    let ts = event.timestamp;
+    let rows = match &event.data {
+        EventData::DanmakuEvent(e) => conn.execute(
+            "INSERT INTO danmaku (roomid, uid, uname, message, medal_name, medal_level, guard_level, timestamp) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                roomid,
+                e.user.uid,
+                e.user.uname,
+                e.message.to_string(),
+                e.fans_medal.as_ref().map(|m| m.medal_name.as_str()),
+                e.fans_medal.as_ref().map(|m| m.medal_level),
+                e.guard_level,
+                ts
+            ],
+        )?,
+        EventData::GiftEvent(e) => conn.execute(
+            "INSERT INTO gift (roomid, uid, uname, gift_id, gift_name, num, price, coin_type, coin_count, timestamp) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                roomid,
+                e.user.uid,
+                e.user.uname,
+                e.gift.gift_id,
+                e.gift.gift_name,
+                e.gift.num,
+                e.gift.price,
+                match e.gift.coin_type {
+                    CoinType::Silver => "silver",
+                    CoinType::Gold => "gold",
+                },
+                e.gift.coin_count,
+                ts
+            ],
+        )?,
+        EventData::SuperChatEvent(e) => conn.execute(
+            "INSERT INTO super_chat (roomid, uid, uname, price, message, message_jpn, timestamp) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                roomid,
+                e.user.uid,
+                e.user.uname,
+                e.price,
+                e.message,
+                e.message_jpn,
+                ts
+            ],
+        )?,
+        EventData::GuardBuyEvent(e) => conn.execute(
+            "INSERT INTO guard (roomid, uid, uname, level, price, timestamp) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![roomid, e.user.uid, e.user.uname, e.level, e.price, ts],
+        )?,
+        _ => 0,
+    };
+    Ok(rows > 0)
+}
@@ -0,0 +1,182 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use axum::{
+    extract::{Path, State},
+    response::sse::{self, KeepAlive, Sse},
+    routing::get,
+    Router,
+};
+use futures_util::Stream;
+use tokio::sync::broadcast;
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    StreamExt,
+};
+
+use crate::{event::Event, filter::EventFilter};
+
+use super::SinkError;
+
+/// 通过Server-Sent Events推送事件，路径为`/rooms/{roomid}/events`
+///
+/// 每条消息的`event`为事件类型（见[`EventData::cmd`](crate::event::EventData::cmd)），`data`为事件的json。
+/// 订阅者跟不上时会跳过积压的事件，直播间的最后一个订阅者断开后释放对应的通道
+///
+/// # 说明
+/// - `filter` 只推送通过过滤的事件
+///
+///```no_run,ignore
+///use bilive_danmaku::sink::SseSink;
+///let sink = SseSink::new(1024);
+///tokio::spawn(sink.clone().serve("0.0.0.0:8080".parse()?));
+///while let Some(Ok(event)) = stream.next().await {
+///    sink.publish(roomid, &event)?;
+///}
+///```
+#[derive(Debug, Clone)]
+pub struct SseSink {
+    pub filter: Option<EventFilter>,
+    capacity: usize,
+    rooms: Arc<Mutex<HashMap<u64, broadcast::Sender<SseMessage>>>>,
+}
+
+#[derive(Debug, Clone)]
+struct SseMessage {
+    cmd: &'static str,
+    data: Arc<str>,
+}
+
+impl SseSink {
+    /// `capacity`为每个订阅者最多积压的事件数
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            filter: None,
+            capacity: capacity.max(1),
+            rooms: Default::default(),
+        }
+    }
+
+    pub fn with_filter(mut self, filter: EventFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// 推送事件，返回订阅者数量，被过滤的事件返回0
+    pub fn publish(&self, roomid: u64, event: &Event) -> Result<usize, SinkError> {
+        if self.filter.as_ref().is_some_and(|f| !f.accept(event)) {
+            return Ok(0);
+        }
+        let Some(tx) = self
+            .rooms
+            .lock()
+            .expect("sse rooms poisoned")
+            .get(&roomid)
+            .cloned()
+        else {
+            return Ok(0);
+        };
+        let message = SseMessage {
+            cmd: event.data.cmd(),
+            data: serde_json::to_string(event)?.into(),
+        };
+        let count = tx.send(message).unwrap_or_default();
+        if count == 0 {
+            self.release(roomid);
+        }
+        Ok(count)
+    }
+
+    /// 当前有订阅者的直播间数量
+    pub fn room_count(&self) -> usize {
+        self.rooms.lock().expect("sse rooms poisoned").len()
+    }
+
+    fn subscribe(&self, roomid: u64) -> Subscription {
+        let rx = self
+            .rooms
+            .lock()
+            .expect("sse rooms poisoned")
+            .entry(roomid)
+            .or_insert_with(|| broadcast::channel(self.capacity).0)
+            .subscribe();
+        Subscription {
+            sink: self.clone(),
+            roomid,
+            stream: Some(BroadcastStream::new(rx)),
+        }
+    }
+
+    /// 直播间已经没有订阅者时移除对应的通道
+    fn release(&self, roomid: u64) {
+        let mut rooms = self.rooms.lock().expect("sse rooms poisoned");
+        if rooms
+            .get(&roomid)
+            .is_some_and(|tx| tx.receiver_count() == 0)
+        {
+            rooms.remove(&roomid);
+        }
+    }
+
+    /// 可以合并到其他的[`Router`]中
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/rooms/:roomid/events", get(events))
+            .with_state(self.clone())
+    }
+
+    /// 在`addr`上启动只有这一个路由的http服务器
+    pub async fn serve(self, addr: SocketAddr) -> Result<(), SinkError> {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        axum::serve(listener, self.router()).await?;
+        Ok(())
+    }
+}
+
+async fn events(
+    State(sink): State<SseSink>,
+    Path(roomid): Path<u64>,
+) -> Sse<impl Stream<Item = Result<sse::Event, Infallible>>> {
+    let stream = sink
+        .subscribe(roomid)
+        .filter_map(move |message| match message {
+            Ok(SseMessage { cmd, data }) => Some(Ok(sse::Event::default().event(cmd).data(&*data))),
+            Err(e) => {
+                tracing::warn!(roomid, error = %e, "SSE订阅者跟不上，跳过了部分事件");
+                None
+            }
+        });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// 一个SSE连接的订阅，断开时如果是直播间的最后一个订阅者则移除对应的通道
+struct Subscription {
+    sink: SseSink,
+    roomid: u64,
+    stream: Option<BroadcastStream<SseMessage>>,
+}
+
+impl Stream for Subscription {
+    type Item = Result<SseMessage, BroadcastStreamRecvError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.stream.as_mut() {
+            Some(stream) => Pin::new(stream).poll_next(cx),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        // 先释放接收端，再检查订阅者数量
+        self.stream.take();
+        self.sink.release(self.roomid);
+    }
+}
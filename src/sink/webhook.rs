@@ -0,0 +1,210 @@
+use std::time::Duration;
+
+use futures_util::{future::Either, Stream, StreamExt};
+
+use crate::{
+    connection::sleep,
+    event::{now_millis, Event},
+    filter::EventFilter,
+};
+
+use super::SinkError;
+
+/// 把事件以json数组的形式POST到指定的地址
+///
+/// # 说明
+/// - `batch_size` 攒够这么多事件后发送一次
+/// - `max_delay` 最早的事件等待超过这个时间后发送，[`forward`](Self::forward)按时发送，直接调用[`send`](Self::send)时在下一次调用时发送
+/// - `max_retries` 网络错误或5xx时的重试次数，重试间隔从`retry_interval`开始翻倍
+/// - `max_buffered` 缓存的事件数上限，默认为10000，服务一直不可用时丢弃最早的事件，丢弃的数量见[`dropped`](Self::dropped)
+/// - `filter` 只转发通过过滤的事件
+///
+///```no_run,ignore
+///use bilive_danmaku::sink::WebhookSink;
+///let mut sink = WebhookSink::new("http://localhost:8080/events").with_batch_size(20);
+///sink.forward(stream.filter_map(|event| async move { event.ok() }).boxed()).await?;
+///```
+#[derive(Debug)]
+pub struct WebhookSink {
+    pub url: String,
+    pub batch_size: usize,
+    pub max_delay: Duration,
+    pub max_retries: u32,
+    pub retry_interval: Duration,
+    pub max_buffered: usize,
+    pub filter: Option<EventFilter>,
+    client: reqwest::Client,
+    buffer: Vec<Event>,
+    first_buffered_at: u64,
+    dropped: u64,
+}
+
+impl WebhookSink {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            batch_size: 1,
+            max_delay: Duration::from_secs(5),
+            max_retries: 3,
+            retry_interval: Duration::from_millis(500),
+            max_buffered: 10000,
+            filter: None,
+            client: reqwest::Client::new(),
+            buffer: vec![],
+            first_buffered_at: 0,
+            dropped: 0,
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn with_retry(mut self, max_retries: u32, retry_interval: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    pub fn with_max_buffered(mut self, max_buffered: usize) -> Self {
+        self.max_buffered = max_buffered.max(1);
+        self
+    }
+
+    pub fn with_filter(mut self, filter: EventFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// 因为缓存已满或者服务拒绝而丢弃的事件数
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// 转发事件流直到结束，最早的事件等待超过`max_delay`时即使没有新事件也会发送
+    ///
+    /// 发送失败只记录日志并继续转发，事件流结束时发送剩余的事件并返回结果
+    pub async fn forward<S>(&mut self, mut events: S) -> Result<(), SinkError>
+    where
+        S: Stream<Item = Event> + Unpin,
+    {
+        loop {
+            let next = if self.buffer.is_empty() {
+                events.next().await
+            } else {
+                let waited =
+                    Duration::from_millis(now_millis().saturating_sub(self.first_buffered_at));
+                let timer = std::pin::pin!(sleep(self.max_delay.saturating_sub(waited)));
+                match futures_util::future::select(events.next(), timer).await {
+                    Either::Left((next, _)) => next,
+                    Either::Right(_) => {
+                        if let Err(e) = self.flush().await {
+                            tracing::warn!(error = %e, "webhook发送失败");
+                        }
+                        continue;
+                    }
+                }
+            };
+            let Some(event) = next else {
+                return self.flush().await;
+            };
+            if let Err(e) = self.send(event).await {
+                tracing::warn!(error = %e, "webhook发送失败");
+            }
+        }
+    }
+
+    /// 缓存事件，满足发送条件时发送
+    pub async fn send(&mut self, event: Event) -> Result<(), SinkError> {
+        if self.filter.as_ref().is_some_and(|f| !f.accept(&event)) {
+            return Ok(());
+        }
+        if self.buffer.is_empty() {
+            self.first_buffered_at = now_millis();
+        }
+        self.buffer.push(event);
+        if self.buffer.len() > self.max_buffered {
+            let overflow = self.buffer.len() - self.max_buffered;
+            self.buffer.drain(..overflow);
+            self.dropped += overflow as u64;
+            tracing::warn!(
+                overflow,
+                dropped = self.dropped,
+                "webhook缓存已满，丢弃最早的事件"
+            );
+        }
+        let waited = now_millis().saturating_sub(self.first_buffered_at);
+        if self.buffer.len() >= self.batch_size || waited >= self.max_delay.as_millis() as u64 {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// 发送缓存中的所有事件
+    ///
+    /// 可以重试的错误重试全部失败时事件仍留在缓存中，`max_delay`之后再次发送；
+    /// 其他错误（例如4xx）重试也不会成功，丢弃这一批事件
+    #[tracing::instrument(level = "debug", skip(self), fields(url = %self.url, len = self.buffer.len()))]
+    pub async fn flush(&mut self) -> Result<(), SinkError> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let body = serde_json::to_vec(&self.buffer)?;
+        let mut interval = self.retry_interval;
+        let mut retries = 0;
+        loop {
+            match self.post(body.clone()).await {
+                Ok(()) => {
+                    self.buffer.clear();
+                    return Ok(());
+                }
+                Err(e) if retries < self.max_retries && is_retryable(&e) => {
+                    tracing::warn!(error = %e, retries, "webhook发送失败，稍后重试");
+                    sleep(interval).await;
+                    interval *= 2;
+                    retries += 1;
+                }
+                Err(e) if is_retryable(&e) => {
+                    self.first_buffered_at = now_millis();
+                    return Err(e);
+                }
+                Err(e) => {
+                    self.dropped += self.buffer.len() as u64;
+                    tracing::warn!(error = %e, len = self.buffer.len(), "webhook拒绝了这批事件，丢弃");
+                    self.buffer.clear();
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    async fn post(&self, body: Vec<u8>) -> Result<(), SinkError> {
+        let resp = self
+            .client
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+            .await
+            .map_err(SinkError::HttpError)?;
+        let status = resp.status();
+        if !status.is_success() {
+            return Err(SinkError::HttpStatus(status));
+        }
+        Ok(())
+    }
+}
+
+fn is_retryable(e: &SinkError) -> bool {
+    match e {
+        SinkError::HttpError(_) => true,
+        SinkError::HttpStatus(status) => status.is_server_error(),
+        _ => false,
+    }
+}
@@ -0,0 +1,7 @@
+//! 从事件流中统计直播间的数据
+mod popularity;
+pub use popularity::*;
+mod rolling;
+pub use rolling::*;
+mod revenue;
+pub use revenue::*;
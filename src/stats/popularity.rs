@@ -0,0 +1,144 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::event::{now_millis, Event, EventData};
+
+/// 人气值的时间序列
+///
+/// 按`resolution`把[`PopularityUpdateEvent`](crate::event::PopularityUpdateEvent)合并为采样点，只保留最近`retention`内的采样点
+///
+/// # 说明
+/// - `resolution` 每个采样点覆盖的时长，最小为1毫秒
+/// - `retention` 保留的时长，超出的采样点会被丢弃
+///
+///```no_run,ignore
+///use bilive_danmaku::stats::PopularityHistory;
+///let mut history = PopularityHistory::new(Duration::from_secs(60), Duration::from_secs(3600));
+///while let Some(Ok(event)) = stream.next().await {
+///    history.push(&event);
+///}
+///let summary = history.summary(Duration::from_secs(600));
+///```
+#[derive(Debug, Clone)]
+pub struct PopularityHistory {
+    resolution: u64,
+    retention: u64,
+    samples: VecDeque<PopularitySample>,
+}
+
+/// 一个采样点内的人气值
+///
+/// # 说明
+/// - `timestamp` 采样点的开始时间，毫秒
+/// - `count` 采样点内收到的人气值数量
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PopularitySample {
+    pub timestamp: u64,
+    pub min: u32,
+    pub max: u32,
+    pub sum: u64,
+    pub count: u64,
+}
+
+impl PopularitySample {
+    pub fn avg(&self) -> f64 {
+        self.sum as f64 / self.count as f64
+    }
+}
+
+/// 一段时间内人气值的统计
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PopularitySummary {
+    pub min: u32,
+    pub max: u32,
+    pub avg: f64,
+    pub count: u64,
+}
+
+impl PopularityHistory {
+    pub fn new(resolution: Duration, retention: Duration) -> Self {
+        let resolution = (resolution.as_millis() as u64).max(1);
+        let retention = retention.as_millis() as u64;
+        Self {
+            resolution,
+            retention,
+            samples: VecDeque::with_capacity((retention / resolution) as usize + 1),
+        }
+    }
+
+    /// 记录人气值事件，返回是否是人气值事件
+    pub fn push(&mut self, event: &Event) -> bool {
+        match &event.data {
+            EventData::PopularityUpdateEvent(e) => {
+                self.record(event.timestamp, e.popularity);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 记录`timestamp`（毫秒）时的人气值，早于保留范围的值会被忽略
+    pub fn record(&mut self, timestamp: u64, popularity: u32) {
+        let start = timestamp - timestamp % self.resolution;
+        match self.samples.iter_mut().rev().find(|s| s.timestamp <= start) {
+            Some(sample) if sample.timestamp == start => {
+                sample.min = sample.min.min(popularity);
+                sample.max = sample.max.max(popularity);
+                sample.sum += popularity as u64;
+                sample.count += 1;
+                return;
+            }
+            _ => {}
+        }
+        let sample = PopularitySample {
+            timestamp: start,
+            min: popularity,
+            max: popularity,
+            sum: popularity as u64,
+            count: 1,
+        };
+        let index = self.samples.partition_point(|s| s.timestamp < start);
+        self.samples.insert(index, sample);
+        let latest = self.samples.back().map_or(start, |s| s.timestamp);
+        while let Some(front) = self.samples.front() {
+            if latest.saturating_sub(front.timestamp) < self.retention {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// 按时间排序的采样点
+    pub fn samples(&self) -> impl Iterator<Item = &PopularitySample> {
+        self.samples.iter()
+    }
+
+    /// 最近一次收到的人气值所在的采样点
+    pub fn latest(&self) -> Option<&PopularitySample> {
+        self.samples.back()
+    }
+
+    /// 最近`last`内的统计，没有采样点时为`None`
+    pub fn summary(&self, last: Duration) -> Option<PopularitySummary> {
+        self.summary_since(now_millis().saturating_sub(last.as_millis() as u64))
+    }
+
+    /// 开始时间不早于`timestamp`（毫秒）所在采样点的统计
+    pub fn summary_since(&self, timestamp: u64) -> Option<PopularitySummary> {
+        let start = timestamp - timestamp % self.resolution;
+        let index = self.samples.partition_point(|s| s.timestamp < start);
+        let mut samples = self.samples.range(index..);
+        let first = *samples.next()?;
+        let (min, max, sum, count) = samples.fold(
+            (first.min, first.max, first.sum, first.count),
+            |(min, max, sum, count), s| {
+                (min.min(s.min), max.max(s.max), sum + s.sum, count + s.count)
+            },
+        );
+        Some(PopularitySummary {
+            min,
+            max,
+            avg: sum as f64 / count as f64,
+            count,
+        })
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use crate::{
+    event::{Event, EventData},
+    model::User,
+};
+
+/// 1元对应的金瓜子数
+pub const GOLD_PER_CNY: u64 = 1000;
+
+/// 金瓜子换算为元
+pub fn gold_to_cny(gold: u64) -> f64 {
+    gold as f64 / GOLD_PER_CNY as f64
+}
+
+/// 事件带来的收入，单位为金瓜子，不是收入类事件时为`None`
+///
+/// # 说明
+/// - 礼物和盲盒礼物按`price * num`计算，银瓜子礼物为0
+/// - 大航海的`price`本身就是金瓜子
+/// - 醒目留言的`price`是元，换算为金瓜子
+pub fn event_value(data: &EventData) -> Option<u64> {
+    match data {
+        EventData::GiftEvent(e) => Some(e.gift.gold_value()),
+        EventData::BlindboxGiftEvent(e) => Some(e.gift.gold_value()),
+        EventData::GuardBuyEvent(e) => Some(e.price),
+        EventData::SuperChatEvent(e) => Some(e.price * GOLD_PER_CNY),
+        _ => None,
+    }
+}
+
+/// 按来源分类的收入，单位为金瓜子
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Revenue {
+    pub gift: u64,
+    pub guard: u64,
+    pub super_chat: u64,
+}
+
+impl Revenue {
+    pub fn total(&self) -> u64 {
+        self.gift + self.guard + self.super_chat
+    }
+
+    pub fn cny(&self) -> f64 {
+        gold_to_cny(self.total())
+    }
+
+    fn add(&mut self, data: &EventData, gold: u64) {
+        match data {
+            EventData::GuardBuyEvent(_) => self.guard += gold,
+            EventData::SuperChatEvent(_) => self.super_chat += gold,
+            _ => self.gift += gold,
+        }
+    }
+}
+
+/// 一个用户的收入贡献
+#[derive(Debug, Clone)]
+pub struct UserRevenue {
+    pub user: User,
+    pub revenue: Revenue,
+}
+
+/// 统计一场直播的收入，包括总收入和每个用户的贡献
+///
+/// 开始新的一场直播时调用[`reset`](Self::reset)
+///
+///```no_run,ignore
+///use bilive_danmaku::stats::RevenueTracker;
+///let mut revenue = RevenueTracker::new();
+///while let Some(Ok(event)) = stream.next().await {
+///    revenue.push(&event);
+///}
+///println!("收入{:.2}元", revenue.total().cny());
+///```
+#[derive(Debug, Clone, Default)]
+pub struct RevenueTracker {
+    total: Revenue,
+    users: HashMap<u64, UserRevenue>,
+}
+
+impl RevenueTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录事件，返回事件带来的收入，不是收入类事件时为`None`
+    pub fn push(&mut self, event: &Event) -> Option<u64> {
+        let gold = event_value(&event.data)?;
+        let user = event.data.user()?;
+        self.total.add(&event.data, gold);
+        self.users
+            .entry(user.uid)
+            .or_insert_with(|| UserRevenue {
+                user: user.clone(),
+                revenue: Revenue::default(),
+            })
+            .revenue
+            .add(&event.data, gold);
+        Some(gold)
+    }
+
+    pub fn total(&self) -> Revenue {
+        self.total
+    }
+
+    pub fn user(&self, uid: u64) -> Option<&UserRevenue> {
+        self.users.get(&uid)
+    }
+
+    /// 贡献最多的`n`个用户，按总收入从大到小排列
+    pub fn top_users(&self, n: usize) -> Vec<&UserRevenue> {
+        let mut users: Vec<_> = self.users.values().collect();
+        users.sort_by_key(|u| std::cmp::Reverse(u.revenue.total()));
+        users.truncate(n);
+        users
+    }
+
+    /// 清空统计，返回清空前的总收入
+    pub fn reset(&mut self) -> Revenue {
+        self.users.clear();
+        std::mem::take(&mut self.total)
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use crate::{
+    event::{now_millis, Event, EventData, StatsEvent},
+    model::CoinType,
+};
+
+/// 滑动窗口内的弹幕、礼物和醒目留言统计
+///
+/// 按事件的`timestamp`计算窗口，窗口外的记录在下一次记录或统计时丢弃
+///
+/// # 说明
+/// - `window` 窗口时长
+/// - `emit_interval` 设置后，[`push`](Self::push)每隔这段时间返回一个[`StatsEvent`]，间隔由收到的事件驱动，没有事件时不会产生
+///
+///```no_run,ignore
+///use bilive_danmaku::stats::RollingStats;
+///let mut stats = RollingStats::new(Duration::from_secs(300)).with_emit_interval(Duration::from_secs(60));
+///while let Some(Ok(event)) = stream.next().await {
+///    if let Some(stats_event) = stats.push(&event) {
+///        println!("{stats_event}");
+///    }
+///}
+///```
+#[derive(Debug, Clone)]
+pub struct RollingStats {
+    window: u64,
+    emit_interval: Option<u64>,
+    last_emit: Option<u64>,
+    /// (时间, uid)
+    danmaku: VecDeque<(u64, u64)>,
+    chatters: HashMap<u64, u64>,
+    /// (时间, 金瓜子)
+    gifts: VecDeque<(u64, u64)>,
+    gift_gold: u64,
+    /// (时间, 元)
+    super_chats: VecDeque<(u64, u64)>,
+    super_chat_price: u64,
+}
+
+impl RollingStats {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window: window.as_millis() as u64,
+            emit_interval: None,
+            last_emit: None,
+            danmaku: VecDeque::new(),
+            chatters: HashMap::new(),
+            gifts: VecDeque::new(),
+            gift_gold: 0,
+            super_chats: VecDeque::new(),
+            super_chat_price: 0,
+        }
+    }
+
+    pub fn with_emit_interval(mut self, interval: Duration) -> Self {
+        self.emit_interval = Some(interval.as_millis() as u64);
+        self
+    }
+
+    /// 记录事件，到达`emit_interval`时返回统计事件
+    pub fn push(&mut self, event: &Event) -> Option<Event> {
+        let timestamp = event.timestamp;
+        match &event.data {
+            EventData::DanmakuEvent(e) => {
+                self.danmaku.push_back((timestamp, e.user.uid));
+                *self.chatters.entry(e.user.uid).or_default() += 1;
+            }
+            EventData::GiftEvent(e) if e.gift.coin_type == CoinType::Gold => {
+                let gold = e.gift.gold_value();
+                self.gifts.push_back((timestamp, gold));
+                self.gift_gold += gold;
+            }
+            EventData::SuperChatEvent(e) => {
+                self.super_chats.push_back((timestamp, e.price));
+                self.super_chat_price += e.price;
+            }
+            _ => {}
+        }
+        self.evict(timestamp);
+        let interval = self.emit_interval?;
+        let last_emit = *self.last_emit.get_or_insert(timestamp);
+        if timestamp.saturating_sub(last_emit) < interval {
+            return None;
+        }
+        self.last_emit = Some(timestamp);
+        Some(Event {
+            timestamp,
+            ..EventData::from(self.snapshot_at(timestamp)).into()
+        })
+    }
+
+    /// 当前时间之前`window`内的统计
+    pub fn snapshot(&mut self) -> StatsEvent {
+        self.snapshot_at(now_millis())
+    }
+
+    /// `timestamp`（毫秒）之前`window`内的统计
+    pub fn snapshot_at(&mut self, timestamp: u64) -> StatsEvent {
+        self.evict(timestamp);
+        let danmaku_count = self.danmaku.len() as u64;
+        let minutes = self.window as f64 / 60_000.0;
+        StatsEvent {
+            window: self.window / 1000,
+            danmaku_count,
+            danmaku_per_minute: if minutes > 0.0 {
+                danmaku_count as f64 / minutes
+            } else {
+                0.0
+            },
+            unique_chatters: self.chatters.len() as u64,
+            gift_gold: self.gift_gold,
+            super_chat_count: self.super_chats.len() as u64,
+            super_chat_price: self.super_chat_price,
+        }
+    }
+
+    fn evict(&mut self, now: u64) {
+        let expired = |timestamp: u64| now.saturating_sub(timestamp) >= self.window;
+        while let Some(&(timestamp, uid)) = self.danmaku.front() {
+            if !expired(timestamp) {
+                break;
+            }
+            self.danmaku.pop_front();
+            if let Some(count) = self.chatters.get_mut(&uid) {
+                *count -= 1;
+                if *count == 0 {
+                    self.chatters.remove(&uid);
+                }
+            }
+        }
+        while let Some(&(timestamp, gold)) = self.gifts.front() {
+            if !expired(timestamp) {
+                break;
+            }
+            self.gifts.pop_front();
+            self.gift_gold -= gold;
+        }
+        while let Some(&(timestamp, price)) = self.super_chats.front() {
+            if !expired(timestamp) {
+                break;
+            }
+            self.super_chats.pop_front();
+            self.super_chat_price -= price;
+        }
+    }
+}
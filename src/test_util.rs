@@ -0,0 +1,368 @@
+//! 测试工具
+//!
+//! [`MockServer`]是一个本地的弹幕服务器，按b站的数据包格式回复鉴权包和心跳包，并依次推送预先准备好的数据帧，
+//! 集成测试不需要连接真实的直播间
+//!```no_run,ignore
+//!use bilive_danmaku::test_util::{brotli_frame, MockServer};
+//!let frame = brotli_frame(&[serde_json::json!({ "cmd": "WATCHED_CHANGE", "data": { "num": 1 } })]);
+//!let server = MockServer::start(vec![frame]).await?;
+//!let mut stream = server.connect(&Default::default()).await?;
+//!```
+//!
+//! 同时会在另一个端口上回复http请求，通过[`MockServer::http_client`]访问，测试调用b站接口的代码
+//!```no_run,ignore
+//!server.respond("/x/web-interface/card", MockResponse::data(&serde_json::json!({ "card": card })));
+//!let profile = server.http_client().user_profile(1).await?;
+//!assert_eq!(server.requests("/x/web-interface/card"), 1);
+//!```
+//!
+//! 检查抓取到的原始消息的[`check_corpus`]只需要`protocol`，在[`corpus`](crate::corpus)模块中
+use std::{
+    io::Write,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{
+    connection::{ConnectConfig, Connection, WsConnectError},
+    http::HttpClient,
+    packet::{Auth, Operation, RawPacket},
+    Connector,
+};
+
+/// 本地弹幕服务器，drop时关闭
+///
+/// # 说明
+/// - 每个连接都会收到全部的`frames`
+/// - 心跳回复中的人气值固定为`popularity`
+/// - http请求按[`respond`](Self::respond)设置的路径回复，没有设置的路径回复404
+#[derive(Debug)]
+pub struct MockServer {
+    addr: SocketAddr,
+    http_addr: SocketAddr,
+    routes: Routes,
+    requests: Arc<Mutex<Vec<String>>>,
+    handle: tokio::task::JoinHandle<()>,
+    http_handle: tokio::task::JoinHandle<()>,
+}
+
+type Routes = Arc<Mutex<Vec<(String, MockResponse)>>>;
+
+/// [`MockServer`]对http请求的回复
+///
+/// # 说明
+/// - `delay` 回复前等待的时间，用来模拟慢请求
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    pub delay: Option<Duration>,
+}
+
+impl MockResponse {
+    /// 状态码为200
+    pub fn new(body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: 200,
+            headers: vec![],
+            body: body.into(),
+            delay: None,
+        }
+    }
+
+    /// b站接口格式的成功响应，`data`字段为`data`
+    pub fn data(data: &Value) -> Self {
+        Self::new(serde_json::json!({ "code": 0, "message": "0", "data": data }).to_string())
+            .with_header("Content-Type", "application/json")
+    }
+
+    /// b站接口格式的失败响应，http状态码仍然为200
+    pub fn api_error(code: i64, message: &str) -> Self {
+        Self::new(serde_json::json!({ "code": code, "message": message }).to_string())
+            .with_header("Content-Type", "application/json")
+    }
+
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+}
+
+impl MockServer {
+    pub const ROOMID: u64 = 1;
+
+    /// 在随机端口上启动，需要在tokio运行时中调用
+    pub async fn start(frames: Vec<Vec<u8>>) -> std::io::Result<Self> {
+        Self::start_with_popularity(frames, 1).await
+    }
+
+    pub async fn start_with_popularity(
+        frames: Vec<Vec<u8>>,
+        popularity: u32,
+    ) -> std::io::Result<Self> {
+        Self::start_inner(frames, popularity, false).await
+    }
+
+    /// 发送完`frames`后直接关闭TCP连接，不发送Close帧，模拟网络中断
+    pub async fn start_disconnecting(frames: Vec<Vec<u8>>) -> std::io::Result<Self> {
+        Self::start_inner(frames, 1, true).await
+    }
+
+    async fn start_inner(
+        frames: Vec<Vec<u8>>,
+        popularity: u32,
+        disconnect: bool,
+    ) -> std::io::Result<Self> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let handle = tokio::spawn(async move {
+            while let Ok((tcp, _)) = listener.accept().await {
+                let frames = frames.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = serve(tcp, frames, popularity, disconnect).await {
+                        tracing::debug!(error = %e, "mock server connection closed");
+                    }
+                });
+            }
+        });
+        let http_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let http_addr = http_listener.local_addr()?;
+        let routes = Routes::default();
+        let requests = Arc::<Mutex<Vec<String>>>::default();
+        let http_handle = tokio::spawn({
+            let routes = routes.clone();
+            let requests = requests.clone();
+            async move {
+                while let Ok((tcp, _)) = http_listener.accept().await {
+                    let routes = routes.clone();
+                    let requests = requests.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = serve_http(tcp, routes, requests).await {
+                            tracing::debug!(error = %e, "mock http connection closed");
+                        }
+                    });
+                }
+            }
+        });
+        Ok(Self {
+            addr,
+            http_addr,
+            routes,
+            requests,
+            handle,
+            http_handle,
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn url(&self) -> String {
+        format!("ws://{}/sub", self.addr)
+    }
+
+    /// http服务的地址，例如`http://127.0.0.1:12345`
+    pub fn http_url(&self) -> String {
+        format!("http://{}", self.http_addr)
+    }
+
+    /// 所有请求都发到这个服务器的[`HttpClient`]，见[`HttpClient::with_base_url`]
+    pub fn http_client(&self) -> HttpClient {
+        HttpClient::new().with_base_url(self.http_url())
+    }
+
+    /// 路径（包括查询参数）以`prefix`开头的http请求回复`response`，同时匹配多个时使用最长的`prefix`，
+    /// 同一个`prefix`重复设置时替换之前的回复
+    pub fn respond(&self, prefix: impl Into<String>, response: MockResponse) {
+        let prefix = prefix.into();
+        let mut routes = self.routes.lock().expect("mock routes poisoned");
+        routes.retain(|(route, _)| *route != prefix);
+        routes.push((prefix, response));
+    }
+
+    /// 路径（包括查询参数）以`prefix`开头的http请求数，包括回复404的请求
+    pub fn requests(&self, prefix: &str) -> usize {
+        self.requests
+            .lock()
+            .expect("mock requests poisoned")
+            .iter()
+            .filter(|path| path.starts_with(prefix))
+            .count()
+    }
+
+    /// 连接到这个服务器的[`Connector`]，可以用来测试重连等逻辑
+    pub fn connector(&self) -> Connector {
+        Connector::new(Self::ROOMID, 0, "").with_url(self.url())
+    }
+
+    /// 以[`ROOMID`](Self::ROOMID)连接
+    pub async fn connect(&self, config: &ConnectConfig) -> Result<Connection, WsConnectError> {
+        Connection::connect(self.url(), Auth::new(0, Self::ROOMID, None), config).await
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+        self.http_handle.abort();
+    }
+}
+
+/// 每个连接只处理一个请求，回复后关闭连接
+async fn serve_http(
+    mut tcp: tokio::net::TcpStream,
+    routes: Routes,
+    requests: Arc<Mutex<Vec<String>>>,
+) -> std::io::Result<()> {
+    let mut buf = Vec::new();
+    let head_len = loop {
+        if let Some(pos) = buf.windows(4).position(|window| window == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        let mut chunk = [0; 1024];
+        let n = tcp.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+    let head = String::from_utf8_lossy(&buf[..head_len]).into_owned();
+    let path = head
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or_default()
+        .to_owned();
+    let content_length = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or_default();
+    // 请求体不需要，读完后丢弃
+    let mut remaining = content_length.saturating_sub(buf.len() - head_len);
+    while remaining > 0 {
+        let mut chunk = [0; 1024];
+        let n = tcp.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        remaining = remaining.saturating_sub(n);
+    }
+    requests
+        .lock()
+        .expect("mock requests poisoned")
+        .push(path.clone());
+    let response = routes
+        .lock()
+        .expect("mock routes poisoned")
+        .iter()
+        .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, response)| response.clone())
+        .unwrap_or_else(|| MockResponse::new("not found").with_status(404));
+    if let Some(delay) = response.delay {
+        tokio::time::sleep(delay).await;
+    }
+    let reason = reqwest::StatusCode::from_u16(response.status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or("Unknown");
+    let mut out = format!("HTTP/1.1 {} {reason}\r\n", response.status);
+    for (name, value) in &response.headers {
+        out.push_str(&format!("{name}: {value}\r\n"));
+    }
+    out.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        response.body.len()
+    ));
+    tcp.write_all(out.as_bytes()).await?;
+    tcp.write_all(&response.body).await?;
+    tcp.shutdown().await
+}
+
+async fn serve(
+    tcp: tokio::net::TcpStream,
+    frames: Vec<Vec<u8>>,
+    popularity: u32,
+    disconnect: bool,
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    let mut ws = tokio_tungstenite::accept_async(tcp).await?;
+    // 第一个包应当是鉴权包
+    match ws.next().await {
+        Some(Ok(Message::Binary(bin))) => {
+            let is_auth = RawPacket::from_buffer(&bin)
+                .is_ok_and(|packet| packet.head().opcode == Operation::Auth as u32);
+            if !is_auth {
+                return Ok(());
+            }
+        }
+        _ => return Ok(()),
+    }
+    let reply = RawPacket::build(Operation::AuthReply, br#"{"code":0}"#.to_vec());
+    ws.send(Message::Binary(reply.ser().into())).await?;
+    for frame in frames {
+        ws.send(Message::Binary(frame.into())).await?;
+    }
+    if disconnect {
+        ws.flush().await?;
+        // 直接drop底层的TCP连接
+        drop(ws.into_inner());
+        return Ok(());
+    }
+    while let Some(message) = ws.next().await {
+        let Message::Binary(bin) = message? else {
+            continue;
+        };
+        let is_heartbeat = RawPacket::from_buffer(&bin)
+            .is_ok_and(|packet| packet.head().opcode == Operation::Heartbeat as u32);
+        if is_heartbeat {
+            let reply =
+                RawPacket::build(Operation::HeartbeatReply, popularity.to_be_bytes().to_vec());
+            ws.send(Message::Binary(reply.ser().into())).await?;
+        }
+    }
+    Ok(())
+}
+
+/// 不压缩的json消息帧
+pub fn json_frame(json: &Value) -> Vec<u8> {
+    RawPacket::build(Operation::SendMsgReply, json.to_string().into_bytes())
+        .with_proto_code(0)
+        .ser()
+}
+
+/// 把多条json消息打包为一个brotli压缩帧，与b站服务器推送的格式相同
+pub fn brotli_frame(jsons: &[Value]) -> Vec<u8> {
+    let mut inner = vec![];
+    for json in jsons {
+        inner.extend(json_frame(json));
+    }
+    let mut compressed = vec![];
+    {
+        let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, 5, 22);
+        writer
+            .write_all(&inner)
+            .expect("writing to a vec never fails");
+    }
+    RawPacket::build(Operation::SendMsgReply, compressed)
+        .with_proto_code(3)
+        .ser()
+}
@@ -0,0 +1,229 @@
+use std::time::Duration;
+
+#[test]
+fn danmaku_history_test() {
+    use crate::{api::HistoryData, event::EventData, model::DanmakuMessage};
+    let data: HistoryData = serde_json::from_str(include_str!("./mock/api/DanmakuHistory.json"))
+        .expect("json parse error");
+    let events = data.into_events();
+    assert_eq!(events.len(), 2);
+    let EventData::DanmakuEvent(danmaku) = &events[0].data else {
+        unreachable!("history should be danmaku")
+    };
+    assert_eq!(events[0].server_timestamp, Some(1684466082000));
+    assert_eq!(danmaku.user.uid, 1689814059);
+    assert_eq!((danmaku.user_level, danmaku.guard_level), (17, 3));
+    assert!(matches!(&danmaku.message, DanmakuMessage::Plain { message } if message == "晚上好"));
+    let medal = danmaku.fans_medal.as_ref().expect("medal");
+    assert_eq!(
+        (medal.medal_level, medal.medal_name.as_str()),
+        (21, "小王子")
+    );
+    assert_eq!((medal.anchor_roomid, medal.guard_level), (851181, 3));
+    assert_eq!(medal.anchor_uname.as_deref(), Some("某主播"));
+    assert!(medal.is_lighted);
+    // 空数组不产生粉丝牌，表情弹幕保留文字
+    let EventData::DanmakuEvent(emoticon) = &events[1].data else {
+        unreachable!("history should be danmaku")
+    };
+    assert!(emoticon.fans_medal.is_none());
+    let DanmakuMessage::Emoticon {
+        emoticon,
+        alt_message,
+    } = &emoticon.message
+    else {
+        unreachable!("should be emoticon")
+    };
+    assert_eq!(alt_message, "赞");
+    assert_eq!(emoticon.unique_id, "official_147");
+    assert_eq!((emoticon.width, emoticon.height), (183, 60));
+}
+
+#[test]
+fn fill_gift_test() {
+    use crate::{
+        api::{fill_gift, GiftInfo},
+        model::{CoinType, Gift},
+    };
+    let info = GiftInfo {
+        id: 31036,
+        name: "小花花".to_owned(),
+        price: 100,
+        coin_type: CoinType::Gold,
+        icon: String::new(),
+    };
+    // 缺少名称和单价时从礼物配置中补全，并计算总价
+    let mut gift = Gift {
+        coin_type: CoinType::Silver,
+        coin_count: 0,
+        action: "投喂".to_owned(),
+        gift_name: String::new(),
+        gift_id: 31036,
+        num: 3,
+        price: 0,
+    };
+    fill_gift(&mut gift, &info);
+    assert_eq!(gift.gift_name, "小花花");
+    assert_eq!((gift.price, gift.coin_type), (100, CoinType::Gold));
+    assert_eq!(gift.coin_count, 300);
+    // 已有的字段不修改
+    let mut gift = Gift {
+        gift_name: "花花".to_owned(),
+        price: 200,
+        coin_count: 200,
+        num: 1,
+        ..gift
+    };
+    fill_gift(&mut gift, &info);
+    assert_eq!(gift.gift_name, "花花");
+    assert_eq!((gift.price, gift.coin_count), (200, 200));
+}
+
+#[tokio::test]
+async fn gift_config_cache_backoff_test() {
+    use crate::{
+        api::GiftConfigCache,
+        test_util::{MockResponse, MockServer},
+        InitError,
+    };
+    const GIFT_CONFIG: &str = "/xlive/web-room/v1/giftPanel/giftConfig";
+    let server = MockServer::start(vec![])
+        .await
+        .expect("start mock server error");
+    server.respond(GIFT_CONFIG, MockResponse::new("").with_status(500));
+    let cache = GiftConfigCache::new(851181).with_client(server.http_client());
+    let error = cache.get(31036).await.expect_err("should fail");
+    assert!(matches!(error, InitError::HttpStatus(status) if status == 500));
+    // 获取失败后不再立即重新获取
+    assert!(matches!(cache.get(31036).await, Ok(None)));
+    assert_eq!(server.requests(GIFT_CONFIG), 1);
+    // 手动刷新不受限制
+    assert!(cache.refresh().await.is_err());
+    assert_eq!(server.requests(GIFT_CONFIG), 2);
+    server.respond(
+        GIFT_CONFIG,
+        MockResponse::data(&serde_json::json!({ "list": [
+            { "id": 31036, "name": "小花花", "price": 100, "coin_type": "gold" }
+        ] })),
+    );
+    cache.refresh().await.expect("refresh error");
+    let gift = cache
+        .get(31036)
+        .await
+        .expect("get error")
+        .expect("missing gift");
+    assert_eq!((gift.name.as_str(), gift.price), ("小花花", 100));
+    assert_eq!(server.requests(GIFT_CONFIG), 3);
+}
+
+#[tokio::test]
+async fn user_cache_test() {
+    use crate::{
+        api::UserCache,
+        test_util::{MockResponse, MockServer},
+        InitError,
+    };
+    const CARD: &str = "/x/web-interface/card?mid=";
+    let server = MockServer::start(vec![])
+        .await
+        .expect("start mock server error");
+    for uid in 1..=4 {
+        let card = serde_json::json!({ "card": { "name": format!("user{uid}"), "face": "" } });
+        server.respond(
+            format!("{CARD}{uid}"),
+            MockResponse::data(&card).with_delay(Duration::from_millis(20)),
+        );
+    }
+    server.respond(
+        format!("{CARD}0"),
+        MockResponse::api_error(-404, "啥都木有").with_delay(Duration::from_millis(20)),
+    );
+    let cache = UserCache::new()
+        .with_capacity(2)
+        .with_client(server.http_client());
+    let requested = || server.requests(CARD);
+    // 同时查询同一个用户只请求一次
+    let (a, b) = futures::join!(cache.get(1), cache.get(1));
+    assert_eq!(a.expect("get error").uname, "user1");
+    assert_eq!(b.expect("get error").uname, "user1");
+    assert_eq!(requested(), 1);
+    // 请求失败时等待的查询得到同样的错误，不会各自再请求
+    let (a, b) = futures::join!(cache.get(0), cache.get(0));
+    assert!(matches!(a, Err(InitError::ApiError { code: -404, .. })));
+    assert!(matches!(b, Err(InitError::ApiError { code: -404, .. })));
+    assert_eq!(requested(), 2);
+
+    // 超出容量时淘汰最久没有使用的用户
+    cache.get(2).await.expect("get error");
+    cache.get(1).await.expect("get error");
+    cache.get(3).await.expect("get error");
+    assert_eq!((cache.len(), requested()), (2, 4));
+    cache.get(1).await.expect("get error");
+    assert_eq!(requested(), 4);
+    cache.get(2).await.expect("get error");
+    assert_eq!(requested(), 5);
+
+    // 被取消的请求不影响之后的查询
+    let cancelled = tokio::time::timeout(Duration::from_millis(5), cache.get(4)).await;
+    assert!(cancelled.is_err());
+    let before = requested();
+    assert_eq!(cache.get(4).await.expect("get error").uid, 4);
+    assert_eq!(requested(), before + 1);
+}
+
+#[test]
+fn guard_list_test() {
+    use crate::api::GuardListData;
+    let json = include_str!("./mock/api/GuardList.json");
+    let data: GuardListData = serde_json::from_str(json).expect("json parse error");
+    let page = data.into_page(1);
+    assert_eq!((page.total, page.page, page.page_count), (23, 1, 2));
+    let ranks: Vec<u64> = page.entries.iter().map(|entry| entry.rank).collect();
+    assert_eq!(ranks, [1, 4, 5]);
+    let top = &page.entries[0];
+    assert_eq!((top.uid, top.uname.as_str()), (1689814059, "某总督"));
+    assert_eq!((top.guard_level, top.accompany), (1, 520));
+    assert_eq!((top.medal_name.as_str(), top.medal_level), ("小王子", 27));
+    // 没有guard时从粉丝牌中取大航海等级
+    assert_eq!(page.entries[1].guard_level, 3);
+    // 没有粉丝牌时名称为空
+    let no_medal = &page.entries[2];
+    assert_eq!((no_medal.guard_level, no_medal.accompany), (3, 0));
+    assert_eq!(
+        (no_medal.medal_name.as_str(), no_medal.medal_level),
+        ("", 0)
+    );
+    // 之后的页不重复前三名
+    let data: GuardListData = serde_json::from_str(json).expect("json parse error");
+    let ranks: Vec<u64> = data
+        .into_page(2)
+        .entries
+        .iter()
+        .map(|entry| entry.rank)
+        .collect();
+    assert_eq!(ranks, [4, 5]);
+}
+
+#[test]
+fn fans_medal_rank_test() {
+    use crate::api::FansMembersRankData;
+    let data: FansMembersRankData =
+        serde_json::from_str(include_str!("./mock/api/FansMembersRank.json"))
+            .expect("json parse error");
+    let page = data.into_page(1);
+    assert_eq!((page.total, page.page), (1523, 1));
+    assert_eq!(page.entries.len(), 2);
+    let top = &page.entries[0];
+    assert_eq!(
+        (top.uid, top.uname.as_str(), top.rank),
+        (1689814059, "某总督", 1)
+    );
+    assert_eq!(top.score, 2564820);
+    assert_eq!((top.medal_name.as_str(), top.medal_level), ("小王子", 27));
+    assert_eq!(top.guard_level, 1);
+    // 缺少的字段取默认值
+    let new = &page.entries[1];
+    assert_eq!((new.uid, new.medal_level), (10086, 1));
+    assert_eq!((new.score, new.guard_level), (0, 0));
+    assert!(new.face.is_empty() && new.medal_name.is_empty());
+}
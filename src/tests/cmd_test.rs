@@ -1,4 +1,11 @@
-use crate::cmd::Cmd;
+use crate::{cmd::Cmd, event::EventData};
+
+/// 解析fixture中的一条命令并转换为事件
+fn event(json: &str) -> Option<EventData> {
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    Cmd::deser(json_val).expect("cmd deser error").into_event()
+}
+
 #[test]
 fn super_chat_test() {
     let json = include_str!("./mock/cmd/SuperChatMessage.json");
@@ -7,6 +14,161 @@ fn super_chat_test() {
     dbg!(cmd);
 }
 
+#[test]
+fn super_chat_jpn_test() {
+    let Some(EventData::SuperChatEvent(super_chat)) =
+        event(include_str!("./mock/cmd/SuperChatMessageJpn.json"))
+    else {
+        unreachable!("SUPER_CHAT_MESSAGE_JPN should be a super chat event")
+    };
+    assert_eq!((super_chat.id, super_chat.user.uid), (3873280, 1689814059));
+    assert_eq!(
+        super_chat.message_jpn.as_deref(),
+        Some("私を蹴ってもいいですか?")
+    );
+}
+
+#[test]
+fn red_pocket_test() {
+    let Some(EventData::RedPocketStartEvent(start)) =
+        event(include_str!("./mock/cmd/PopularityRedPocketStart.json"))
+    else {
+        unreachable!("POPULARITY_RED_POCKET_START should be a red pocket event")
+    };
+    assert_eq!((start.total_price, start.duration), (18000, 180));
+    assert_eq!(start.awards.len(), 3);
+    let Some(EventData::RedPocketWinnerEvent(winner)) = event(include_str!(
+        "./mock/cmd/PopularityRedPocketWinnerList.json"
+    )) else {
+        unreachable!("POPULARITY_RED_POCKET_WINNER_LIST should be a red pocket event")
+    };
+    assert_eq!(winner.lot_id, start.lot_id);
+    assert_eq!(winner.winners.len(), 3);
+    assert_eq!(winner.winners[1].gift_name, "干杯");
+}
+
+#[test]
+fn anchor_lot_test() {
+    let Some(EventData::AnchorLotStartEvent(start)) =
+        event(include_str!("./mock/cmd/AnchorLotStart.json"))
+    else {
+        unreachable!("ANCHOR_LOT_START should be an anchor lot event")
+    };
+    assert!(start.gift.is_none());
+    assert_eq!(start.duration, 600);
+    let Some(EventData::AnchorLotAwardEvent(award)) =
+        event(include_str!("./mock/cmd/AnchorLotAward.json"))
+    else {
+        unreachable!("ANCHOR_LOT_AWARD should be an anchor lot event")
+    };
+    assert_eq!(award.id, start.id);
+    assert_eq!(award.winners[0].uid, 35473587);
+}
+
+#[test]
+fn room_change_test() {
+    let Some(EventData::RoomChangeEvent(change)) =
+        event(include_str!("./mock/cmd/RoomChange.json"))
+    else {
+        unreachable!("ROOM_CHANGE should be a room change event")
+    };
+    assert_eq!(change.title, "【3D】新衣服回！");
+    assert_eq!(
+        (change.area_name.as_str(), change.parent_area_id),
+        ("虚拟日常", 9)
+    );
+}
+
+#[test]
+fn like_info_test() {
+    let Some(EventData::LikeClickEvent(click)) =
+        event(include_str!("./mock/cmd/LikeInfoV3Click.json"))
+    else {
+        unreachable!("LIKE_INFO_V3_CLICK should be a like event")
+    };
+    assert_eq!(click.user.uid, 35473587);
+    assert!(click.fans_medal.is_some_and(|medal| medal.is_lighted));
+    let Some(EventData::LikeCountUpdateEvent(update)) =
+        event(include_str!("./mock/cmd/LikeInfoV3Update.json"))
+    else {
+        unreachable!("LIKE_INFO_V3_UPDATE should be a like count event")
+    };
+    assert_eq!(update.count, 3489);
+}
+
+#[test]
+fn fans_update_test() {
+    let Some(EventData::FansUpdateEvent(update)) =
+        event(include_str!("./mock/cmd/RoomRealTimeMessageUpdate.json"))
+    else {
+        unreachable!("ROOM_REAL_TIME_MESSAGE_UPDATE should be a fans update event")
+    };
+    assert_eq!((update.fans, update.fans_club), (68651, 688));
+}
+
+#[test]
+fn danmu_aggregation_test() {
+    let Some(EventData::DanmakuAggregationEvent(aggregation)) =
+        event(include_str!("./mock/cmd/DanmuAggregation.json"))
+    else {
+        unreachable!("DANMU_AGGREGATION should be an aggregation event")
+    };
+    assert_eq!(aggregation.message, "关注主播，点点关注");
+    assert_eq!(aggregation.count, 12);
+}
+
+#[test]
+fn entry_effect_test() {
+    let Some(EventData::GuardEnterRoomEvent(enter)) =
+        event(include_str!("./mock/cmd/EntryEffect.json"))
+    else {
+        unreachable!("ENTRY_EFFECT should be a guard enter event")
+    };
+    assert_eq!(enter.user.uid, 3780985);
+    assert_eq!(enter.user.uname, "_Mercur...");
+    assert_eq!(enter.guard_level, 3);
+    assert_eq!(enter.copy_writing, "欢迎舰长 _Mercur... 进入直播间");
+}
+
+#[test]
+fn interact_word_test() {
+    let json = include_str!("./mock/cmd/InteractWord.json");
+    let Some(EventData::EnterRoomEvent(enter)) = event(json) else {
+        unreachable!("INTERACT_WORD should be an enter room event")
+    };
+    assert_eq!(enter.user.uid, 33778290);
+    assert_eq!((enter.guard_level, enter.user_level), (0, 0));
+    // 没有roomid的消息也能解析
+    let mut json_val: serde_json::Value = serde_json::from_str(json).expect("json parse error");
+    let data = json_val["data"]
+        .as_object_mut()
+        .expect("data should be an object");
+    data.remove("roomid");
+    data.insert("privilege_type".to_owned(), 3.into());
+    data.insert("user_level".to_owned(), 21.into());
+    let Some(EventData::EnterRoomEvent(enter)) = event(&json_val.to_string()) else {
+        unreachable!("INTERACT_WORD should be an enter room event")
+    };
+    assert_eq!((enter.guard_level, enter.user_level), (3, 21));
+}
+
+#[test]
+fn online_rank_test() {
+    let Some(EventData::OnlineRankUpdateEvent(rank)) =
+        event(include_str!("./mock/cmd/OnlineRankV2.json"))
+    else {
+        unreachable!("ONLINE_RANK_V2 should be a rank event")
+    };
+    assert_eq!(rank.list.len(), 7);
+    assert_eq!((rank.list[0].user.uid, rank.list[0].score), (2037101, 112));
+    let Some(EventData::OnlineRankTop3Event(top3)) =
+        event(include_str!("./mock/cmd/OnlineRankTop3.json"))
+    else {
+        unreachable!("ONLINE_RANK_TOP3 should be a rank event")
+    };
+    assert_eq!(top3.messages, ["恭喜 盐焗果冻 成为高能榜"]);
+}
+
 #[test]
 fn send_gift_test() {
     let json = include_str!("./mock/cmd/SendGift.json");
@@ -22,3 +184,230 @@ fn stop_list_test() {
     let cmd = Cmd::deser(json_val).expect("cmd deser error");
     dbg!(cmd);
 }
+
+#[test]
+fn danmu_msg_test() {
+    let json = include_str!("./mock/cmd/DanmuMsg.json");
+    let json_val: Vec<serde_json::Value> = serde_json::from_str(json).expect("json parse error");
+    for val in json_val {
+        let cmd = Cmd::deser(val).expect("cmd deser error");
+        let Some(EventData::DanmakuEvent(danmaku)) = cmd.into_event() else {
+            unreachable!("DANMU_MSG should be a danmaku event")
+        };
+        if danmaku.user.uid == 34371618 {
+            let medal = danmaku.fans_medal.expect("missing fans medal");
+            assert_eq!(medal.anchor_uname.as_deref(), Some("伊丽莎白鼠"));
+            assert_eq!(medal.target_id, 375375);
+            assert!(!medal.is_lighted);
+            assert_eq!(danmaku.user_level, 9);
+            assert_eq!(
+                (danmaku.mode, danmaku.font_size, danmaku.color),
+                (1, 25, 0xFFFFFF)
+            );
+        }
+    }
+}
+
+#[test]
+fn open_platform_dm_test() {
+    use crate::model::DanmakuMessage;
+    let Some(EventData::DanmakuEvent(danmaku)) =
+        event(include_str!("./mock/cmd/LiveOpenPlatformDm.json"))
+    else {
+        unreachable!("LIVE_OPEN_PLATFORM_DM should be a danmaku event")
+    };
+    assert!(matches!(danmaku.message, DanmakuMessage::Emoticon { .. }));
+    assert_eq!(danmaku.guard_level, 3);
+    let medal = danmaku.fans_medal.expect("missing fans medal");
+    assert_eq!((medal.anchor_roomid, medal.medal_level), (1, 12));
+}
+
+#[test]
+fn open_platform_gift_test() {
+    use crate::model::CoinType;
+    let Some(EventData::GiftEvent(gift)) =
+        event(include_str!("./mock/cmd/LiveOpenPlatformSendGift.json"))
+    else {
+        unreachable!("LIVE_OPEN_PLATFORM_SEND_GIFT should be a gift event")
+    };
+    assert_eq!((gift.user.uid, gift.user.uname.as_str()), (0, "ad"));
+    assert!(gift.user.face.is_some());
+    assert_eq!(
+        (gift.gift.gift_id, gift.gift.gift_name.as_str()),
+        (31036, "小花花")
+    );
+    assert_eq!((gift.gift.num, gift.gift.price), (3, 100));
+    assert_eq!(gift.gift.coin_type, CoinType::Gold);
+    assert_eq!(gift.gift.coin_count, 300);
+    let medal = gift.fans_medal.expect("missing fans medal");
+    assert_eq!((medal.anchor_roomid, medal.medal_level), (1, 12));
+    assert_eq!(medal.medal_name, "小王子");
+}
+
+#[test]
+fn open_platform_super_chat_test() {
+    let Some(EventData::SuperChatEvent(super_chat)) =
+        event(include_str!("./mock/cmd/LiveOpenPlatformSuperChat.json"))
+    else {
+        unreachable!("LIVE_OPEN_PLATFORM_SUPER_CHAT should be a super chat event")
+    };
+    assert_eq!(
+        (super_chat.price, super_chat.message.as_str()),
+        (30, "主播好")
+    );
+    assert_eq!(super_chat.user.uname, "ad");
+    assert!(super_chat.message_jpn.is_none());
+    let medal = super_chat.fans_medal.expect("missing fans medal");
+    assert_eq!((medal.medal_level, medal.guard_level), (21, 2));
+}
+
+#[test]
+fn open_platform_guard_test() {
+    let Some(EventData::GuardBuyEvent(guard)) =
+        event(include_str!("./mock/cmd/LiveOpenPlatformGuard.json"))
+    else {
+        unreachable!("LIVE_OPEN_PLATFORM_GUARD should be a guard buy event")
+    };
+    assert_eq!((guard.level, guard.price), (3, 198000));
+    assert_eq!((guard.user.uid, guard.user.uname.as_str()), (0, "ad"));
+}
+
+#[test]
+fn server_timestamp_test() {
+    let json = include_str!("./mock/cmd/DanmuMsg.json");
+    let json_val: Vec<serde_json::Value> = serde_json::from_str(json).expect("json parse error");
+    for val in json_val {
+        let ts = crate::cmd::server_timestamp(&val).expect("missing server timestamp");
+        assert_eq!(Some(ts), val["info"][0][4].as_u64());
+    }
+    let json = include_str!("./mock/cmd/SendGift.json");
+    let json_val: serde_json::Value = serde_json::from_str(json).expect("json parse error");
+    let ts = crate::cmd::server_timestamp(&json_val).expect("missing server timestamp");
+    assert_eq!(ts % 1000, 0);
+}
+
+#[test]
+fn unknown_cmd_test() {
+    use crate::cmd::CmdDeserError;
+    let cmd = Cmd::deser(serde_json::json!({ "cmd": "SOMETHING_NEW", "data": {} }))
+        .expect("unknown cmd should not fail");
+    assert!(matches!(cmd, Cmd::Unknown { cmd, .. } if cmd == "SOMETHING_NEW"));
+    let err = Cmd::deser(serde_json::json!({ "cmd": "WATCHED_CHANGE", "data": { "num": "x" } }))
+        .expect_err("malformed cmd should fail");
+    assert!(matches!(err, CmdDeserError::CannotDeser { path, .. } if path == "data.num"));
+    let err = Cmd::deser(serde_json::json!({ "cmd": "DANMU_MSG", "info": [[], "hi", []] }))
+        .expect_err("malformed danmaku should fail");
+    assert!(matches!(err, CmdDeserError::Malformed { path, .. } if path == "info[2][0]"));
+}
+
+#[test]
+fn no_event_error_test() {
+    use crate::event::Event;
+    let cmd = Cmd::deser(serde_json::json!({ "cmd": "SOMETHING_NEW", "data": {} }))
+        .expect("unknown cmd should not fail");
+    let err = Event::try_from(cmd).expect_err("unknown cmd should have no event");
+    assert_eq!(err.to_string(), "未知的cmd: SOMETHING_NEW");
+    assert!(matches!(*err.cmd, Cmd::Unknown { cmd, .. } if cmd == "SOMETHING_NEW"));
+    let cmd = Cmd::deser(serde_json::json!({ "cmd": "WATCHED_CHANGE", "data": { "num": 7 } }))
+        .expect("cmd deser error");
+    assert!(Event::try_from(cmd).is_ok());
+}
+
+#[test]
+fn error_source_test() {
+    use crate::packet::EventParseError;
+    use std::error::Error;
+    let err = Cmd::deser(serde_json::json!({ "cmd": "WATCHED_CHANGE", "data": { "num": "x" } }))
+        .expect_err("malformed cmd should fail");
+    let err = EventParseError::CmdDeserError(err);
+    let cmd_error = err.source().expect("missing cmd deser error");
+    let json_error = cmd_error.source().expect("missing json error");
+    assert!(json_error.is::<serde_json::Error>());
+}
+
+#[test]
+fn live_status_test() {
+    use crate::model::LiveStatus;
+    let live =
+        serde_json::json!({ "cmd": "LIVE", "live_key": "1", "roomid": 1, "live_time": 1700000000 });
+    let Some(EventData::LiveStatusEvent(live)) =
+        Cmd::deser(live).expect("cmd deser error").into_event()
+    else {
+        unreachable!("LIVE should be a live status event")
+    };
+    assert_eq!(live.status, LiveStatus::Live);
+    assert_eq!(live.live_time, Some(1700000000));
+    let round = serde_json::json!({ "cmd": "PREPARING", "round": 1, "roomid": "1" });
+    let Some(EventData::LiveStatusEvent(round)) =
+        Cmd::deser(round).expect("cmd deser error").into_event()
+    else {
+        unreachable!("PREPARING should be a live status event")
+    };
+    assert_eq!(round.status, LiveStatus::Round);
+}
+
+#[test]
+fn pk_battle_test() {
+    let Some(EventData::PkBattlePreEvent(pre)) = event(include_str!("./mock/cmd/PkBattlePre.json"))
+    else {
+        unreachable!("PK_BATTLE_PRE_NEW should be a pk event")
+    };
+    assert_eq!((pre.opponent.uid, pre.opponent_roomid), (2052135, 5440));
+    let Some(EventData::PkBattleStartEvent(start)) =
+        event(include_str!("./mock/cmd/PkBattleStart.json"))
+    else {
+        unreachable!("PK_BATTLE_START_NEW should be a pk event")
+    };
+    assert_eq!((start.init.roomid, start.matched.roomid), (21452505, 5440));
+    assert_eq!(start.end_time - start.start_time, 310);
+    let Some(EventData::PkBattleProcessEvent(process)) =
+        event(include_str!("./mock/cmd/PkBattleProcess.json"))
+    else {
+        unreachable!("PK_BATTLE_PROCESS_NEW should be a pk event")
+    };
+    assert_eq!((process.init.votes, process.matched.votes), (520, 100));
+    let Some(EventData::PkBattleEndEvent(end)) = event(include_str!("./mock/cmd/PkBattleEnd.json"))
+    else {
+        unreachable!("PK_BATTLE_END should be a pk event")
+    };
+    assert_eq!(end.winner, Some(21452505));
+    assert_eq!(end.matched.best_uname, "路人");
+}
+
+#[test]
+fn room_block_test() {
+    use crate::model::BlockOperator;
+    let Some(EventData::UserBlockedEvent(blocked)) =
+        event(include_str!("./mock/cmd/RoomBlockMsg.json"))
+    else {
+        unreachable!("ROOM_BLOCK_MSG should be a user blocked event")
+    };
+    assert_eq!(blocked.user.uid, 40162947);
+    assert_eq!(blocked.operator, BlockOperator::Admin);
+}
+
+#[test]
+fn notice_msg_test() {
+    let Some(EventData::NoticeEvent(notice)) = event(include_str!("./mock/cmd/NoticeMsg.json"))
+    else {
+        unreachable!("NOTICE_MSG should be a notice event")
+    };
+    assert_eq!((notice.msg_type, notice.roomid), (2, 22894962));
+    assert_eq!(notice.highlights(), ["夜然z", "靡烟miya"]);
+    assert_eq!(
+        notice.text(),
+        "夜然z投喂:靡烟miya1个鸿运小电视，点击前往TA的房间吧！"
+    );
+    assert!(notice
+        .link_url
+        .starts_with("https://live.bilibili.com/22894962"));
+}
+
+#[test]
+fn corpus_test() {
+    use crate::corpus::check_corpus;
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/mock/cmd");
+    let report = check_corpus(dir).expect("read corpus error");
+    assert!(report.is_ok(), "{:#?}", report.failures);
+    assert!(report.events > 0);
+}
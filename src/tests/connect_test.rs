@@ -1,3 +1,15 @@
+#[test]
+#[cfg(feature = "connect")]
+fn default_timeouts_test() {
+    use crate::connection::ConnectConfig;
+    use std::time::Duration;
+    let config = ConnectConfig::default();
+    assert_eq!(config.handshake_timeout, Some(Duration::from_secs(10)));
+    assert_eq!(config.auth_timeout, Some(Duration::from_secs(10)));
+    // 大于默认的心跳间隔
+    let stall_timeout = config.stall_timeout.expect("stall timeout");
+    assert!(stall_timeout > config.heartbeat.interval);
+}
 // #[test]
 // fn cli_test() {
 //     use std::io::{stdin, stdout};
@@ -0,0 +1,108 @@
+#[test]
+fn credential_debug_test() {
+    use crate::credential::Credential;
+    let credential = Credential::new("secret_sessdata", "secret_csrf")
+        .with_uid(10086)
+        .with_refresh_token("secret_token");
+    let debug = format!("{credential:?}");
+    assert!(!debug.contains("secret"), "{debug}");
+    assert!(debug.contains("10086"), "{debug}");
+}
+
+#[test]
+#[cfg(feature = "cookie_refresh")]
+fn correspond_path_test() {
+    let path = crate::credential::correspond_path(1684466082000).expect("encrypt error");
+    // 1024位公钥加密得到128字节
+    assert_eq!(path.len(), 256);
+    assert!(path.chars().all(|c| c.is_ascii_hexdigit()));
+}
+
+#[tokio::test]
+#[cfg(feature = "cookie_refresh")]
+async fn credential_manager_test() {
+    use crate::credential::{Credential, CredentialManager, CODE_NOT_LOGGED_IN};
+    use crate::test_util::{MockResponse, MockServer};
+    use crate::InitError;
+    use std::error::Error;
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+    const REFRESH: &str = "/x/passport-login/web/cookie/refresh";
+    let server = MockServer::start(vec![])
+        .await
+        .expect("start mock server error");
+    server.respond(
+        "/x/passport-login/web/cookie/info",
+        MockResponse::data(&serde_json::json!({ "refresh": true, "timestamp": 1684466082000u64 })),
+    );
+    server.respond(
+        "/correspond/1/",
+        MockResponse::new(r#"<div id="1-name">refresh_csrf</div>"#),
+    );
+    server.respond(
+        REFRESH,
+        MockResponse::data(&serde_json::json!({ "refresh_token": "token2" }))
+            .with_header("Set-Cookie", "SESSDATA=new; Path=/")
+            .with_header("Set-Cookie", "bili_jct=csrf2; Path=/"),
+    );
+    server.respond(
+        "/x/passport-login/web/confirm/refresh",
+        MockResponse::data(&serde_json::json!({})),
+    );
+    let missing = server
+        .http_client()
+        .refresh_credential(&Credential::new("old", "csrf"))
+        .await;
+    assert!(matches!(missing, Err(InitError::MissingRefreshToken)));
+    assert_eq!(server.requests("/"), 0);
+
+    let relogins = Arc::new(AtomicU32::new(0));
+    let manager =
+        CredentialManager::new(Credential::new("old", "csrf").with_refresh_token("token"))
+            .with_client(server.http_client())
+            .with_on_relogin({
+                let relogins = relogins.clone();
+                move |_| {
+                    relogins.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+    let api = |credential: Credential| async move {
+        match credential.sessdata.as_str() {
+            "new" => Ok(credential.sessdata),
+            _ => Err(InitError::ApiError {
+                code: CODE_NOT_LOGGED_IN,
+                message: "账号未登录".to_owned(),
+            }),
+        }
+    };
+    // 同时失效的两个调用只刷新一次，然后都用新的凭据重试
+    let (a, b) = futures::join!(manager.call(api), manager.call(api));
+    assert_eq!(a.expect("call error"), "new");
+    assert_eq!(b.expect("call error"), "new");
+    assert_eq!(server.requests(REFRESH), 1);
+    let credential = manager.credential();
+    assert_eq!(
+        (
+            credential.bili_jct.as_str(),
+            credential.refresh_token.as_str()
+        ),
+        ("csrf2", "token2")
+    );
+    assert_eq!(relogins.load(Ordering::Relaxed), 0);
+
+    // 刷新失败时调用on_relogin，返回刷新的错误和原来的错误
+    server.respond(REFRESH, MockResponse::api_error(86095, "refresh_csrf错误"));
+    manager.set(Credential::new("expired", "csrf").with_refresh_token("token2"));
+    let error = manager.call(api).await.expect_err("should fail");
+    let InitError::RefreshFailed { source, expired } = &error else {
+        unreachable!("unexpected error {error}")
+    };
+    assert!(matches!(**source, InitError::ApiError { code: 86095, .. }));
+    assert!(expired.is_credential_expired());
+    let source = error.source().expect("missing refresh error");
+    assert!(source.to_string().contains("86095"), "{source}");
+    assert_eq!(server.requests(REFRESH), 2);
+    assert_eq!(relogins.load(Ordering::Relaxed), 1);
+}
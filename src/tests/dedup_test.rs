@@ -0,0 +1,15 @@
+use crate::dedup::Deduplicator;
+use std::time::Duration;
+#[test]
+fn dedup_test() {
+    let json = include_str!("./mock/cmd/SendGift.json");
+    let json_val: serde_json::Value = serde_json::from_str(json).expect("json parse error");
+    let dedup = Deduplicator::new(Duration::from_secs(10));
+    assert!(dedup.check_json(&json_val));
+    assert!(!dedup.clone().check_json(&json_val));
+    let json = include_str!("./mock/cmd/DanmuMsg.json");
+    let json_val: Vec<serde_json::Value> = serde_json::from_str(json).expect("json parse error");
+    assert!(dedup.check_json(&json_val[0]));
+    assert!(dedup.check_json(&json_val[1]));
+    assert!(!dedup.check_json(&json_val[0]));
+}
@@ -0,0 +1,33 @@
+use crate::event::{Event, EventData, WatchedUpdateEvent};
+#[test]
+fn event_serde_test() {
+    let event: Event = EventData::from(WatchedUpdateEvent { num: 100 }).into();
+    let json = serde_json::to_value(&event).expect("event ser error");
+    assert_eq!(json["cmd"], "WatchedUpdateEvent");
+    assert_eq!(json["data"]["num"], 100);
+    assert_eq!(json["timestamp"], event.timestamp);
+    let event: Event = serde_json::from_value(json).expect("event deser error");
+    assert_eq!(event.data.cmd(), "WatchedUpdateEvent");
+}
+
+#[test]
+fn display_test() {
+    use crate::{event::SuperChatEvent, model::User};
+    let data = EventData::from(SuperChatEvent {
+        id: 1,
+        user: User {
+            uid: 1,
+            uname: "user".to_owned(),
+            face: None,
+        },
+        fans_medal: None,
+        price: 30,
+        message: "message".to_owned(),
+        message_jpn: None,
+    });
+    assert_eq!(data.to_string(), "[醒目留言 ¥30] user: message");
+    assert_eq!(
+        data.format_colored(),
+        "\x1b[31m[醒目留言 ¥30] user: message\x1b[0m"
+    );
+}
@@ -0,0 +1,69 @@
+use crate::{
+    event::{DanmakuEvent, Event, EventData, WatchedUpdateEvent},
+    filter::{CmdFilter, DanmakuSampler, EventFilter},
+    model::FansMedal,
+};
+
+fn danmaku(uid: u64, message: &str, medal_level: u64) -> Event {
+    EventData::from(DanmakuEvent {
+        fans_medal: Some(FansMedal {
+            anchor_roomid: 851181,
+            guard_level: 0,
+            medal_level,
+            medal_name: "medal".to_owned(),
+            anchor_uname: None,
+            target_id: 0,
+            is_lighted: true,
+        }),
+        ..super::danmaku(uid, message)
+    })
+    .into()
+}
+
+#[test]
+fn filter_test() {
+    let filter = EventFilter::new()
+        .cmds(["DanmakuEvent"])
+        .keyword("广告")
+        .block_uid(10086)
+        .min_medal_level(5);
+    assert!(filter.accept(&danmaku(1, "你好", 5)));
+    assert!(!filter.accept(&danmaku(1, "看广告", 5)));
+    assert!(!filter.accept(&danmaku(10086, "你好", 5)));
+    assert!(!filter.accept(&danmaku(1, "你好", 4)));
+    assert!(!filter.accept(&EventData::from(WatchedUpdateEvent { num: 1 }).into()));
+}
+
+#[test]
+fn cmd_filter_test() {
+    let filter = CmdFilter::new()
+        .only(["DANMU_MSG", "SEND_GIFT"])
+        .ignore(["SEND_GIFT"]);
+    assert!(filter.accept("DANMU_MSG"));
+    assert!(!filter.accept("SEND_GIFT"));
+    assert!(!filter.accept("INTERACT_WORD"));
+}
+
+#[test]
+fn danmaku_sampler_test() {
+    let at = |timestamp: u64, event: Event| Event { timestamp, ..event };
+    let mut sampler = DanmakuSampler::new(2).with_summary(true);
+    let mut delivered = vec![];
+    for timestamp in [0, 100, 200, 300] {
+        delivered.extend(sampler.sample(at(timestamp, danmaku(1, "刷屏", 0))));
+    }
+    // 其他事件不受限流影响
+    let watched = at(400, EventData::from(WatchedUpdateEvent { num: 1 }).into());
+    delivered.extend(sampler.sample(watched));
+    assert_eq!(delivered.len(), 3);
+    // 下一个窗口先产生汇总
+    let next: Vec<_> = sampler.sample(at(1000, danmaku(1, "你好", 0))).collect();
+    let [summary, danmaku] = &next[..] else {
+        unreachable!("expected summary and danmaku, got {next:?}")
+    };
+    let EventData::DanmakuThrottledEvent(summary) = &summary.data else {
+        unreachable!("unexpected event {:?}", summary.data)
+    };
+    assert_eq!(summary.dropped, 2);
+    assert!(matches!(danmaku.data, EventData::DanmakuEvent(_)));
+}
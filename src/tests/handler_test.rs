@@ -0,0 +1,32 @@
+use crate::{
+    event::{Event, EventData, WatchedUpdateEvent},
+    handler::{drive, EventHandler},
+};
+
+#[derive(Default)]
+struct Counter {
+    watched: u64,
+    unknown: u64,
+}
+
+impl EventHandler for Counter {
+    fn on_watched_update(&mut self, watched: WatchedUpdateEvent, _timestamp: u64) {
+        self.watched += watched.num;
+    }
+
+    fn on_unknown(&mut self, _data: EventData, _timestamp: u64) {
+        self.unknown += 1;
+    }
+}
+
+#[test]
+fn drive_test() {
+    let events = (1..=3).map(|num| {
+        let event: Event = EventData::from(WatchedUpdateEvent { num }).into();
+        Ok::<_, ()>(event)
+    });
+    let mut counter = Counter::default();
+    futures::executor::block_on(drive(futures::stream::iter(events), &mut counter))
+        .expect("drive error");
+    assert_eq!((counter.watched, counter.unknown), (6, 0));
+}
@@ -0,0 +1,13 @@
+use crate::http::RateLimiter;
+use std::time::{Duration, Instant};
+
+#[tokio::test]
+async fn rate_limiter_test() {
+    let limiter = RateLimiter::new(10.0, 2);
+    let start = Instant::now();
+    for _ in 0..4 {
+        limiter.acquire().await;
+    }
+    // 前两个请求立即通过，后两个各需要等待约100ms
+    assert!(start.elapsed() >= Duration::from_millis(150));
+}
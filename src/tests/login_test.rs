@@ -0,0 +1,10 @@
+#[test]
+fn credential_from_url_test() {
+    use crate::login::credential_from_url;
+    let url = "https://passport.biligame.com/x/passport-login/web/crossDomain?DedeUserID=10086&DedeUserID__ckMd5=abc&Expires=1&SESSDATA=a%2C1%2Cb&bili_jct=csrf&gourl=https%3A%2F%2Fwww.bilibili.com";
+    let credential = credential_from_url(url).expect("parse error");
+    assert_eq!(credential.uid, 10086);
+    assert_eq!(credential.sessdata, "a%2C1%2Cb");
+    assert_eq!(credential.bili_jct, "csrf");
+    assert!(credential_from_url("https://www.bilibili.com").is_err());
+}
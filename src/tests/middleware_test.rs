@@ -0,0 +1,29 @@
+use crate::{
+    event::{Event, EventData, WatchedUpdateEvent},
+    middleware::Middlewares,
+};
+
+#[test]
+fn middleware_chain_test() {
+    let middlewares = Middlewares::new()
+        .then(|mut event| {
+            if let EventData::WatchedUpdateEvent(watched) = &mut event.data {
+                watched.num *= 10;
+            }
+            Some(event)
+        })
+        .then(|event| match &event.data {
+            EventData::WatchedUpdateEvent(watched) if watched.num > 20 => None,
+            _ => Some(event),
+        });
+    let watched = |num| Event::from(EventData::from(WatchedUpdateEvent { num }));
+    let Some(Event {
+        data: EventData::WatchedUpdateEvent(kept),
+        ..
+    }) = middlewares.apply(watched(2))
+    else {
+        unreachable!("event should be kept")
+    };
+    assert_eq!(kept.num, 20);
+    assert!(middlewares.apply(watched(3)).is_none());
+}
@@ -0,0 +1,440 @@
+use futures_util::StreamExt;
+
+use crate::{
+    connection::CloseReason,
+    event::EventData,
+    test_util::{brotli_frame, json_frame, MockServer},
+};
+
+#[tokio::test]
+async fn mock_server_test() {
+    let frame = brotli_frame(&[
+        serde_json::json!({ "cmd": "WATCHED_CHANGE", "data": { "num": 1 } }),
+        serde_json::json!({ "cmd": "WATCHED_CHANGE", "data": { "num": 2 } }),
+    ]);
+    let server = MockServer::start(vec![frame])
+        .await
+        .expect("start mock server error");
+    let mut stream = server
+        .connect(&Default::default())
+        .await
+        .expect("connect error");
+    for expected in [1, 2] {
+        let event = stream
+            .next()
+            .await
+            .expect("stream ended")
+            .expect("stream error");
+        let EventData::WatchedUpdateEvent(watched) = event.data else {
+            unreachable!("unexpected event {:?}", event.data)
+        };
+        assert_eq!(watched.num, expected);
+    }
+    let closed = stream.closed();
+    assert!(stream.close_reason().is_none());
+    stream.cancel_token().cancel();
+    assert!(stream.next().await.is_none());
+    let reason = closed.await;
+    assert!(matches!(reason, CloseReason::Cancelled), "{reason}");
+    assert!(reason.is_clean());
+    stream.abort();
+}
+
+#[tokio::test]
+async fn batched_test() {
+    let frame = brotli_frame(
+        &(1..=3)
+            .map(|num| serde_json::json!({ "cmd": "WATCHED_CHANGE", "data": { "num": num } }))
+            .collect::<Vec<_>>(),
+    );
+    let server = MockServer::start(vec![frame])
+        .await
+        .expect("start mock server error");
+    // 去掉心跳回复的人气值，只留下服务器推送的事件
+    let config = crate::connection::ConnectConfig {
+        filter: Some(crate::filter::EventFilter::new().cmds(["WatchedUpdateEvent"])),
+        ..Default::default()
+    };
+    let stream = server.connect(&config).await.expect("connect error");
+    let mut batches = stream.batched(2, std::time::Duration::from_millis(50));
+    // 攒够数量时立即产生，剩下的等到超时
+    for expected in [2, 1] {
+        let batch = batches
+            .next()
+            .await
+            .expect("stream ended")
+            .expect("stream error");
+        assert_eq!(batch.len(), expected);
+    }
+    batches.into_inner().abort();
+}
+
+#[tokio::test]
+async fn pinned_host_test() {
+    let frame =
+        brotli_frame(&[serde_json::json!({ "cmd": "WATCHED_CHANGE", "data": { "num": 3 } })]);
+    let server = MockServer::start(vec![frame])
+        .await
+        .expect("start mock server error");
+    let connector = server.connector();
+    assert!(connector.pinned_hosts);
+    assert_eq!(connector.host_list[0].host, "127.0.0.1");
+    assert_eq!(connector.host_list[0].wss_port, server.addr().port());
+    let mut stream = Box::pin(connector.connect_switching());
+    let event = stream
+        .next()
+        .await
+        .expect("stream ended")
+        .expect("stream error");
+    assert!(matches!(event.data, EventData::WatchedUpdateEvent(watched) if watched.num == 3));
+}
+
+#[tokio::test]
+async fn rtt_switch_test() {
+    let server = MockServer::start_with_popularity(vec![], 1)
+        .await
+        .expect("start mock server error");
+    let mut connector = server.connector();
+    connector.config.heartbeat.interval = std::time::Duration::from_secs(1);
+    // 任何延迟都超过上限，连续3次心跳后切换服务器
+    connector.config.max_rtt = Some(std::time::Duration::from_nanos(1));
+    let mut stream = Box::pin(connector.connect_switching());
+    let switch = async {
+        let mut popularity = 0;
+        loop {
+            let event = stream
+                .next()
+                .await
+                .expect("stream ended")
+                .expect("stream error");
+            match event.data {
+                EventData::PopularityUpdateEvent(_) => popularity += 1,
+                EventData::HostSwitchEvent(switch) => return (switch, popularity),
+                _ => {}
+            }
+        }
+    };
+    let (switch, popularity) = tokio::time::timeout(std::time::Duration::from_secs(10), switch)
+        .await
+        .expect("should switch host when rtt degrades");
+    assert_eq!(switch.reason, "心跳延迟过高");
+    assert!(popularity >= 3);
+}
+
+#[tokio::test]
+async fn read_ahead_test() {
+    let frames = (1..=5)
+        .map(|num| {
+            json_frame(&serde_json::json!({ "cmd": "WATCHED_CHANGE", "data": { "num": num } }))
+        })
+        .collect();
+    let server = MockServer::start(frames)
+        .await
+        .expect("start mock server error");
+    let config = crate::connection::ConnectConfig {
+        buffer_capacity: Some(1),
+        ..Default::default()
+    };
+    let mut stream = server.connect(&config).await.expect("connect error");
+    // 预读满了之后暂停读取，不会丢弃后面的帧
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    let mut nums = vec![];
+    while nums.len() < 5 {
+        let event = stream
+            .next()
+            .await
+            .expect("stream ended")
+            .expect("stream error");
+        if let EventData::WatchedUpdateEvent(watched) = event.data {
+            nums.push(watched.num);
+        }
+    }
+    assert_eq!(nums, vec![1, 2, 3, 4, 5]);
+    stream.abort();
+}
+
+#[tokio::test]
+async fn room_lag_test() {
+    use crate::room::{LagPolicy, Room, RoomState};
+    let jsons: Vec<_> = (1..=6)
+        .map(|num| serde_json::json!({ "cmd": "WATCHED_CHANGE", "data": { "num": num } }))
+        .collect();
+    let server = MockServer::start(vec![brotli_frame(&jsons)])
+        .await
+        .expect("start mock server error");
+    let room = Room::spawn(server.connector(), 2);
+    let mut skip = room.subscribe();
+    let room = room.with_lag_policy(LagPolicy::Disconnect);
+    let mut disconnect = room.subscribe();
+    room.watch_state()
+        .wait_for(|state| *state == RoomState::Connected)
+        .await
+        .expect("room task dropped state");
+    // 等后台任务发出所有事件，订阅者都没有读取
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    let event = skip
+        .recv()
+        .await
+        .expect("skip policy should keep receiving");
+    assert!(matches!(event.data, EventData::WatchedUpdateEvent(_)));
+    assert!(skip.lagged() >= 4);
+    assert_eq!(
+        disconnect
+            .recv()
+            .await
+            .expect_err("slow subscriber should be closed"),
+        tokio::sync::broadcast::error::RecvError::Closed
+    );
+    assert!(disconnect.lagged() >= 4);
+    assert_eq!(room.lagged(), skip.lagged() + disconnect.lagged());
+    room.close().await;
+}
+
+#[tokio::test]
+async fn room_filtered_test() {
+    use crate::{event::WatchedUpdateEvent, model::LiveStatus, room::Room};
+    let like: serde_json::Value =
+        serde_json::from_str(include_str!("./mock/cmd/LikeInfoV3Update.json"))
+            .expect("json parse error");
+    let frame = brotli_frame(&[
+        like,
+        serde_json::json!({ "cmd": "LIVE", "roomid": 1 }),
+        serde_json::json!({ "cmd": "WATCHED_CHANGE", "data": { "num": 5 } }),
+    ]);
+    let server = MockServer::start(vec![frame])
+        .await
+        .expect("start mock server error");
+    let room = Room::spawn(server.connector(), 16);
+    let mut all = room.subscribe();
+    let mut watched = room.subscribe_filtered::<WatchedUpdateEvent>();
+    let mut live_status = room.watch_live_status();
+    assert_eq!(*live_status.borrow(), LiveStatus::Preparing);
+    let first = all.recv().await.expect("recv error");
+    assert!(matches!(first.data, EventData::LikeCountUpdateEvent(_)));
+    let update = watched.recv().await.expect("recv error");
+    assert_eq!(update.num, 5);
+    live_status.changed().await.expect("room closed");
+    assert_eq!(room.live_status(), LiveStatus::Live);
+    room.close().await;
+    assert!(watched.recv().await.is_err());
+}
+
+#[tokio::test]
+async fn room_reconnect_test() {
+    use crate::{
+        event::{HostSwitchEvent, WatchedUpdateEvent},
+        room::{Room, RoomState},
+    };
+    let frame = json_frame(&serde_json::json!({ "cmd": "WATCHED_CHANGE", "data": { "num": 1 } }));
+    // 每次连接都在发送一帧后断开TCP，没有Close帧
+    let server = MockServer::start_disconnecting(vec![frame])
+        .await
+        .expect("start mock server error");
+    let cancel = tokio_util::sync::CancellationToken::new();
+    let mut connector = server.connector();
+    connector.config.cancel = Some(cancel.clone());
+    let room = Room::spawn(connector, 16);
+    let mut watched = room.subscribe_filtered::<WatchedUpdateEvent>();
+    let mut switches = room.subscribe_filtered::<HostSwitchEvent>();
+    watched.recv().await.expect("recv error");
+    switches.recv().await.expect("should switch host after eof");
+    // 断开后自动重连
+    watched.recv().await.expect("recv error");
+    // 外部取消后，即使Room还在，订阅者也会收到Closed
+    cancel.cancel();
+    let mut state = room.watch_state();
+    state
+        .wait_for(|state| *state == RoomState::Disconnected)
+        .await
+        .expect("room task dropped state");
+    loop {
+        match watched.recv().await {
+            Ok(_) => continue,
+            Err(e) => {
+                assert_eq!(e, tokio::sync::broadcast::error::RecvError::Closed);
+                break;
+            }
+        }
+    }
+    assert!(room.subscribe().recv().await.is_err());
+    room.close().await;
+}
+
+#[tokio::test]
+async fn ws_headers_test() {
+    use crate::connection::{ConnectConfig, WsConnectError};
+    let server = MockServer::start(vec![])
+        .await
+        .expect("start mock server error");
+    let config = ConnectConfig {
+        headers: crate::http::browser_headers(),
+        ..Default::default()
+    };
+    server
+        .connect(&config)
+        .await
+        .expect("connect error")
+        .abort();
+    let config = ConnectConfig {
+        headers: vec![("bad header".to_owned(), "x".to_owned())],
+        ..Default::default()
+    };
+    let result = server.connect(&config).await;
+    assert!(matches!(result, Err(WsConnectError::WsError(_))));
+}
+
+#[tokio::test]
+async fn resolver_test() {
+    use crate::connection::{ConnectConfig, Connection, Resolver};
+    use crate::Auth;
+    let server = MockServer::start(vec![])
+        .await
+        .expect("start mock server error");
+    let url = format!("ws://danmaku.invalid:{}/sub", server.addr().port());
+    let auth = || Auth::new(0, MockServer::ROOMID, None);
+    let config = ConnectConfig {
+        resolver: Some(Resolver::new().with_override("danmaku.invalid", [server.addr().ip()])),
+        ..Default::default()
+    };
+    Connection::connect(url.clone(), auth(), &config)
+        .await
+        .expect("connect error")
+        .abort();
+    let ip = server.addr().ip();
+    let config = ConnectConfig {
+        resolver: Some(Resolver::new().with_resolver(move |host, port| async move {
+            assert_eq!(host, "danmaku.invalid");
+            Ok(vec![std::net::SocketAddr::new(ip, port)])
+        })),
+        ..Default::default()
+    };
+    Connection::connect(url, auth(), &config)
+        .await
+        .expect("connect error")
+        .abort();
+}
+
+#[tokio::test]
+async fn drop_closes_socket_test() {
+    use crate::connection::Connection;
+    use crate::{Auth, Operation, RawPacket};
+    use futures_util::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind error");
+    let url = format!("ws://{}/sub", listener.local_addr().expect("no local addr"));
+    let server = tokio::spawn(async move {
+        let (tcp, _) = listener.accept().await.expect("accept error");
+        let mut ws = tokio_tungstenite::accept_async(tcp)
+            .await
+            .expect("handshake error");
+        let _auth = ws.next().await;
+        let reply = RawPacket::build(Operation::AuthReply, br#"{"code":0}"#.to_vec());
+        ws.send(Message::Binary(reply.ser().into()))
+            .await
+            .expect("send error");
+        // 客户端drop后，心跳任务也应当结束并关闭socket
+        while let Some(Ok(_)) = ws.next().await {}
+    });
+    let connection = Connection::connect(url, Auth::new(0, 1, None), &Default::default())
+        .await
+        .expect("connect error");
+    drop(connection);
+    tokio::time::timeout(std::time::Duration::from_secs(5), server)
+        .await
+        .expect("socket is still open")
+        .expect("server task failed");
+}
+
+#[tokio::test]
+async fn diagnostics_test() {
+    use crate::connection::ConnectConfig;
+    use crate::{Diagnostic, Operation, RawPacket};
+    let invalid_json = RawPacket::build(Operation::SendMsgReply, b"{not json".to_vec())
+        .with_proto_code(0)
+        .ser();
+    let bad_danmaku = json_frame(&serde_json::json!({ "cmd": "DANMU_MSG", "info": "bad" }));
+    let watched = json_frame(&serde_json::json!({ "cmd": "WATCHED_CHANGE", "data": { "num": 1 } }));
+    let server = MockServer::start(vec![invalid_json, bad_danmaku, watched])
+        .await
+        .expect("start mock server error");
+    let (tx, mut rx) = futures::channel::mpsc::unbounded();
+    let config = ConnectConfig {
+        diagnostics: Some(tx),
+        ..Default::default()
+    };
+    let mut stream = server.connect(&config).await.expect("connect error");
+    // 出错的消息不影响之后的事件
+    let event = stream
+        .next()
+        .await
+        .expect("stream ended")
+        .expect("stream error");
+    assert!(matches!(event.data, EventData::WatchedUpdateEvent(_)));
+    let Some(Diagnostic::InvalidJson { raw, .. }) = rx.next().await else {
+        unreachable!("expected invalid json")
+    };
+    assert_eq!(&raw[..], b"{not json");
+    let Some(Diagnostic::CmdParse { cmd, raw, .. }) = rx.next().await else {
+        unreachable!("expected cmd parse error")
+    };
+    assert_eq!(cmd, "DANMU_MSG");
+    assert!(raw.contains("bad"));
+    stream.abort();
+}
+
+#[tokio::test]
+async fn handshake_timeout_test() {
+    use crate::connection::{ConnectConfig, Connection, WsConnectError};
+    use crate::Auth;
+    // 只接受TCP连接，不进行websocket握手
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind error");
+    let url = format!("ws://{}/sub", listener.local_addr().expect("no local addr"));
+    let _accept = tokio::spawn(async move { listener.accept().await });
+    let config = ConnectConfig {
+        handshake_timeout: Some(std::time::Duration::from_millis(100)),
+        ..Default::default()
+    };
+    let result = Connection::connect(url, Auth::new(0, 1, None), &config).await;
+    assert!(matches!(result, Err(WsConnectError::HandshakeTimeout)));
+}
+
+#[tokio::test]
+async fn heartbeat_rtt_test() {
+    let server = MockServer::start_with_popularity(vec![], 42)
+        .await
+        .expect("start mock server error");
+    let mut stream = server
+        .connect(&Default::default())
+        .await
+        .expect("connect error");
+    // 连接后立即发出第一个心跳，不消费事件流也会在读到回复时记录往返时间
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    let rtt = stream.rtt().expect("rtt should be measured on receive");
+    assert!(rtt < std::time::Duration::from_millis(200));
+    assert_eq!(stream.get_popularity(), Some(42));
+    assert_eq!(*stream.subscribe_popularity().borrow(), Some(42));
+    loop {
+        let event = stream
+            .next()
+            .await
+            .expect("stream ended")
+            .expect("stream error");
+        if let EventData::PopularityUpdateEvent(e) = event.data {
+            assert_eq!(e.popularity, 42);
+            break;
+        }
+    }
+    let stats = stream.stats();
+    assert!(stream.rtt().is_some());
+    assert_eq!(stats.rtt_samples, 1);
+    assert_eq!(stats.rtt, stats.avg_rtt);
+    // 鉴权包为1，第一个心跳为2
+    assert!(stats.sequence_out >= 2);
+    assert_eq!(stats.last_sequence_in, Some(1));
+    assert_eq!(stats.sequence_gaps, 0);
+    stream.abort();
+}
@@ -1,6 +1,145 @@
 #[cfg(test)]
-#[cfg(feature = "connect")]
+#[cfg(feature = "rt_tokio")]
+mod api_test;
+
+#[cfg(test)]
+#[cfg(feature = "protocol")]
 mod cmd_test;
 
 #[cfg(test)]
 mod connect_test;
+
+#[cfg(test)]
+#[cfg(feature = "connect")]
+mod credential_test;
+
+#[cfg(test)]
+#[cfg(feature = "connect")]
+mod dedup_test;
+
+#[cfg(test)]
+#[cfg(feature = "event")]
+mod event_test;
+
+#[cfg(test)]
+#[cfg(feature = "event")]
+mod filter_test;
+
+#[cfg(test)]
+#[cfg(feature = "event")]
+mod handler_test;
+
+#[cfg(test)]
+#[cfg(feature = "rt_tokio")]
+mod http_test;
+
+#[cfg(test)]
+#[cfg(feature = "connect")]
+mod login_test;
+
+#[cfg(test)]
+#[cfg(feature = "event")]
+mod stats_test;
+
+#[cfg(test)]
+#[cfg(feature = "test-util")]
+mod mock_server_test;
+
+#[cfg(test)]
+#[cfg(feature = "event")]
+mod middleware_test;
+
+#[cfg(test)]
+#[cfg(feature = "open_live")]
+mod open_live_test;
+
+#[cfg(test)]
+#[cfg(feature = "protocol")]
+mod packet_test;
+
+#[cfg(test)]
+#[cfg(feature = "record")]
+mod record_test;
+
+#[cfg(test)]
+#[cfg(feature = "rt_tokio")]
+mod retry_test;
+
+#[cfg(test)]
+#[cfg(feature = "connect")]
+mod sender_test;
+
+#[cfg(test)]
+#[cfg(feature = "event")]
+mod text_test;
+
+#[cfg(test)]
+#[cfg(any(
+    feature = "sqlite",
+    feature = "grpc",
+    feature = "sse",
+    feature = "webhook"
+))]
+mod sink_test;
+
+#[cfg(feature = "event")]
+use crate::{
+    event::{DanmakuEvent, Event, EventData, GiftEvent},
+    model::{CoinType, DanmakuMessage, Gift, User},
+};
+
+/// 测试中共用的用户，用户名为`user{uid}`
+#[cfg(feature = "event")]
+fn user(uid: u64) -> User {
+    User {
+        uid,
+        uname: format!("user{uid}"),
+        face: None,
+    }
+}
+
+/// 测试中共用的普通弹幕，没有粉丝牌
+#[cfg(feature = "event")]
+fn danmaku(uid: u64, message: &str) -> DanmakuEvent {
+    DanmakuEvent {
+        flag: 0,
+        message: DanmakuMessage::Plain {
+            message: message.to_owned(),
+        },
+        user: user(uid),
+        fans_medal: None,
+        guard_level: 0,
+        user_level: 0,
+        mode: 1,
+        font_size: 25,
+        color: 0xFFFFFF,
+    }
+}
+
+/// 测试中共用的礼物，`price`为单价
+#[cfg(feature = "event")]
+fn gift(uid: u64, coin_type: CoinType, price: u64, num: u64) -> GiftEvent {
+    GiftEvent {
+        user: user(uid),
+        fans_medal: None,
+        blindbox: None,
+        gift: Gift {
+            coin_type,
+            coin_count: price * num,
+            action: "投喂".to_owned(),
+            gift_name: "礼物".to_owned(),
+            gift_id: 1,
+            num,
+            price,
+        },
+    }
+}
+
+/// 指定本地时间戳的事件
+#[cfg(feature = "event")]
+fn at(timestamp: u64, data: impl Into<EventData>) -> Event {
+    Event {
+        timestamp,
+        ..Event::from(data.into())
+    }
+}
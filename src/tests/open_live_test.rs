@@ -0,0 +1,28 @@
+#[test]
+fn open_live_sign_test() {
+    use crate::open_live::sign_headers;
+    let body = r#"{"code":"ABCDEF","app_id":1}"#;
+    let headers = sign_headers(
+        "key_id",
+        "secret",
+        body,
+        1700000000,
+        "1700000000000".to_owned(),
+    );
+    let header = |key: &str| {
+        headers
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value.as_str())
+    };
+    assert_eq!(
+        header("x-bili-content-md5"),
+        Some("ea39eba8644ad6f45b52f02699c7518d")
+    );
+    assert_eq!(header("x-bili-timestamp"), Some("1700000000"));
+    // 与独立实现（python的hmac模块）计算的结果一致
+    assert_eq!(
+        header("Authorization"),
+        Some("c7146419f3de9d9e398c5ae4a5f9e7242e639ca1188393a41c2f935d49e0d5be")
+    );
+}
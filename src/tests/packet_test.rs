@@ -0,0 +1,159 @@
+use crate::packet::{Operation, PacketParseError, RawPacket};
+
+#[test]
+fn packet_roundtrip_test() {
+    let bin = RawPacket::build(Operation::Auth, b"{}".to_vec()).ser();
+    let packet = RawPacket::from_buffer(&bin).expect("parse packet error");
+    assert_eq!(packet.head().opcode, Operation::Auth as u32);
+    assert_eq!(packet.body(), b"{}");
+}
+
+#[test]
+fn malformed_packet_test() {
+    let bin = RawPacket::heartbeat().ser();
+    assert!(matches!(
+        RawPacket::from_buffer(&bin[..20]),
+        Err(PacketParseError::TooShort {
+            expected: 31,
+            actual: 20
+        })
+    ));
+    // size为0时不能死循环
+    let mut zero_size = bin.clone();
+    zero_size[..4].copy_from_slice(&0_u32.to_be_bytes());
+    assert!(matches!(
+        RawPacket::from_buffer(&zero_size),
+        Err(PacketParseError::InvalidHead(_))
+    ));
+}
+
+#[test]
+fn from_buffers_test() {
+    let first = RawPacket::build(Operation::SendMsgReply, b"a".to_vec()).ser();
+    let second = RawPacket::build(Operation::SendMsgReply, b"b".to_vec()).ser();
+    let mut corrupted = RawPacket::build(Operation::SendMsgReply, b"x".to_vec()).ser();
+    corrupted[..4].copy_from_slice(&0_u32.to_be_bytes());
+    let mut buffer = [first.clone(), corrupted, second.clone()].concat();
+    // 截断的最后一个包
+    buffer.extend_from_slice(&first[..10]);
+    let results: Vec<_> = RawPacket::from_buffers(buffer.into()).collect();
+    assert_eq!(results.len(), 4);
+    assert_eq!(results[0].as_ref().expect("first packet").body(), b"a");
+    assert!(matches!(results[1], Err(PacketParseError::InvalidHead(_))));
+    assert_eq!(results[2].as_ref().expect("second packet").body(), b"b");
+    assert!(matches!(results[3], Err(PacketParseError::TooShort { .. })));
+}
+
+#[test]
+fn auth_builder_test() {
+    use crate::packet::Auth;
+    let auth = Auth::new(1, 510, Some("token".to_owned()))
+        .with_protover(1)
+        .with_platform("android")
+        .with_buvid("XY123");
+    let json: serde_json::Value = serde_json::from_slice(&auth.ser()).expect("json parse error");
+    assert_eq!(json["protover"], 1);
+    assert_eq!(json["platform"], "android");
+    assert_eq!(json["type"], 2);
+    assert_eq!(json["buvid"], "XY123");
+}
+
+#[test]
+fn scan_cmd_test() {
+    use crate::packet::scan_cmd;
+    assert_eq!(
+        scan_cmd(br#"{"cmd":"DANMU_MSG","info":[]}"#),
+        Some("DANMU_MSG")
+    );
+    assert_eq!(
+        scan_cmd(br#"{"data":{}, "cmd": "SEND_GIFT"}"#),
+        Some("SEND_GIFT")
+    );
+    assert_eq!(scan_cmd(br#"{"cmd":"A\"B"}"#), None);
+    assert_eq!(scan_cmd(br#"{"code":0}"#), None);
+}
+
+#[test]
+fn decoder_test() {
+    use crate::{decoder::Decoder, event::EventData, filter::CmdFilter};
+    let json = serde_json::json!({ "cmd": "WATCHED_CHANGE", "data": { "num": 7 } });
+    let frame = RawPacket::build(Operation::SendMsgReply, json.to_string().into_bytes())
+        .with_proto_code(0)
+        .ser();
+    let events = Decoder::new().decode(&frame).expect("decode error");
+    assert!(
+        matches!(&events[..], [event] if matches!(event.data, EventData::WatchedUpdateEvent(ref e) if e.num == 7))
+    );
+    let decoder = Decoder::new().with_cmd_filter(CmdFilter::new().ignore(["WATCHED_CHANGE"]));
+    assert!(decoder.decode(&frame).expect("decode error").is_empty());
+
+    // 自定义的事件类型，只保留需要的消息
+    #[derive(Debug, PartialEq)]
+    struct Watched(u64);
+    impl TryFrom<crate::Cmd> for Watched {
+        type Error = ();
+        fn try_from(cmd: crate::Cmd) -> Result<Self, ()> {
+            match cmd {
+                crate::Cmd::WatchedChange { num } => Ok(Watched(num)),
+                _ => Err(()),
+            }
+        }
+    }
+    let watched: Vec<Watched> = Decoder::new().decode_as(&frame).expect("decode error");
+    assert_eq!(watched, [Watched(7)]);
+    let events: Vec<crate::event::Event> = Decoder::new().decode_as(&frame).expect("decode error");
+    assert_eq!(events.len(), 1);
+}
+
+#[test]
+fn auth_reply_code_test() {
+    use crate::packet::{Operation, RawPacket};
+    let ok = RawPacket::build(Operation::AuthReply, br#"{"code":0}"#.to_vec());
+    assert_eq!(ok.auth_reply_code(), Some(0));
+    let rejected = RawPacket::build(Operation::AuthReply, br#"{"code":-101}"#.to_vec());
+    assert_eq!(rejected.auth_reply_code(), Some(-101));
+    let heartbeat = RawPacket::build(Operation::HeartbeatReply, br#"{"code":1}"#.to_vec());
+    assert_eq!(heartbeat.auth_reply_code(), None);
+}
+
+#[test]
+fn cmd_parsers_test() {
+    use crate::{
+        decoder::Decoder,
+        event::{EventData, WatchedUpdateEvent},
+        parser::CmdParsers,
+    };
+    let frame = |json: serde_json::Value| {
+        RawPacket::build(Operation::SendMsgReply, json.to_string().into_bytes())
+            .with_proto_code(0)
+            .ser()
+    };
+    let banner = frame(serde_json::json!({ "cmd": "WIDGET_BANNER", "data": { "timestamp": 1 } }));
+    let watched = frame(serde_json::json!({ "cmd": "WATCHED_CHANGE", "data": { "num": 7 } }));
+    // 内置解析会忽略WIDGET_BANNER
+    assert!(Decoder::new()
+        .decode(&banner)
+        .expect("decode error")
+        .is_empty());
+    let decoder = Decoder::new().with_parsers(
+        CmdParsers::new()
+            .passthrough(["WIDGET_BANNER"])
+            .register("WATCHED_CHANGE", |json| {
+                let num = json["data"]["num"].as_u64()? * 10;
+                Some(EventData::from(WatchedUpdateEvent { num }).into())
+            }),
+    );
+    let events = decoder.decode(&banner).expect("decode error");
+    let [event] = &events[..] else {
+        unreachable!("unexpected events {:?}", events)
+    };
+    let EventData::CustomEvent(custom) = &event.data else {
+        unreachable!("unexpected event {:?}", event.data)
+    };
+    assert_eq!(custom.cmd, "WIDGET_BANNER");
+    assert_eq!(custom.data["timestamp"], 1);
+    let events = decoder.decode(&watched).expect("decode error");
+    assert!(
+        matches!(&events[..], [event] if matches!(event.data, EventData::WatchedUpdateEvent(ref e) if e.num == 70))
+    );
+}
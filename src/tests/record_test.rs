@@ -0,0 +1,76 @@
+use crate::{
+    event::{Event, EventData, WatchedUpdateEvent},
+    record::{RecordConfig, Recorder, ReplaySource},
+};
+use std::path::{Path, PathBuf};
+
+/// 每个测试独占的临时目录，用进程号和纳秒时间戳区分，结束时删除
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("system time before unix epoch")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!(
+            "bilive-danmaku-{name}-{}-{nanos}",
+            std::process::id()
+        ));
+        Self(dir)
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn record_rotate_test() {
+    let dir = TempDir::new("record-test");
+    let config = RecordConfig {
+        max_size: Some(1),
+        max_duration: None,
+    };
+    let mut recorder = Recorder::new(dir.path(), 851181, config).expect("create recorder error");
+    for num in 0..3 {
+        let event: Event = EventData::from(WatchedUpdateEvent { num }).into();
+        recorder.record(&event).expect("record error");
+    }
+    drop(recorder);
+    let files = std::fs::read_dir(dir.path())
+        .expect("read dir error")
+        .count();
+    assert_eq!(files, 3);
+}
+
+#[test]
+fn replay_test() {
+    let dir = TempDir::new("replay-test");
+    let mut recorder =
+        Recorder::new(dir.path(), 851181, RecordConfig::default()).expect("create recorder error");
+    for num in 0..3 {
+        let event: Event = EventData::from(WatchedUpdateEvent { num }).into();
+        recorder.record(&event).expect("record error");
+    }
+    let path = recorder.path().to_owned();
+    drop(recorder);
+    let replay = ReplaySource::open(path).expect("open replay error");
+    let nums = replay
+        .map(|record| {
+            let record = record.expect("replay error");
+            assert_eq!(record.roomid, 851181);
+            match record.event.data {
+                EventData::WatchedUpdateEvent(WatchedUpdateEvent { num }) => Some(num),
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(nums, vec![Some(0), Some(1), Some(2)]);
+}
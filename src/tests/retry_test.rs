@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+#[tokio::test]
+async fn retry_policy_test() {
+    use crate::retry::{ErrorClass, RetryPolicy};
+    use crate::{ConnectError, InitError};
+    let policy = RetryPolicy::default()
+        .with_delay(Duration::from_millis(10), Duration::from_millis(50))
+        .with_jitter(0.0)
+        .with_max_attempts(3);
+    assert_eq!(policy.delay_for(1), Duration::from_millis(10));
+    assert_eq!(policy.delay_for(3), Duration::from_millis(40));
+    assert_eq!(policy.delay_for(10), Duration::from_millis(50));
+    let server_error = InitError::HttpStatus(reqwest::StatusCode::BAD_GATEWAY);
+    assert!(policy.should_retry(&server_error, 2));
+    assert!(!policy.should_retry(&server_error, 3));
+    assert!(!policy.should_retry(&ConnectError::HostListIsEmpty, 1));
+    let api_error = InitError::ApiError {
+        code: -400,
+        message: String::new(),
+    };
+    assert!(!policy.should_retry(&api_error, 1));
+    let policy = policy.with_retryable(&[ErrorClass::Api]);
+    assert!(policy.should_retry(&api_error, 1));
+
+    let mut attempts = 0;
+    let result: Result<(), InitError> = policy
+        .retry(|| {
+            attempts += 1;
+            async {
+                Err(InitError::ApiError {
+                    code: -400,
+                    message: String::new(),
+                })
+            }
+        })
+        .await;
+    assert!(result.is_err());
+    assert_eq!(attempts, 3);
+}
@@ -0,0 +1,7 @@
+#[test]
+fn split_message_test() {
+    use crate::sender::split_message;
+    assert_eq!(split_message("  你好  ", 20), vec!["你好"]);
+    assert_eq!(split_message("一二三四五", 2), vec!["一二", "三四", "五"]);
+    assert!(split_message("   ", 20).is_empty());
+}
@@ -0,0 +1,187 @@
+use crate::event::{Event, EventData};
+
+fn danmaku() -> Event {
+    EventData::from(super::danmaku(10086, "hello")).into()
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn sqlite_sink_test() {
+    use crate::event::WatchedUpdateEvent;
+    let mut sink = crate::sink::SqliteSink::open_in_memory().expect("open sqlite error");
+    let danmaku = danmaku();
+    let watched: Event = EventData::from(WatchedUpdateEvent { num: 1 }).into();
+    let count = sink
+        .write_all(851181, [&danmaku, &watched])
+        .expect("write error");
+    assert_eq!(count, 1);
+    let message: String = sink
+        .connection()
+        .query_row(
+            "SELECT message FROM danmaku WHERE roomid = ?1 AND uid = ?2",
+            [851181, 10086],
+            |row| row.get(0),
+        )
+        .expect("query error");
+    assert_eq!(message, "hello");
+}
+
+#[test]
+#[cfg(feature = "grpc")]
+fn grpc_sink_test() {
+    use crate::{
+        event::{CustomEvent, WatchedUpdateEvent},
+        sink::{proto, to_proto, GrpcSink},
+    };
+    let event = to_proto(851181, &danmaku());
+    assert_eq!(event.cmd, "DanmakuEvent");
+    let Some(proto::event::Data::Danmaku(data)) = event.data else {
+        unreachable!("DanmakuEvent should be converted to Danmaku");
+    };
+    assert_eq!(data.message, "hello");
+    assert_eq!(data.user.map(|u| u.uid), Some(10086));
+    let watched: Event = EventData::from(WatchedUpdateEvent { num: 1 }).into();
+    let event = to_proto(851181, &watched);
+    assert_eq!(
+        event.data,
+        Some(proto::event::Data::WatchedUpdate(proto::WatchedUpdate {
+            num: 1
+        }))
+    );
+    // 没有固定结构的事件以json传输
+    let custom: Event = EventData::from(CustomEvent {
+        cmd: "NEW_CMD".to_owned(),
+        data: serde_json::json!({"num": 1}),
+    })
+    .into();
+    let event = to_proto(851181, &custom);
+    assert_eq!(event.cmd, "CustomEvent");
+    assert_eq!(
+        event.data,
+        Some(proto::event::Data::Json(r#"{"num":1}"#.to_owned()))
+    );
+    // 没有订阅者
+    assert_eq!(
+        GrpcSink::new(16)
+            .publish(851181, &watched)
+            .expect("publish error"),
+        0
+    );
+}
+
+#[tokio::test]
+#[cfg(feature = "sse")]
+async fn sse_sink_test() {
+    use crate::sink::SseSink;
+    let sink = SseSink::new(16);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind error");
+    let addr = listener.local_addr().expect("addr error");
+    let router = sink.router();
+    let server = tokio::spawn(async move { axum::serve(listener, router).await });
+    let mut response = reqwest::get(format!("http://{addr}/rooms/851181/events"))
+        .await
+        .expect("request error");
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    // 响应头返回时已经订阅
+    assert_eq!(sink.publish(851181, &danmaku()).expect("publish error"), 1);
+    assert_eq!(sink.publish(10086, &danmaku()).expect("publish error"), 0);
+    let chunk = response
+        .chunk()
+        .await
+        .expect("read error")
+        .expect("stream ended");
+    let text = String::from_utf8_lossy(&chunk);
+    assert!(text.starts_with("event: DanmakuEvent\ndata: {"), "{text}");
+    assert!(text.contains(r#""uid":10086"#), "{text}");
+    assert_eq!(sink.room_count(), 1);
+    // 最后一个订阅者断开后释放直播间
+    drop(response);
+    tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while sink.room_count() > 0 {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("room should be released after the subscriber disconnects");
+    server.abort();
+}
+
+/// 对每个请求都返回`status`的http服务，每收到一个请求发送一次请求体
+#[cfg(feature = "webhook")]
+async fn webhook_server(
+    status: u16,
+) -> (
+    std::net::SocketAddr,
+    tokio::sync::mpsc::UnboundedReceiver<String>,
+) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind error");
+    let addr = listener.local_addr().expect("addr error");
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let mut request = vec![0; 65536];
+            let Ok(len) = socket.read(&mut request).await else {
+                continue;
+            };
+            let request = String::from_utf8_lossy(&request[..len]).into_owned();
+            let body = request
+                .split_once("\r\n\r\n")
+                .map(|(_, body)| body.to_owned())
+                .unwrap_or_default();
+            let _ = tx.send(body);
+            let response =
+                format!("HTTP/1.1 {status} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+            let _ = socket.write_all(response.as_bytes()).await;
+        }
+    });
+    (addr, rx)
+}
+
+#[tokio::test]
+#[cfg(feature = "webhook")]
+async fn webhook_sink_test() {
+    use crate::sink::{SinkError, WebhookSink};
+    use std::time::Duration;
+    // 缓存满时丢弃最早的事件
+    let mut sink = WebhookSink::new("http://127.0.0.1:1/")
+        .with_batch_size(10)
+        .with_max_buffered(2);
+    for _ in 0..3 {
+        sink.send(danmaku()).await.expect("send error");
+    }
+    assert_eq!(sink.dropped(), 1);
+
+    // 4xx不重试，丢弃这一批
+    let (addr, mut requests) = webhook_server(400).await;
+    let mut sink = WebhookSink::new(format!("http://{addr}/"))
+        .with_batch_size(2)
+        .with_retry(3, Duration::from_millis(10));
+    sink.send(danmaku()).await.expect("send error");
+    let error = sink.send(danmaku()).await.expect_err("should be rejected");
+    assert!(matches!(error, SinkError::HttpStatus(status) if status.as_u16() == 400));
+    assert_eq!(sink.dropped(), 2);
+    requests.recv().await.expect("request");
+    assert!(requests.try_recv().is_err());
+
+    // 没有新事件时也按max_delay发送
+    let (addr, mut requests) = webhook_server(200).await;
+    let mut sink = WebhookSink::new(format!("http://{addr}/"))
+        .with_batch_size(10)
+        .with_max_delay(Duration::from_millis(50));
+    let events = futures_util::StreamExt::chain(
+        futures_util::stream::iter([danmaku()]),
+        futures_util::stream::pending(),
+    );
+    let forward = tokio::spawn(async move { sink.forward(Box::pin(events)).await });
+    let body = tokio::time::timeout(Duration::from_secs(5), requests.recv())
+        .await
+        .expect("should flush on timer")
+        .expect("request");
+    assert!(body.contains(r#""uid":10086"#), "{body}");
+    forward.abort();
+}
@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use super::{at, danmaku, gift, user};
+use crate::{
+    event::{EventData, GuardBuyEvent, PopularityUpdateEvent, SuperChatEvent, WatchedUpdateEvent},
+    model::CoinType,
+    stats::{event_value, PopularityHistory, RevenueTracker, RollingStats},
+};
+
+#[test]
+fn popularity_history_test() {
+    let mut history = PopularityHistory::new(Duration::from_secs(60), Duration::from_secs(300));
+    let popularity =
+        |timestamp: u64, popularity: u32| at(timestamp, PopularityUpdateEvent { popularity });
+    assert!(history.push(&popularity(0, 10)));
+    assert!(history.push(&popularity(30_000, 30)));
+    assert!(history.push(&popularity(90_000, 50)));
+    assert!(!history.push(&EventData::from(WatchedUpdateEvent { num: 1 }).into()));
+    // 乱序的值合并到对应的采样点
+    history.record(45_000, 20);
+    let samples: Vec<_> = history.samples().copied().collect();
+    assert_eq!(samples.len(), 2);
+    assert_eq!(
+        (samples[0].min, samples[0].max, samples[0].count),
+        (10, 30, 3)
+    );
+    assert_eq!(samples[0].avg(), 20.0);
+    let summary = history.summary_since(0).expect("no samples");
+    assert_eq!((summary.min, summary.max, summary.count), (10, 50, 4));
+    assert_eq!(summary.avg, 27.5);
+    assert_eq!(history.summary_since(60_000).map(|s| s.max), Some(50));
+    // 超出保留时长的采样点被丢弃
+    history.record(300_000, 40);
+    assert_eq!(history.samples().next().map(|s| s.timestamp), Some(60_000));
+    assert_eq!(history.latest().map(|s| s.max), Some(40));
+}
+
+#[test]
+fn rolling_stats_test() {
+    let mut stats =
+        RollingStats::new(Duration::from_secs(120)).with_emit_interval(Duration::from_secs(60));
+    assert!(stats.push(&at(0, danmaku(1, "hello"))).is_none());
+    assert!(stats.push(&at(10_000, danmaku(1, "hello"))).is_none());
+    assert!(stats.push(&at(20_000, danmaku(2, "hello"))).is_none());
+    assert!(stats
+        .push(&at(30_000, gift(1, CoinType::Gold, 1000, 2)))
+        .is_none());
+    assert!(stats
+        .push(&at(30_000, gift(1, CoinType::Silver, 100, 1)))
+        .is_none());
+    let super_chat = SuperChatEvent {
+        id: 1,
+        user: user(3),
+        fans_medal: None,
+        price: 30,
+        message: "hi".to_owned(),
+        message_jpn: None,
+    };
+    assert!(stats.push(&at(40_000, super_chat)).is_none());
+    let emitted = stats
+        .push(&at(60_000, WatchedUpdateEvent { num: 1 }))
+        .expect("stats event should be emitted");
+    let EventData::StatsEvent(snapshot) = emitted.data else {
+        unreachable!("RollingStats should emit StatsEvent");
+    };
+    assert_eq!(emitted.timestamp, 60_000);
+    assert_eq!((snapshot.window, snapshot.danmaku_count), (120, 3));
+    assert_eq!(snapshot.danmaku_per_minute, 1.5);
+    assert_eq!(snapshot.unique_chatters, 2);
+    assert_eq!(snapshot.gift_gold, 2000);
+    assert_eq!(
+        (snapshot.super_chat_count, snapshot.super_chat_price),
+        (1, 30)
+    );
+    // 前两条弹幕移出窗口
+    let snapshot = stats.snapshot_at(135_000);
+    assert_eq!((snapshot.danmaku_count, snapshot.unique_chatters), (1, 1));
+    assert_eq!(snapshot.gift_gold, 2000);
+    let snapshot = stats.snapshot_at(200_000);
+    assert_eq!(
+        (
+            snapshot.danmaku_count,
+            snapshot.gift_gold,
+            snapshot.super_chat_count
+        ),
+        (0, 0, 0)
+    );
+}
+
+#[test]
+fn revenue_test() {
+    let guard = GuardBuyEvent {
+        level: 3,
+        price: 198000,
+        user: user(2),
+    };
+    assert_eq!(
+        event_value(&gift(1, CoinType::Silver, 100, 5).into()),
+        Some(0)
+    );
+    assert_eq!(event_value(&danmaku(1, "hello").into()), None);
+    let mut revenue = RevenueTracker::new();
+    assert_eq!(
+        revenue.push(&at(0, gift(1, CoinType::Gold, 100, 5))),
+        Some(500)
+    );
+    assert_eq!(revenue.push(&at(0, guard)), Some(198000));
+    assert_eq!(revenue.push(&at(0, danmaku(1, "hello"))), None);
+    let top = revenue.top_users(1);
+    assert_eq!(top.len(), 1);
+    assert_eq!(top[0].user.uid, 2);
+    assert_eq!(revenue.user(1).map(|u| u.revenue.gift), Some(500));
+    let total = revenue.reset();
+    assert_eq!(
+        (total.gift, total.guard, total.super_chat),
+        (500, 198000, 0)
+    );
+    assert_eq!(total.cny(), 198.5);
+    assert_eq!(revenue.total().total(), 0);
+    assert!(revenue.user(1).is_none());
+}
@@ -0,0 +1,39 @@
+use std::borrow::Cow;
+
+use crate::{
+    event::{Event, EventData},
+    text::{normalize, strip_invisible, to_halfwidth, unescape_html, TextNormalizer},
+};
+
+#[test]
+fn text_normalize_test() {
+    assert!(matches!(unescape_html("没有实体"), Cow::Borrowed(_)));
+    assert_eq!(
+        unescape_html("&lt;b&gt; &amp;&#39;&#x4f60; &unknown; &"),
+        "<b> &'你 &unknown; &"
+    );
+    assert_eq!(strip_invisible("a\u{200b}b\u{feff}\nc\u{7}"), "ab c");
+    assert_eq!(to_halfwidth("ＡＢＣ１２３！\u{3000}中文"), "ABC123! 中文");
+    // 转义后的零宽字符同样被去掉
+    assert_eq!(normalize(" ｈｉ&#8203;&amp;\t "), "ｈｉ&");
+    assert_eq!(
+        TextNormalizer::default()
+            .with_halfwidth(true)
+            .normalize("ＯＫ&amp;"),
+        "OK&"
+    );
+}
+
+#[test]
+fn text_normalize_event_test() {
+    let danmaku = super::danmaku(1, "你好！\u{200b}");
+    let middleware = TextNormalizer::default().middleware();
+    let Some(event) = middleware(Event::from(EventData::from(danmaku))) else {
+        unreachable!("event should be kept")
+    };
+    let EventData::DanmakuEvent(danmaku) = event.data else {
+        unreachable!("unexpected event {:?}", event.data)
+    };
+    // 默认不改写中文的全角标点
+    assert_eq!(danmaku.message.to_string(), "你好！");
+}
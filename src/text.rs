@@ -0,0 +1,213 @@
+//! 弹幕文本的清理
+//!
+//! 弹幕中常见html转义（`&lt;`）、零宽字符和全角字母数字，显示或统计前通常需要清理
+//!```no_run,ignore
+//!use bilive_danmaku::{middleware::Middlewares, text::TextNormalizer};
+//!assert_eq!(bilive_danmaku::text::normalize("hello\u{200b}&amp;"), "hello&");
+//!// 全角转半角会改写中文的全角标点，需要时单独开启
+//!assert_eq!(TextNormalizer::default().with_halfwidth(true).normalize("ｈｅｌｌｏ！"), "hello!");
+//!// 作为中间件清理所有事件中的文本
+//!connector.config.middlewares = Middlewares::new().then(TextNormalizer::default().middleware());
+//!```
+use std::borrow::Cow;
+
+use crate::{
+    event::{Event, EventData},
+    model::DanmakuMessage,
+};
+
+/// 反转义html实体，支持常见的命名实体和`&#数字;`、`&#x十六进制;`，无法识别的实体保持原样
+pub fn unescape_html(text: &str) -> Cow<'_, str> {
+    if !text.contains('&') {
+        return Cow::Borrowed(text);
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        // 实体连同`&`和`;`不超过12个字符
+        let entity = rest
+            .char_indices()
+            .take(12)
+            .find(|(_, c)| *c == ';')
+            .and_then(|(end, _)| Some((decode_entity(&rest[1..end])?, end)));
+        match entity {
+            Some((c, end)) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    Cow::Owned(result)
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let code = entity.strip_prefix('#')?;
+            let code = match code.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => code.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+/// 零宽字符、双向文本控制符和软连字符，显示时不可见，常被用来绕过屏蔽词
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00ad}'
+            | '\u{200b}'..='\u{200f}'
+            | '\u{202a}'..='\u{202e}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{2069}'
+            | '\u{feff}'
+    )
+}
+
+/// 去掉不可见字符和控制字符，换行和制表符替换为空格
+pub fn strip_invisible(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(|c| is_invisible(c) || c.is_control()) {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(
+        text.chars()
+            .filter(|c| !is_invisible(*c))
+            .filter_map(|c| match c {
+                '\n' | '\r' | '\t' => Some(' '),
+                c if c.is_control() => None,
+                c => Some(c),
+            })
+            .collect(),
+    )
+}
+
+/// 全角的ascii字符（U+FF01到U+FF5E）和全角空格转换为半角
+///
+/// 常用的全角标点（如`，`、`！`）也在这个范围内，需要保留时不要使用
+pub fn to_halfwidth(text: &str) -> Cow<'_, str> {
+    fn convert(c: char) -> char {
+        match c {
+            '\u{3000}' => ' ',
+            '\u{ff01}'..='\u{ff5e}' => char::from_u32(c as u32 - 0xfee0).unwrap_or(c),
+            c => c,
+        }
+    }
+    if !text.chars().any(|c| convert(c) != c) {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(text.chars().map(convert).collect())
+}
+
+/// 使用默认的[`TextNormalizer`]清理文本
+pub fn normalize(text: &str) -> String {
+    TextNormalizer::default().normalize(text)
+}
+
+/// 组合几种清理方式，默认启用`halfwidth`以外的清理方式
+///
+/// # 说明
+/// - `html` 反转义html实体，见[`unescape_html`]
+/// - `invisible` 去掉不可见字符和控制字符，见[`strip_invisible`]
+/// - `halfwidth` 全角字符转为半角，见[`to_halfwidth`]，会把中文的全角标点也转为半角，默认不启用
+/// - `trim` 去掉首尾的空白
+#[derive(Debug, Clone, Copy)]
+pub struct TextNormalizer {
+    pub html: bool,
+    pub invisible: bool,
+    pub halfwidth: bool,
+    pub trim: bool,
+}
+
+impl Default for TextNormalizer {
+    fn default() -> Self {
+        Self {
+            html: true,
+            invisible: true,
+            halfwidth: false,
+            trim: true,
+        }
+    }
+}
+
+impl TextNormalizer {
+    pub fn with_html(mut self, html: bool) -> Self {
+        self.html = html;
+        self
+    }
+
+    pub fn with_invisible(mut self, invisible: bool) -> Self {
+        self.invisible = invisible;
+        self
+    }
+
+    pub fn with_halfwidth(mut self, halfwidth: bool) -> Self {
+        self.halfwidth = halfwidth;
+        self
+    }
+
+    pub fn with_trim(mut self, trim: bool) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    pub fn normalize(&self, text: &str) -> String {
+        let mut text = text.to_owned();
+        // 先反转义，转义后的不可见字符也会被去掉
+        if self.html {
+            text = unescape_html(&text).into_owned();
+        }
+        if self.invisible {
+            text = strip_invisible(&text).into_owned();
+        }
+        if self.halfwidth {
+            text = to_halfwidth(&text).into_owned();
+        }
+        if self.trim {
+            text = text.trim().to_owned();
+        }
+        text
+    }
+
+    /// 清理弹幕、表情的替代文字和醒目留言的文本，其他事件不修改
+    pub fn apply(&self, event: &mut Event) {
+        match &mut event.data {
+            EventData::DanmakuEvent(e) => match &mut e.message {
+                DanmakuMessage::Plain { message } => *message = self.normalize(message),
+                DanmakuMessage::Emoticon { alt_message, .. } => {
+                    *alt_message = self.normalize(alt_message)
+                }
+                DanmakuMessage::Voice { .. } => {}
+            },
+            EventData::SuperChatEvent(e) => {
+                e.message = self.normalize(&e.message);
+                if let Some(message_jpn) = &mut e.message_jpn {
+                    *message_jpn = self.normalize(message_jpn);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 作为[`Middlewares`](crate::middleware::Middlewares)中的一环使用
+    pub fn middleware(self) -> impl Fn(Event) -> Option<Event> + Send + Sync + 'static {
+        move |mut event| {
+            self.apply(&mut event);
+            Some(event)
+        }
+    }
+}